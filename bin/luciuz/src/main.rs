@@ -1,4 +1,8 @@
+use std::future::Future;
+use std::io;
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
 
 use axum::error_handling::HandleErrorLayer;
@@ -13,15 +17,23 @@ use axum::{
         },
         HeaderMap, HeaderValue, Method, Request, StatusCode, Uri,
     },
-    middleware::{from_fn_with_state, Next},
+    middleware::{from_fn, from_fn_with_state, Next},
     response::{IntoResponse, Redirect, Response},
-    routing::{get, get_service},
+    routing::get,
     Router,
 };
-use clap::{Parser, Subcommand};
+use axum_server::accept::{Accept, DefaultAcceptor};
+use clap::{Parser, Subcommand, ValueEnum};
+use luciuz_config::{AcmeChallenge, Profile};
+use luciuz_control::{live_router, reload_on_sighup, Reloader};
+use luciuz_proxy::{
+    admin_mw, client_ip_mw, compression_mw, error_pages_mw, request_id_mw, AdminState, ClientInfo,
+    ClientIpState, CompressionState, ErrorPagesState, ProxyProtocolAcceptor,
+};
+use tokio::io::AsyncWriteExt;
+use tokio_rustls::LazyConfigAcceptor;
 use tower::timeout::TimeoutLayer;
 use tower::{BoxError, ServiceBuilder};
-use tower_http::services::ServeDir;
 use tracing::{info, warn};
 
 static COOP: HeaderName = HeaderName::from_static("cross-origin-opener-policy");
@@ -40,12 +52,98 @@ enum Command {
     Check {
         #[arg(short, long, default_value = "luciuz.toml")]
         config: String,
+        /// Report format: compiler-style diagnostics, or JSON for CI
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+        /// Also print the config with its includes merged (`${...}`
+        /// references are left unresolved)
+        #[arg(long)]
+        merged: bool,
+    },
+    /// Report security practices the config falls short of, by rule, with a
+    /// score; exits with status 1 while findings remain
+    Audit {
+        #[arg(short, long, default_value = "luciuz.toml")]
+        config: String,
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+        /// Do not report this rule, by id (A006) or name (no-canonical-host);
+        /// repeatable, added to `audit.allow`
+        #[arg(long, value_name = "RULE")]
+        allow: Vec<String>,
+        /// Print the rules and exit
+        #[arg(long)]
+        list_rules: bool,
     },
     /// Run server
     Run {
         #[arg(short, long, default_value = "luciuz.toml")]
         config: String,
     },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        cmd: ConfigCommand,
+    },
+    /// Translate an nginx config or a Caddyfile into luciuz.toml; what is
+    /// not translated is listed on stderr and in the file's header
+    Import {
+        #[arg(value_enum)]
+        source: ImportSource,
+        file: String,
+        /// Write the config here instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Contact address for ACME, when the source has none
+        #[arg(long)]
+        acme_email: Option<String>,
+    },
+    /// Hash a password read from the first line of stdin, for the
+    /// `password_hash` of upload and admin users
+    HashPassword,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the effective config: every value with defaults filled in and
+    /// where it comes from, secrets redacted
+    Dump {
+        #[arg(short, long, default_value = "luciuz.toml")]
+        config: String,
+        #[arg(long, value_enum, default_value_t = DumpFormat::Toml)]
+        format: DumpFormat,
+    },
+    /// Print the JSON Schema of the config file, for editors and generators
+    Schema,
+    /// Rewrite an older config and its includes to the current version,
+    /// keeping comments; lists the changes unless --write is given
+    Migrate {
+        #[arg(short, long, default_value = "luciuz.toml")]
+        config: String,
+        /// Write the changes to the files
+        #[arg(long)]
+        write: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ImportSource {
+    Nginx,
+    Caddy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum DumpFormat {
+    /// Values annotated with their origin in comments
+    Toml,
+    /// `{files, config, origins}`
+    Json,
 }
 
 #[tokio::main]
@@ -53,8 +151,42 @@ async fn main() -> Result<(), anyhow::Error> {
     let cli = Cli::parse();
 
     match cli.cmd {
-        Command::Check { config } => {
-            let cfg = luciuz_config::load_from_path(&config).map_err(|e| anyhow::anyhow!(e))?;
+        Command::Check {
+            config,
+            format,
+            merged,
+        } => {
+            let (cfg, report) =
+                luciuz_config::check_path(&config).map_err(|e| anyhow::anyhow!(e))?;
+            match format {
+                Format::Human => {
+                    eprint!("{}", report.render());
+                    if let Some(table) = report.merged().filter(|_| merged) {
+                        print!("{table}");
+                    }
+                }
+                Format::Json => {
+                    let mut out = serde_json::json!({
+                        "file": report.file,
+                        "files": report.files().collect::<Vec<_>>(),
+                        "valid": !report.has_errors(),
+                        "errors": report.errors().count(),
+                        "warnings": report.warnings().count(),
+                        "diagnostics": report.diagnostics,
+                    });
+                    if merged {
+                        out["merged"] = serde_json::to_value(report.merged())?;
+                    }
+                    println!("{}", serde_json::to_string_pretty(&out)?);
+                }
+            }
+            let cfg = match cfg {
+                Some(cfg) if !report.has_errors() => cfg,
+                _ => std::process::exit(1),
+            };
+            if format == Format::Json {
+                return Ok(());
+            }
             luciuz_telemetry::init(&cfg);
 
             info!("config ok");
@@ -62,7 +194,7 @@ async fn main() -> Result<(), anyhow::Error> {
                 info!(proxy_routes = ?p.routes, "proxy routes");
             }
             info!(
-                http_listen = %cfg.server.http_listen,
+                http_listen = ?cfg.server.http_listen,
                 https_listen = %cfg.server.https_listen,
                 profile = %cfg.server.profile,
                 acme_enabled = cfg.acme.enabled,
@@ -74,60 +206,193 @@ async fn main() -> Result<(), anyhow::Error> {
             Ok(())
         }
 
+        Command::Audit {
+            config,
+            format,
+            allow,
+            list_rules,
+        } => {
+            if list_rules {
+                for rule in luciuz_config::RULES {
+                    println!(
+                        "{} {:<24} {:<6}  {}",
+                        rule.id,
+                        rule.name,
+                        rule.risk.to_string(),
+                        rule.summary
+                    );
+                }
+                return Ok(());
+            }
+            if let Some(unknown) = allow.iter().find(|a| luciuz_config::rule(a).is_none()) {
+                anyhow::bail!("--allow: unknown rule {unknown:?} (see --list-rules)");
+            }
+            let (cfg, report) = load_or_exit(&config)?;
+            let audit = luciuz_config::audit(&cfg, &report, &allow);
+            match format {
+                Format::Human => print!("{}", audit.render()),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&audit)?),
+            }
+            if !audit.findings.is_empty() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+
+        Command::Config {
+            cmd: ConfigCommand::Dump { config, format },
+        } => {
+            let (cfg, report) = load_or_exit(&config)?;
+            let dump = luciuz_config::dump(&cfg, &report);
+            match format {
+                DumpFormat::Toml => print!("{}", dump.to_toml()),
+                DumpFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "files": report.files().collect::<Vec<_>>(),
+                        "config": dump.values(),
+                        "origins": dump.origins(),
+                    }))?
+                ),
+            }
+            Ok(())
+        }
+
+        Command::Config {
+            cmd: ConfigCommand::Schema,
+        } => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&luciuz_config::schema())?
+            );
+            Ok(())
+        }
+
+        Command::Config {
+            cmd: ConfigCommand::Migrate { config, write },
+        } => {
+            let files = luciuz_config::migrate(&config).map_err(|e| anyhow::anyhow!(e))?;
+            let changed: Vec<_> = files.iter().filter(|f| !f.changes.is_empty()).collect();
+            if changed.is_empty() {
+                println!(
+                    "{config} is up to date (config version {})",
+                    luciuz_config::CONFIG_VERSION
+                );
+                return Ok(());
+            }
+            for f in &changed {
+                for c in &f.changes {
+                    println!("{}:{}: {}", f.file, c.line, c.message);
+                }
+            }
+            if write {
+                for f in &changed {
+                    std::fs::write(&f.file, &f.text)
+                        .map_err(|e| anyhow::anyhow!("cannot write {}: {e}", f.file))?;
+                }
+                println!(
+                    "migrated {} file(s) to config version {}",
+                    changed.len(),
+                    luciuz_config::CONFIG_VERSION
+                );
+            } else {
+                println!("nothing written; rerun with --write to apply");
+            }
+            Ok(())
+        }
+
+        Command::Import {
+            source,
+            file,
+            output,
+            acme_email,
+        } => {
+            let text = std::fs::read_to_string(&file)
+                .map_err(|e| anyhow::anyhow!("cannot read {file}: {e}"))?;
+            let options = luciuz_import::Options { acme_email };
+            let import = match source {
+                ImportSource::Nginx => luciuz_import::nginx(&file, &text, &options),
+                ImportSource::Caddy => luciuz_import::caddy(&file, &text, &options),
+            }
+            .map_err(|e| anyhow::anyhow!(e))?;
+            for n in &import.notes {
+                eprintln!("{}", luciuz_import::located(&file, n));
+            }
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, &import.config)
+                        .map_err(|e| anyhow::anyhow!("cannot write {path}: {e}"))?;
+                    eprintln!("wrote {path} ({} note(s) to review)", import.notes.len());
+                }
+                None => print!("{}", import.config),
+            }
+            Ok(())
+        }
+        Command::HashPassword => {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            let password = line.trim_end_matches(['\r', '\n']);
+            if password.is_empty() {
+                anyhow::bail!("no password on stdin");
+            }
+            println!("{}", luciuz_proxy::hash_password(password)?);
+            Ok(())
+        }
+
         Command::Run { config } => {
-            let cfg = luciuz_config::load_from_path(&config).map_err(|e| anyhow::anyhow!(e))?;
+            let (cfg, report) = luciuz_config::load(&config).map_err(|e| anyhow::anyhow!(e))?;
             luciuz_telemetry::init(&cfg);
+            for d in report.warnings() {
+                warn!(code = %d.code, at = %report.location(d), "{}", d.message);
+            }
 
             if cfg.acme.enabled
-                && cfg.acme.challenge == "http-01"
-                && cfg.server.http_listen.trim().is_empty()
+                && cfg.acme.challenge == AcmeChallenge::Http01
+                && cfg.server.http_listen.is_none()
             {
                 return Err(anyhow::anyhow!(
                     "server.http_listen is empty but acme.challenge=http-01 requires port 80"
                 ));
             }
 
-            let http_addr: Option<SocketAddr> = if cfg.server.http_listen.trim().is_empty() {
-                None
+            let http_addr = cfg.server.http_listen;
+            let https_addr = cfg.server.https_listen;
+
+            info!(
+                http_listen = ?cfg.server.http_listen,
+                https_listen = %cfg.server.https_listen,
+                profile = %cfg.server.profile,
+                acme_enabled = cfg.acme.enabled,
+                "starting luciuz"
+            );
+
+            if cfg.acme.enabled {
+                run_https_with_acme_http01(&config, cfg, http_addr, https_addr).await?;
+            } else if let Some(http_addr) = http_addr {
+                let acceptor = ProxyProtocolAcceptor::new(DefaultAcceptor::new(), &cfg, "http")?;
+                let reloader = Reloader::new(
+                    &config,
+                    cfg,
+                    Box::new(|cfg| request_layers(app_router(cfg, "http")?, cfg, "http")),
+                )?;
+                start_control(&reloader).await?;
+                let app = live_router(reloader, |app| app);
+                axum_server::bind(http_addr)
+                    .acceptor(acceptor)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await?;
             } else {
-                Some(cfg.server.http_listen.parse()?)
-            };
-            let https_addr: SocketAddr = cfg.server.https_listen.parse()?;
-
-            // ✅ IMPORTANT: static_router is defined OUTSIDE the match, so it’s always in scope.
-            let static_router: axum::Router<()> = {
-                if cfg.server.profile == "static_site" {
-                    let s = cfg
-                        .static_site
-                        .as_ref()
-                        .expect("config validated: missing [static_site]");
-
-                    let service =
-                        get_service(ServeDir::new(&s.root).append_index_html_on_directories(true))
-                            .handle_error(|err| async move {
-                                tracing::error!(?err, "static file error");
-                                (StatusCode::INTERNAL_SERVER_ERROR, "static file error")
-                            });
-
-                    Router::new()
-                        .route("/healthz", get(|| async { "ok" }))
-                        .fallback_service(service)
-                } else {
-                    Router::new()
-                }
-            };
+                tracing::info!("HTTP listener disabled (server.http_listen is empty)");
+            }
+
+            warn!("server stopped");
+            Ok(())
+        }
+    }
+}
 
-            let app: axum::Router<()> = match cfg.server.profile.as_str() {
-                "static_site" => static_router,
-
-                "public_api" => {
-                    let mut r = luciuz_proxy::router(&cfg).map_err(|e| anyhow::anyhow!(e))?;
-                    r = r.route("/healthz", get(|| async { "ok" }));
-                    r.route(
-                        "/",
-                        get(|| async {
-                            Html(
-                                r#"<!doctype html>
+/// Built-in landing page, served at `/` when nothing else handles it.
+const LANDING_HTML: &str = r#"<!doctype html>
 <html lang="en">
 <head>
   <meta charset="utf-8" />
@@ -156,43 +421,75 @@ async fn main() -> Result<(), anyhow::Error> {
     <li>This server is intentionally strict (canonical host, security headers, HSTS).</li>
   </ul>
 </body>
-</html>"#,
-                            )
-                        }),
-                    )
-                }
-
-                _ => Router::new()
-                    .route("/healthz", get(|| async { "ok" }))
-                    .route(
-                        "/",
-                        get(|| async { Html("<h1>Luciuz is running</h1><p>Minimal landing.</p>") }),
-                    ),
-            };
-
-            info!(
-                http_listen = %cfg.server.http_listen,
-                https_listen = %cfg.server.https_listen,
-                profile = %cfg.server.profile,
-                acme_enabled = cfg.acme.enabled,
-                "starting luciuz"
-            );
+</html>"#;
+
+/// Routes of the configured profile, by precedence: `/healthz`, proxy routes,
+/// static files (as the fallback), then the landing page at `/` if no static
+/// site is mounted. An `admin_panel` puts all of them behind `[admin]`.
+fn app_router(cfg: &luciuz_config::Config, proto: &'static str) -> Result<Router, anyhow::Error> {
+    let profile = cfg.server.profile;
+    let mixed = matches!(profile, Profile::Site | Profile::AdminPanel);
+    let with_proxy = profile == Profile::PublicApi || (mixed && cfg.proxy.is_some());
+    let with_static = profile == Profile::StaticSite || (mixed && cfg.static_site.is_some());
+
+    let mut app = Router::new().route("/healthz", get(|| async { "ok" }));
+    if with_proxy {
+        app = app.merge(luciuz_proxy::router(cfg, proto)?);
+    }
+    if with_static {
+        app = app.merge(luciuz_proxy::static_router(cfg)?);
+    } else if cfg.server.landing_page {
+        let landing = match profile {
+            Profile::PublicApi | Profile::Site => LANDING_HTML,
+            _ => "<h1>Luciuz is running</h1><p>Minimal landing.</p>",
+        };
+        app = app.route("/", get(move || async move { Html(landing) }));
+    }
+    if profile == Profile::AdminPanel {
+        app = app.layer(from_fn_with_state(AdminState::new(cfg)?, admin_mw));
+    }
+    Ok(app)
+}
 
-            if cfg.acme.enabled {
-                run_https_with_acme_http01(cfg, http_addr, https_addr, app).await?;
-            } else {
-                if let Some(http_addr) = http_addr {
-                    let listener = tokio::net::TcpListener::bind(http_addr).await?;
-                    axum::serve(listener, app).await?;
-                } else {
-                    tracing::info!("HTTP listener disabled (server.http_listen is empty)");
-                }
+/// Reload the config on SIGHUP and, if `[control]` is set, serve the control
+/// API. The control listener is bound here so a bad address fails startup.
+async fn start_control<T: Send + Sync + 'static>(
+    reloader: &Arc<Reloader<T>>,
+) -> Result<(), anyhow::Error> {
+    tokio::spawn(reload_on_sighup(reloader.clone()));
+    if let Some(control) = &reloader.current().config.control {
+        let listener = tokio::net::TcpListener::bind(&control.listen).await?;
+        let reloader = reloader.clone();
+        tokio::spawn(async move {
+            if let Err(err) = luciuz_control::serve(reloader, listener).await {
+                tracing::error!(error = %err, "control API stopped");
             }
-
-            warn!("server stopped");
-            Ok(())
-        }
+        });
     }
+    Ok(())
+}
+
+/// Outermost layers of every listener: resolve the client address first, so
+/// every layer sees it, then assign the request id, compress, and fill error
+/// bodies.
+fn request_layers(
+    app: Router,
+    cfg: &luciuz_config::Config,
+    proto: &'static str,
+) -> Result<Router, anyhow::Error> {
+    let app = app.layer(from_fn_with_state(
+        ErrorPagesState::new(cfg)?,
+        error_pages_mw,
+    ));
+    let app = match CompressionState::new(cfg) {
+        Some(state) => app.layer(from_fn_with_state(state, compression_mw)),
+        None => app,
+    };
+    let app = app.layer(from_fn(request_id_mw));
+    Ok(app.layer(from_fn_with_state(
+        ClientIpState::new(cfg, proto),
+        client_ip_mw,
+    )))
 }
 
 #[derive(Clone)]
@@ -208,6 +505,7 @@ async fn canonical_host_mw(
 ) -> Response {
     let canonical = state.canonical.as_str();
     let www = state.www.as_str();
+    let client_ip = req.extensions().get::<ClientInfo>().map(|c| c.ip);
 
     // Host header (strip optional port)
     let host = req
@@ -229,11 +527,20 @@ async fn canonical_host_mw(
             return Redirect::permanent(&target).into_response();
         }
         Some(h) => {
-            warn!(host = %h, uri = %req.uri(), "rejecting request for unknown host");
+            warn!(
+                host = %h,
+                uri = %req.uri(),
+                client_ip = ?client_ip,
+                "rejecting request for unknown host"
+            );
             return axum::http::StatusCode::MISDIRECTED_REQUEST.into_response();
         }
         None => {
-            warn!(uri = %req.uri(), "rejecting request without Host header");
+            warn!(
+                uri = %req.uri(),
+                client_ip = ?client_ip,
+                "rejecting request without Host header"
+            );
             return axum::http::StatusCode::BAD_REQUEST.into_response();
         }
     }
@@ -315,10 +622,17 @@ async fn http_guard_mw(
     req: Request<Body>,
     next: Next,
 ) -> Response {
+    let client_ip = req.extensions().get::<ClientInfo>().map(|c| c.ip);
+
     // Only allow GET/HEAD on port 80 (ACME + redirect only).
     let m = req.method();
     if *m != Method::GET && *m != Method::HEAD {
-        warn!(method = %m, uri = %req.uri(), "rejecting non-GET/HEAD on http");
+        warn!(
+            method = %m,
+            uri = %req.uri(),
+            client_ip = ?client_ip,
+            "rejecting non-GET/HEAD on http"
+        );
         return method_not_allowed();
     }
 
@@ -334,11 +648,20 @@ async fn http_guard_mw(
             // ok
         }
         Some(h) => {
-            warn!(host = %h, uri = %req.uri(), "rejecting request for unknown host on http");
+            warn!(
+                host = %h,
+                uri = %req.uri(),
+                client_ip = ?client_ip,
+                "rejecting request for unknown host on http"
+            );
             return StatusCode::MISDIRECTED_REQUEST.into_response(); // 421
         }
         None => {
-            warn!(uri = %req.uri(), "rejecting request without Host header on http");
+            warn!(
+                uri = %req.uri(),
+                client_ip = ?client_ip,
+                "rejecting request without Host header on http"
+            );
             return StatusCode::BAD_REQUEST.into_response(); // 400
         }
     }
@@ -346,61 +669,223 @@ async fn http_guard_mw(
     next.run(req).await
 }
 
+/// Routers and TLS of one config generation when ACME is enabled.
+struct AcmeListeners {
+    https: Router,
+    http: Router,
+    tls: Tls,
+}
+
+/// Certificates for one `[acme]` section. A reload keeps them while the
+/// section is unchanged, and starts over (stopping the renewals of the old
+/// one once no generation uses it) when it changes.
+struct AcmeCerts {
+    config: luciuz_config::Acme,
+    resolver: Arc<rustls_acme::ResolvesServerCertAcme>,
+    challenge_tls: Arc<rustls::ServerConfig>,
+    default_tls: Arc<rustls::ServerConfig>,
+    http01_service: rustls_acme::tower::TowerHttp01ChallengeService,
+    events: tokio::task::AbortHandle,
+}
+
+impl AcmeCerts {
+    fn start(acme: &luciuz_config::Acme) -> Arc<Self> {
+        use rustls_acme::caches::DirCache;
+        use rustls_acme::AcmeConfig;
+        use rustls_acme::UseChallenge::{Http01, TlsAlpn01};
+        use tokio_stream::StreamExt;
+
+        let challenge = match acme.challenge {
+            AcmeChallenge::Http01 => Http01,
+            AcmeChallenge::TlsAlpn01 => TlsAlpn01,
+        };
+        let mut state = AcmeConfig::new(acme.domains.clone())
+            .contact_push(format!("mailto:{}", acme.email))
+            .cache(DirCache::new(acme.cache_dir.clone()))
+            .directory_lets_encrypt(acme.prod)
+            .challenge_type(challenge)
+            .state();
+        let certs = Self {
+            config: acme.clone(),
+            resolver: state.resolver(),
+            challenge_tls: state.challenge_rustls_config(),
+            default_tls: state.default_rustls_config(),
+            http01_service: state.http01_challenge_tower_service(),
+            events: tokio::spawn(async move {
+                // Driving the state orders and renews; log what happens.
+                loop {
+                    match state.next().await {
+                        Some(Ok(evt)) => tracing::info!(?evt, "acme event"),
+                        Some(Err(err)) => tracing::error!(?err, "acme error"),
+                        None => break,
+                    }
+                }
+            })
+            .abort_handle(),
+        };
+        Arc::new(certs)
+    }
+}
+
+impl Drop for AcmeCerts {
+    fn drop(&mut self) {
+        self.events.abort();
+    }
+}
+
+/// TLS of one generation: its certificates, and the rustls config of
+/// regular connections (which verifies client certificates for an
+/// `admin_panel` with `admin.client_ca`).
+struct Tls {
+    certs: Arc<AcmeCerts>,
+    config: Arc<rustls::ServerConfig>,
+}
+
+impl Tls {
+    /// `cached`: the certificates of the last generation built, reused if
+    /// `[acme]` is unchanged.
+    fn new(cfg: &luciuz_config::Config, cached: Option<Arc<AcmeCerts>>) -> anyhow::Result<Self> {
+        let certs = cached
+            .filter(|c| c.config == cfg.acme)
+            .unwrap_or_else(|| AcmeCerts::start(&cfg.acme));
+        let config = match cfg
+            .admin
+            .as_ref()
+            .filter(|_| cfg.server.profile == Profile::AdminPanel)
+            .and_then(|a| a.client_ca.as_deref())
+        {
+            Some(ca) => client_auth_config(certs.resolver.clone(), ca)?,
+            None => certs.default_tls.clone(),
+        };
+        Ok(Self { certs, config })
+    }
+}
+
+/// Terminates TLS with the live generation's settings, so that a reload
+/// applies to the connections accepted after it.
+#[derive(Clone)]
+struct LiveTlsAcceptor(Arc<Reloader<AcmeListeners>>);
+
+impl<I, S> Accept<I, S> for LiveTlsAcceptor
+where
+    I: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = tokio_rustls::server::TlsStream<I>;
+    type Service = S;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, S)>> + Send>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        // Only the TLS part, so a slow handshake does not hold the routers.
+        let tls = &self.0.current().state.tls;
+        let (certs, config) = (tls.certs.clone(), tls.config.clone());
+        Box::pin(async move {
+            let start = LazyConfigAcceptor::new(Default::default(), stream).await?;
+            if rustls_acme::is_tls_alpn_challenge(&start.client_hello()) {
+                let mut validation = start.into_stream(certs.challenge_tls.clone()).await?;
+                validation.shutdown().await?;
+                return Err(io::Error::other("TLS-ALPN-01 validation request"));
+            }
+            let stream = start.into_stream(config).await?;
+            Ok((stream, service))
+        })
+    }
+}
+
 async fn run_https_with_acme_http01(
+    path: &str,
     cfg: luciuz_config::Config,
     http_addr: Option<SocketAddr>,
     https_addr: SocketAddr,
-    https_app: Router,
 ) -> Result<(), anyhow::Error> {
     use axum_server::bind;
-    use rustls_acme::caches::DirCache;
-    use rustls_acme::tower::TowerHttp01ChallengeService;
-    use rustls_acme::AcmeConfig;
-    use rustls_acme::UseChallenge::{Http01, TlsAlpn01};
-    use tokio_stream::StreamExt;
-
-    // --- ACME state
-    let challenge = match cfg.acme.challenge.as_str() {
-        "http-01" => Http01,
-        "tls-alpn-01" => TlsAlpn01,
-        other => {
+
+    // The certificates of the last generation built, for the next one.
+    let cached = std::sync::Mutex::new(None::<Arc<AcmeCerts>>);
+    let http_acceptor = ProxyProtocolAcceptor::new(DefaultAcceptor::new(), &cfg, "http")?;
+    let reloader = Reloader::new(
+        path,
+        cfg,
+        Box::new(move |cfg| {
+            let mut cached = cached.lock().unwrap_or_else(|e| e.into_inner());
+            let tls = Tls::new(cfg, cached.clone())?;
+            let http01_service = (cfg.acme.challenge == AcmeChallenge::Http01)
+                .then(|| tls.certs.http01_service.clone());
+            let listeners = AcmeListeners {
+                https: https_app(cfg)?,
+                http: http_redirect_app(cfg, http01_service)?,
+                tls,
+            };
+            *cached = Some(listeners.tls.certs.clone());
+            Ok(listeners)
+        }),
+    )?;
+    start_control(&reloader).await?;
+    let live = reloader.current();
+    let challenge = live.config.acme.challenge;
+
+    // --- Servers (PROXY protocol, if enabled, is read before TLS)
+    let https_acceptor =
+        ProxyProtocolAcceptor::new(LiveTlsAcceptor(reloader.clone()), &live.config, "https")?;
+    drop(live);
+    let https_future = bind(https_addr).acceptor(https_acceptor).serve(
+        live_router(reloader.clone(), |l| &l.https)
+            .into_make_service_with_connect_info::<SocketAddr>(),
+    );
+
+    if let Some(http_addr) = http_addr {
+        let http_future = bind(http_addr).acceptor(http_acceptor).serve(
+            live_router(reloader, |l| &l.http).into_make_service_with_connect_info::<SocketAddr>(),
+        );
+        tokio::try_join!(https_future, http_future)?;
+    } else {
+        // 443-only: on ne lance que HTTPS
+        if challenge == AcmeChallenge::Http01 {
             return Err(anyhow::anyhow!(
-                "acme.challenge invalid: {other} (allowed: http-01|tls-alpn-01)"
+                "server.http_listen is empty but acme.challenge=http-01 requires port 80"
             ));
         }
-    };
-
-    let mut state = AcmeConfig::new(cfg.acme.domains.clone())
-        .contact_push(format!("mailto:{}", cfg.acme.email))
-        .cache(DirCache::new(cfg.acme.cache_dir))
-        .directory_lets_encrypt(cfg.acme.prod)
-        .challenge_type(challenge)
-        .state();
-
-    // Rustls acceptor for axum-server.
-    let acceptor = state.axum_acceptor(state.default_rustls_config());
+        https_future.await?;
+    }
 
-    let http01_service: Option<TowerHttp01ChallengeService> = if cfg.acme.challenge == "http-01" {
-        Some(state.http01_challenge_tower_service())
-    } else {
-        None
-    };
+    Ok(())
+}
 
-    // Log ACME events in the background.
-    tokio::spawn(async move {
-        loop {
-            match state.next().await {
-                Some(Ok(evt)) => tracing::info!(?evt, "acme event"),
-                Some(Err(err)) => tracing::error!(?err, "acme error"),
-                None => break,
-            }
-        }
-    });
+/// TLS config of an `admin_panel` with `admin.client_ca`: the handshake
+/// fails without a client certificate issued by one of the CAs in `ca_file`.
+fn client_auth_config(
+    resolver: Arc<rustls_acme::ResolvesServerCertAcme>,
+    ca_file: &str,
+) -> Result<Arc<rustls::ServerConfig>, anyhow::Error> {
+    use rustls::pki_types::{pem::PemObject, CertificateDer};
+
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_file)
+        .map_err(|e| anyhow::anyhow!("admin.client_ca: cannot read {ca_file}: {e}"))?
+    {
+        let cert = cert.map_err(|e| anyhow::anyhow!("admin.client_ca: {ca_file}: {e}"))?;
+        roots.add(cert)?;
+    }
+    if roots.is_empty() {
+        anyhow::bail!("admin.client_ca: no certificate in {ca_file}");
+    }
+    let verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(roots)).build()?;
+    let mut config = rustls::ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_cert_resolver(resolver);
+    // As in the default config: HTTP/2 first.
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
 
+/// The HTTPS service: profile routes with canonical host redirect, HSTS,
+/// security headers and the handler timeout.
+fn https_app(cfg: &luciuz_config::Config) -> Result<Router, anyhow::Error> {
+    let https_app = app_router(cfg, "https")?;
     let canonical = cfg.server.canonical_host.clone();
 
     // --- HTTPS: apply canonical host redirect (www -> apex)
-    let https_app = if let Some(ch) = canonical.clone() {
+    let https_app = if let Some(ch) = canonical {
         let state = CanonicalHost {
             www: format!("www.{ch}"),
             canonical: ch,
@@ -412,7 +897,7 @@ async fn run_https_with_acme_http01(
 
     // --- HTTPS: HSTS (HTTPS only)
     let https_app = if cfg.server.hsts {
-        let mut v = format!("max-age={}", cfg.server.hsts_max_age);
+        let mut v = format!("max-age={}", cfg.server.hsts_max_age.as_secs());
         if cfg.server.hsts_include_subdomains {
             v.push_str("; includeSubDomains");
         }
@@ -442,7 +927,36 @@ async fn run_https_with_acme_http01(
         https_app
     };
 
-    let http_app = if cfg.acme.challenge == "http-01" {
+    let handler_timeout = cfg
+        .timeouts
+        .as_ref()
+        .map_or(Duration::from_secs(30), |t| t.handler);
+
+    let https_app = https_app.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|err: BoxError| async move {
+                if err.is::<tower::timeout::error::Elapsed>() {
+                    warn!("request timed out");
+                    return (StatusCode::GATEWAY_TIMEOUT, "").into_response();
+                }
+                // fallback
+                (StatusCode::INTERNAL_SERVER_ERROR, "").into_response()
+            }))
+            .layer(TimeoutLayer::new(handler_timeout)),
+    );
+
+    request_layers(https_app, cfg, "https")
+}
+
+/// The HTTP service: ACME HTTP-01 challenges, and a redirect to HTTPS for
+/// everything else.
+fn http_redirect_app(
+    cfg: &luciuz_config::Config,
+    http01_service: Option<rustls_acme::tower::TowerHttp01ChallengeService>,
+) -> Result<Router, anyhow::Error> {
+    let canonical = cfg.server.canonical_host.clone();
+
+    let http_app = if cfg.acme.challenge == AcmeChallenge::Http01 {
         let acme_challenge_service =
             http01_service.expect("http-01 selected but http01_service was not initialized");
 
@@ -474,44 +988,7 @@ async fn run_https_with_acme_http01(
         http_app
     };
 
-    let handler_timeout_secs = cfg.timeouts.as_ref().map(|t| t.handler_secs).unwrap_or(30);
-
-    let https_app = https_app.layer(
-        ServiceBuilder::new()
-            .layer(HandleErrorLayer::new(|err: BoxError| async move {
-                if err.is::<tower::timeout::error::Elapsed>() {
-                    warn!("request timed out");
-                    return (StatusCode::GATEWAY_TIMEOUT, "").into_response();
-                }
-                // fallback
-                (StatusCode::INTERNAL_SERVER_ERROR, "").into_response()
-            }))
-            .layer(TimeoutLayer::new(Duration::from_secs(handler_timeout_secs))),
-    );
-
-    // --- Servers
-    let https_future = bind(https_addr)
-        .acceptor(acceptor)
-        .serve(https_app.into_make_service_with_connect_info::<SocketAddr>());
-
-    if let Some(http_addr) = http_addr {
-        // Si on est en http-01, le port 80 doit exister (sinon on ne peut pas valider).
-        if cfg.acme.challenge == "http-01" {
-            // http_app déjà construit plus haut (ACME+redirect)
-        }
-        let http_future = bind(http_addr).serve(http_app.into_make_service());
-        tokio::try_join!(https_future, http_future)?;
-    } else {
-        // 443-only: on ne lance que HTTPS
-        if cfg.acme.challenge == "http-01" {
-            return Err(anyhow::anyhow!(
-                "server.http_listen is empty but acme.challenge=http-01 requires port 80"
-            ));
-        }
-        https_future.await?;
-    }
-
-    Ok(())
+    request_layers(http_app, cfg, "http")
 }
 
 async fn http_to_https_redirect(
@@ -536,3 +1013,16 @@ async fn http_to_https_redirect(
     Redirect::permanent(&target)
 }
 
+/// Load `path` for a `config` subcommand; on errors print the report and
+/// exit with status 1.
+fn load_or_exit(path: &str) -> anyhow::Result<(luciuz_config::Config, luciuz_config::Report)> {
+    let (cfg, report) = luciuz_config::check_path(path).map_err(|e| anyhow::anyhow!(e))?;
+    match cfg {
+        Some(cfg) if !report.has_errors() => Ok((cfg, report)),
+        _ => {
+            eprint!("{}", report.render());
+            std::process::exit(1)
+        }
+    }
+}
+
@@ -0,0 +1,291 @@
+//! Reads the config model (`src/model.rs`) and writes `schema_defs.rs`:
+//! each struct, `choice!` enum and untagged enum with its doc comments,
+//! field types and serde defaults, for `luciuz config schema`. A serde
+//! attribute it does not account for fails the build.
+
+use std::fmt::Write;
+
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Fields, GenericArgument, Ident, Item, LitStr, PathArguments, Token, Type};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/model.rs");
+    let source = std::fs::read_to_string("src/model.rs").expect("read src/model.rs");
+    let file = syn::parse_file(&source).expect("parse src/model.rs");
+
+    let mut out = String::from("// Generated by build.rs from src/model.rs.\n\n");
+    out.push_str("pub(crate) static DEFS: &[crate::schema::Def] = &[\n");
+    for item in &file.items {
+        match item {
+            Item::Struct(s) if derives_deserialize(&s.attrs) => {
+                supported(&s.attrs, &["deny_unknown_fields"], &s.ident.to_string());
+                let Fields::Named(fields) = &s.fields else {
+                    continue;
+                };
+                let fields = fields_code(fields.named.iter());
+                def(
+                    &mut out,
+                    &s.ident,
+                    &s.attrs,
+                    &format!("Struct(&[{fields}])"),
+                );
+            }
+            Item::Enum(e) if derives_deserialize(&e.attrs) && serde_flag(&e.attrs, "untagged") => {
+                supported(
+                    &e.attrs,
+                    &["untagged", "deny_unknown_fields"],
+                    &e.ident.to_string(),
+                );
+                let variants: Vec<String> = e
+                    .variants
+                    .iter()
+                    .map(|v| match &v.fields {
+                        Fields::Unnamed(f) => shape(&f.unnamed[0].ty, None),
+                        Fields::Named(f) => format!(
+                            "crate::schema::Shape::Object(&[{}])",
+                            fields_code(f.named.iter())
+                        ),
+                        Fields::Unit => panic!("unit variant {} in untagged enum", v.ident),
+                    })
+                    .collect();
+                def(
+                    &mut out,
+                    &e.ident,
+                    &e.attrs,
+                    &format!("Untagged(&[{}])", variants.join(", ")),
+                );
+            }
+            Item::Macro(m) if m.mac.path.is_ident("choice") => {
+                let choice: Choice = m.mac.parse_body().expect("parse choice!");
+                let values: Vec<String> = choice
+                    .values
+                    .iter()
+                    .map(|(attrs, text)| format!("({text:?}, {:?})", doc(attrs)))
+                    .collect();
+                def(
+                    &mut out,
+                    &choice.name,
+                    &choice.attrs,
+                    &format!("Choice(&[{}])", values.join(", ")),
+                );
+            }
+            _ => {}
+        }
+    }
+    out.push_str("];\n");
+
+    let dest = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("schema_defs.rs");
+    std::fs::write(dest, out).expect("write schema_defs.rs");
+}
+
+fn def(out: &mut String, name: &Ident, attrs: &[Attribute], body: &str) {
+    writeln!(
+        out,
+        "    crate::schema::Def {{ name: {:?}, doc: {:?}, body: crate::schema::Body::{body} }},",
+        name.to_string(),
+        doc(attrs)
+    )
+    .unwrap();
+}
+
+fn fields_code<'a>(fields: impl Iterator<Item = &'a syn::Field>) -> String {
+    fields
+        .map(|f| {
+            let at = f.ident.as_ref().map_or("field".into(), |i| i.to_string());
+            supported(&f.attrs, FIELD_ATTRS, &at);
+            let name = serde_value(&f.attrs, "rename")
+                .unwrap_or_else(|| f.ident.as_ref().unwrap().to_string());
+            let with = serde_value(&f.attrs, "with")
+                .or_else(|| serde_value(&f.attrs, "deserialize_with"));
+            let optional = outer(&f.ty, "Option").is_some();
+            let default = match serde_value(&f.attrs, "default") {
+                Some(func) if func.is_empty() => "TypeDefault".to_string(),
+                Some(func) => match serde_value(&f.attrs, "with") {
+                    Some(with) => format!(
+                        "Fn(|| {with}::serialize(&{func}(), serde_json::value::Serializer).unwrap())"
+                    ),
+                    None => format!("Fn(|| serde_json::to_value({func}()).unwrap())"),
+                },
+                None => "None".to_string(),
+            };
+            format!(
+                "crate::schema::Field {{ name: {name:?}, doc: {:?}, shape: {}, required: {}, \
+                 default: crate::schema::DefaultValue::{default} }}",
+                doc(&f.attrs),
+                shape(&f.ty, with.as_deref()),
+                !optional && default == "None",
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The `crate::schema::Shape` expression for a field of type `ty`.
+fn shape(ty: &Type, with: Option<&str>) -> String {
+    match with {
+        Some("duration") => return "crate::schema::Shape::Duration".into(),
+        Some("size") => return "crate::schema::Shape::Size".into(),
+        Some("optional_addr") => return "crate::schema::Shape::AddressOrEmpty".into(),
+        Some(other) => panic!("no schema shape for `with = {other:?}`: add one to build.rs"),
+        None => {}
+    }
+    if let Some(inner) = outer(ty, "Option") {
+        return format!("crate::schema::Shape::Option(&{})", shape(inner, None));
+    }
+    if let Some(inner) = outer(ty, "Vec") {
+        return format!("crate::schema::Shape::List(&{})", shape(inner, None));
+    }
+    if let Some(value) = outer(ty, "BTreeMap") {
+        return format!("crate::schema::Shape::Map(&{})", shape(value, None));
+    }
+    let Type::Path(p) = ty else {
+        panic!("unsupported field type in src/model.rs");
+    };
+    let name = p.path.segments.last().unwrap().ident.to_string();
+    match name.as_str() {
+        "bool" => "crate::schema::Shape::Bool".into(),
+        "u8" | "u16" | "u32" | "u64" | "usize" => {
+            format!("crate::schema::Shape::Integer({name}::MAX as u64)")
+        }
+        "String" => "crate::schema::Shape::String".into(),
+        "SocketAddr" => "crate::schema::Shape::Address".into(),
+        "Cidr" => "crate::schema::Shape::Network".into(),
+        "Duration" => "crate::schema::Shape::Duration".into(),
+        _ => format!("crate::schema::Shape::Def({name:?})"),
+    }
+}
+
+/// `T` when `ty` is `wrapper<..., T>`.
+fn outer<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let last = p.path.segments.last()?;
+    if last.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return None;
+    };
+    args.args.iter().rev().find_map(|a| match a {
+        GenericArgument::Type(t) => Some(t),
+        _ => None,
+    })
+}
+
+/// Doc comment lines joined into paragraphs.
+fn doc(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta {
+            syn::Meta::NameValue(nv) => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    lines
+        .join("\n")
+        .split("\n\n")
+        .map(|p| p.replace('\n', " "))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn derives_deserialize(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|a| {
+        let mut found = false;
+        if a.path().is_ident("derive") {
+            let _ = a.parse_nested_meta(|m| {
+                found |= m
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|s| s.ident == "Deserialize");
+                Ok(())
+            });
+        }
+        found
+    })
+}
+
+/// Field attributes the schema accounts for (`alias` through
+/// `migrate::RENAMED`).
+const FIELD_ATTRS: &[&str] = &["default", "with", "deserialize_with", "rename", "alias"];
+
+/// Fail the build on a `#[serde(...)]` key the schema would silently miss
+/// (`flatten`, `skip`, ...).
+fn supported(attrs: &[Attribute], keys: &[&str], at: &str) {
+    for a in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        a.parse_nested_meta(|m| {
+            let key = m.path.get_ident().map(Ident::to_string).unwrap_or_default();
+            if !keys.contains(&key.as_str()) {
+                panic!("#[serde({key})] on {at} is not understood by build.rs");
+            }
+            if let Ok(v) = m.value() {
+                v.parse::<syn::Expr>()?;
+            }
+            Ok(())
+        })
+        .expect("parse #[serde]");
+    }
+}
+
+fn serde_flag(attrs: &[Attribute], flag: &str) -> bool {
+    serde_value(attrs, flag).is_some()
+}
+
+/// The value of `#[serde(key = "...")]`, `""` for a bare `#[serde(key)]`.
+fn serde_value(attrs: &[Attribute], key: &str) -> Option<String> {
+    let mut value = None;
+    for a in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let _ = a.parse_nested_meta(|m| {
+            if m.path.is_ident(key) {
+                value = Some(match m.value() {
+                    Ok(v) => v.parse::<LitStr>()?.value(),
+                    Err(_) => String::new(),
+                });
+            } else if let Ok(v) = m.value() {
+                v.parse::<syn::Expr>()?;
+            }
+            Ok(())
+        });
+    }
+    value
+}
+
+/// The body of a `choice!` invocation.
+struct Choice {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    /// (docs, config string) per variant.
+    values: Vec<(Vec<Attribute>, String)>,
+}
+
+impl Parse for Choice {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let name = input.parse()?;
+        let body;
+        syn::braced!(body in input);
+        let mut values = Vec::new();
+        while !body.is_empty() {
+            let attrs = body.call(Attribute::parse_outer)?;
+            body.parse::<Ident>()?;
+            body.parse::<Token![=]>()?;
+            let text: LitStr = body.parse()?;
+            body.parse::<Option<Token![,]>>()?;
+            values.push((attrs, text.value()));
+        }
+        Ok(Self {
+            attrs,
+            name,
+            values,
+        })
+    }
+}
@@ -0,0 +1,398 @@
+//! `luciuz audit`: practices a valid config can still fall short of. Each
+//! rule has a stable id (`A001`) and a name; `audit.allow` in the config, or
+//! `luciuz audit --allow`, turns one off.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::model::Config;
+use crate::report::Report;
+use crate::units::format_duration;
+
+/// The max-age HSTS preload lists expect at the least.
+const PRELOAD_MAX_AGE: Duration = Duration::from_secs(15_768_000);
+
+/// Above this, a request body limit is more an open door than a limit.
+const LARGE_BODY: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+impl Risk {
+    /// Points a finding costs out of the score's 100.
+    fn weight(self) -> u32 {
+        match self {
+            Risk::High => 25,
+            Risk::Medium => 10,
+            Risk::Low => 5,
+        }
+    }
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Risk::High => "high",
+            Risk::Medium => "medium",
+            Risk::Low => "low",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Rule {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub risk: Risk,
+    /// What the rule looks for.
+    pub summary: &'static str,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: "A001",
+        name: "hsts-preload-max-age",
+        risk: Risk::High,
+        summary: "HSTS preload with a max-age below 6 months",
+    },
+    Rule {
+        id: "A002",
+        name: "hsts-preload-subdomains",
+        risk: Risk::Medium,
+        summary: "HSTS preload without includeSubDomains",
+    },
+    Rule {
+        id: "A003",
+        name: "security-headers-off",
+        risk: Risk::High,
+        summary: "server.security_headers = false",
+    },
+    Rule {
+        id: "A004",
+        name: "plaintext-upstream",
+        risk: Risk::High,
+        summary: "http:// upstream on a non-loopback address",
+    },
+    Rule {
+        id: "A005",
+        name: "acme-staging",
+        risk: Risk::Medium,
+        summary: "staging certificates (acme.prod = false) on a public site",
+    },
+    Rule {
+        id: "A006",
+        name: "no-canonical-host",
+        risk: Risk::Low,
+        summary: "no server.canonical_host, so any Host is answered",
+    },
+    Rule {
+        id: "A007",
+        name: "large-body-limit",
+        risk: Risk::Medium,
+        summary: "request body limit above 100MiB",
+    },
+    Rule {
+        id: "A008",
+        name: "acme-cache-readable",
+        risk: Risk::High,
+        summary: "world-readable ACME cache directory (account and certificate keys)",
+    },
+];
+
+/// The rule with id or name `key`.
+pub fn rule(key: &str) -> Option<&'static Rule> {
+    RULES
+        .iter()
+        .find(|r| r.id.eq_ignore_ascii_case(key) || r.name == key)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    #[serde(flatten)]
+    pub rule: Rule,
+    pub message: String,
+    /// Config key path the finding is about.
+    pub path: String,
+    /// `file:line:column` of `path`, or of the closest table that sets it.
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Audit {
+    pub file: String,
+    /// 100, less the weight of each finding by risk; never below 0.
+    pub score: u32,
+    /// Most risky first, then in rule order.
+    pub findings: Vec<Finding>,
+    /// Findings of rules turned off by `audit.allow` or the caller.
+    pub suppressed: Vec<Finding>,
+}
+
+/// Audit `cfg`, validated into `report`. Rules listed in `audit.allow` or in
+/// `allow` (ids or names) are reported as suppressed.
+pub fn audit(cfg: &Config, report: &Report, allow: &[String]) -> Audit {
+    let mut found = Vec::new();
+    let mut add = |id: &str, path: &str, message: String| {
+        let rule = *rule(id).expect("audit rule");
+        found.push(Finding {
+            rule,
+            message,
+            path: path.to_string(),
+            location: report.position(path),
+        });
+    };
+
+    let server = &cfg.server;
+    if server.hsts && server.hsts_preload && server.hsts_max_age < PRELOAD_MAX_AGE {
+        add(
+            "A001",
+            "server.hsts_max_age",
+            format!(
+                "hsts_max_age is {} with hsts_preload; preload lists expect at least 6 months ({})",
+                format_duration(server.hsts_max_age),
+                format_duration(PRELOAD_MAX_AGE)
+            ),
+        );
+    }
+    if server.hsts && server.hsts_preload && !server.hsts_include_subdomains {
+        add(
+            "A002",
+            "server.hsts_include_subdomains",
+            "hsts_preload without hsts_include_subdomains: preload lists refuse the domain"
+                .to_string(),
+        );
+    }
+    if !server.security_headers {
+        add(
+            "A003",
+            "server.security_headers",
+            "security_headers = false: responses go out without nosniff, framing, \
+             referrer and cross-origin protection"
+                .to_string(),
+        );
+    }
+
+    if let Some(proxy) = &cfg.proxy {
+        for (i, route) in proxy.routes.iter().enumerate() {
+            if plaintext(&route.upstream) {
+                add(
+                    "A004",
+                    &format!("proxy.routes[{i}].upstream"),
+                    format!(
+                        "{} is plain HTTP to another host; use https:// or a loopback upstream",
+                        route.upstream
+                    ),
+                );
+            }
+        }
+        for (name, pool) in &proxy.pools {
+            for (i, target) in pool.targets.iter().enumerate() {
+                if plaintext(target) {
+                    add(
+                        "A004",
+                        &format!("proxy.pools.{name}.targets[{i}]"),
+                        format!(
+                            "{target} is plain HTTP to another host; use https:// or a loopback upstream"
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    let acme = &cfg.acme;
+    let public_names: Vec<&str> = acme
+        .domains
+        .iter()
+        .map(String::as_str)
+        .filter(|d| is_public_name(d))
+        .collect();
+    if acme.enabled
+        && !acme.prod
+        && !server.https_listen.ip().is_loopback()
+        && !public_names.is_empty()
+    {
+        add(
+            "A005",
+            "acme.prod",
+            format!(
+                "acme.prod = false: {} gets a staging certificate, which browsers reject",
+                public_names.join(", ")
+            ),
+        );
+    }
+    if server.canonical_host.is_none() {
+        add(
+            "A006",
+            "server.canonical_host",
+            "no canonical_host: requests for any Host are answered".to_string(),
+        );
+    }
+
+    let mut limits = Vec::new();
+    if let Some(proxy) = &cfg.proxy {
+        limits.push(("proxy.max_body_bytes", proxy.max_body_bytes));
+    }
+    if let Some(upload) = cfg.static_site.as_ref().and_then(|s| s.upload.as_ref()) {
+        limits.push(("static_site.upload.max_body_bytes", upload.max_body_bytes));
+    }
+    for (path, limit) in limits {
+        if limit as u64 > LARGE_BODY {
+            add(
+                "A007",
+                path,
+                format!(
+                    "{path} is {}MiB: one client can tie up that much per request",
+                    limit >> 20
+                ),
+            );
+        }
+    }
+
+    if acme.enabled {
+        if let Some(message) = readable_cache(Path::new(&acme.cache_dir)) {
+            add("A008", "acme.cache_dir", message);
+        }
+    }
+
+    let (suppressed, mut findings): (Vec<_>, Vec<_>) = found.into_iter().partition(|f| {
+        cfg.audit
+            .allow
+            .iter()
+            .chain(allow)
+            .any(|a| rule(a).is_some_and(|r| r.id == f.rule.id))
+    });
+    findings.sort_by(|a, b| b.rule.risk.cmp(&a.rule.risk).then(a.rule.id.cmp(b.rule.id)));
+    let lost: u32 = findings.iter().map(|f| f.rule.risk.weight()).sum();
+    Audit {
+        file: report.file.clone(),
+        score: 100u32.saturating_sub(lost),
+        findings,
+        suppressed,
+    }
+}
+
+impl Audit {
+    /// Each finding with its location, then the score.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for f in &self.findings {
+            out.push_str(&format!(
+                "{}[{} {}]: {}\n --> {}\n\n",
+                f.rule.risk, f.rule.id, f.rule.name, f.message, f.location
+            ));
+        }
+        let mut summary = match self.findings.len() {
+            0 => "no findings".to_string(),
+            n => {
+                let by_risk: Vec<String> = [Risk::High, Risk::Medium, Risk::Low]
+                    .into_iter()
+                    .filter_map(|risk| {
+                        let count = self.findings.iter().filter(|f| f.rule.risk == risk).count();
+                        (count > 0).then(|| format!("{count} {risk}"))
+                    })
+                    .collect();
+                format!(
+                    "{n} finding{} ({})",
+                    if n == 1 { "" } else { "s" },
+                    by_risk.join(", ")
+                )
+            }
+        };
+        if !self.suppressed.is_empty() {
+            summary.push_str(&format!(", {} suppressed", self.suppressed.len()));
+        }
+        out.push_str(&format!(
+            "{}: score {}/100, {summary}\n",
+            self.file, self.score
+        ));
+        out
+    }
+}
+
+/// `url` is `http://` to a host other than this one.
+fn plaintext(url: &str) -> bool {
+    let Some(rest) = url.strip_prefix("http://") else {
+        return false;
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host_port.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host_port.split(':').next().unwrap_or_default(),
+    };
+    let loopback = host.eq_ignore_ascii_case("localhost")
+        || host.to_ascii_lowercase().ends_with(".localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+    !loopback
+}
+
+/// A name a public CA can issue for: not local, reserved for tests or
+/// documentation (RFC 2606, RFC 6761), or an IP.
+fn is_public_name(name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    let reserved = [
+        "localhost",
+        "local",
+        "test",
+        "example",
+        "invalid",
+        "internal",
+    ];
+    let documentation = ["example.com", "example.net", "example.org"];
+    let last = name.rsplit('.').next().unwrap_or_default();
+    name.contains('.')
+        && name.parse::<IpAddr>().is_err()
+        && !reserved.contains(&last)
+        && !documentation
+            .iter()
+            .any(|d| name == *d || name.ends_with(&format!(".{d}")))
+}
+
+/// Why `dir`, or something in it, is world-readable; `None` when it is
+/// not, or does not exist yet.
+#[cfg(unix)]
+fn readable_cache(dir: &Path) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fn walk(path: &Path, depth: usize, out: &mut Vec<(String, u32)>) {
+        let Ok(meta) = std::fs::symlink_metadata(path) else {
+            return;
+        };
+        let mode = meta.permissions().mode() & 0o777;
+        if mode & 0o004 != 0 {
+            out.push((path.display().to_string(), mode));
+        }
+        if meta.is_dir() && depth < 4 {
+            for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+                walk(&entry.path(), depth + 1, out);
+            }
+        }
+    }
+
+    let mut readable = Vec::new();
+    walk(dir, 0, &mut readable);
+    let (first, mode) = readable.first()?;
+    let more = match readable.len() {
+        1 => String::new(),
+        n => format!(" and {} more", n - 1),
+    };
+    Some(format!(
+        "{first} is world-readable (mode {mode:o}){more}; the cache holds the account \
+         and certificate keys, restrict it with chmod -R o-rwx"
+    ))
+}
+
+#[cfg(not(unix))]
+fn readable_cache(_dir: &Path) -> Option<String> {
+    None
+}
@@ -0,0 +1,96 @@
+//! The effective config for `luciuz config dump`: every value, defaults
+//! included, with where it comes from and secrets redacted.
+
+use std::collections::BTreeMap;
+
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+use crate::include::join;
+use crate::report::Report;
+use crate::Config;
+
+/// Keys whose values are never shown.
+const SECRET_KEYS: &[&str] = &["secret", "password_hash"];
+
+/// Paths whose values often carry credentials (e.g. a token in the query).
+const SECRET_PATHS: &[&str] = &["admin.forward_auth.url"];
+
+const REDACTED: &str = "<redacted>";
+
+/// The effective config of one load.
+#[derive(Debug, Clone)]
+pub struct Dump {
+    files: Vec<String>,
+    doc: DocumentMut,
+    origins: BTreeMap<String, String>,
+}
+
+/// The effective config `cfg`, as loaded with `report`. Each value's
+/// origin is `default`, `file:line`, `include file:line`, or the `${...}`
+/// references it was built from followed by its place in parentheses
+/// (`env NAME (file:line)`). Secrets, values at sensitive paths and values
+/// read from `${file:...}` are replaced by `"<redacted>"`.
+pub fn dump(cfg: &Config, report: &Report) -> Dump {
+    let text = toml::to_string(cfg).expect("the config model serializes to TOML");
+    let mut doc: DocumentMut = text.parse().expect("serialized TOML parses");
+    let mut origins = BTreeMap::new();
+    annotate(doc.as_table_mut(), "", report, &mut origins);
+    Dump {
+        files: report.files().map(str::to_string).collect(),
+        doc,
+        origins,
+    }
+}
+
+impl Dump {
+    /// TOML with each value's origin as a trailing comment.
+    pub fn to_toml(&self) -> String {
+        format!(
+            "# Effective config of {}\n\n{}",
+            self.files.join(", "),
+            self.doc
+        )
+    }
+
+    /// The values alone, e.g. for JSON.
+    pub fn values(&self) -> toml::Table {
+        toml::from_str(&self.doc.to_string()).expect("dumped TOML parses")
+    }
+
+    /// Key path (`proxy.routes[0].prefix`) → origin of each value.
+    pub fn origins(&self) -> &BTreeMap<String, String> {
+        &self.origins
+    }
+}
+
+fn annotate(table: &mut Table, at: &str, r: &Report, origins: &mut BTreeMap<String, String>) {
+    for (key, item) in table.iter_mut() {
+        let path = join(at, key.get());
+        match item {
+            Item::Table(t) => annotate(t, &path, r, origins),
+            Item::ArrayOfTables(a) => {
+                for (i, t) in a.iter_mut().enumerate() {
+                    annotate(t, &format!("{path}[{i}]"), r, origins);
+                }
+            }
+            Item::Value(v) => {
+                let refs = r.interpolated(&path);
+                if SECRET_KEYS.contains(&key.get())
+                    || SECRET_PATHS.contains(&path.as_str())
+                    || refs.iter().any(|r| r.starts_with("file "))
+                {
+                    *v = Value::from(REDACTED);
+                }
+                let origin = match r.line_of(&path) {
+                    None => "default".to_string(),
+                    Some(at) if !refs.is_empty() => format!("{} ({at})", refs.join(", ")),
+                    Some(at) if r.is_included(&path) => format!("include {at}"),
+                    Some(at) => at,
+                };
+                v.decor_mut().set_suffix(format!("  # {origin}"));
+                origins.insert(path, origin);
+            }
+            Item::None => {}
+        }
+    }
+}
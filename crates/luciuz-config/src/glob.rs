@@ -0,0 +1,234 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Upper bound on the sequences a pattern expands to (`{a,b}` alternatives
+/// multiply) and on a `{n}` repetition count.
+const MAX_EXPANSION: usize = 256;
+
+/// A path glob, e.g. `*.html`, `/assets/**/*.css` or `*.[0-9a-f]{8}.js`.
+///
+/// Syntax: `*` matches any run of characters except `/`, `**` any run
+/// including `/` (`**/` also matches no directory at all), `?` one
+/// character, `[a-z0-9]` / `[!a-z]` a character class, `{a,b}` one of the
+/// alternatives, and `{n}` repeats the preceding character, `?` or class n
+/// times. `\` escapes the next character.
+///
+/// A pattern without `/` is matched against the last path segment (the file
+/// name); otherwise against the whole path, with the leading `/` optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    source: String,
+    whole_path: bool,
+    /// The pattern with alternatives expanded; it matches if any does.
+    sequences: Vec<Vec<Token>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    GlobStar,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    /// `path` is a URL path (`/a/b.js`) or a relative file path (`a/b.js`).
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let target = if self.whole_path {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        let chars: Vec<char> = target.chars().collect();
+        self.sequences.iter().any(|seq| match_tokens(seq, &chars))
+    }
+}
+
+fn match_tokens(tokens: &[Token], s: &[char]) -> bool {
+    let Some((first, rest)) = tokens.split_first() else {
+        return s.is_empty();
+    };
+    match first {
+        Token::Star => (0..=s.len())
+            .take_while(|&k| k == 0 || s[k - 1] != '/')
+            .any(|k| match_tokens(rest, &s[k..])),
+        Token::GlobStar => {
+            // `**/` may stand for no directory at all.
+            if let Some((Token::Char('/'), after)) = rest.split_first() {
+                if match_tokens(after, s) {
+                    return true;
+                }
+            }
+            (0..=s.len()).any(|k| match_tokens(rest, &s[k..]))
+        }
+        single => match s.split_first() {
+            Some((&c, tail)) => single_matches(single, c) && match_tokens(rest, tail),
+            None => false,
+        },
+    }
+}
+
+fn single_matches(token: &Token, c: char) -> bool {
+    match token {
+        Token::Char(t) => *t == c,
+        Token::Any => c != '/',
+        Token::Class { negated, ranges } => {
+            c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+        }
+        Token::Star | Token::GlobStar => false,
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    /// Parse until the end of the pattern, or until `,`/`}` inside braces.
+    fn sequence(&mut self, in_braces: bool) -> Result<Vec<Vec<Token>>, String> {
+        let mut seqs: Vec<Vec<Token>> = vec![Vec::new()];
+        // Whether the last atom can take a `{n}` repetition.
+        let mut repeatable = false;
+        while let Some(&c) = self.chars.peek() {
+            if in_braces && (c == ',' || c == '}') {
+                break;
+            }
+            self.chars.next();
+            let token = match c {
+                '*' if self.chars.peek() == Some(&'*') => {
+                    self.chars.next();
+                    Token::GlobStar
+                }
+                '*' => Token::Star,
+                '?' => Token::Any,
+                '[' => self.class()?,
+                '\\' => Token::Char(self.chars.next().ok_or("trailing \\")?),
+                '{' if self.chars.peek().is_some_and(char::is_ascii_digit) => {
+                    let n = self.count()?;
+                    if !repeatable {
+                        return Err("{n} must follow a character, ? or [...]".into());
+                    }
+                    for seq in &mut seqs {
+                        let last = seq.last().cloned().expect("repeatable atom");
+                        seq.extend(std::iter::repeat_n(last, n - 1));
+                    }
+                    repeatable = false;
+                    continue;
+                }
+                '{' => {
+                    let mut alternatives = self.sequence(true)?;
+                    loop {
+                        match self.chars.next() {
+                            Some(',') => alternatives.extend(self.sequence(true)?),
+                            Some('}') => break,
+                            _ => return Err("unclosed {".into()),
+                        }
+                    }
+                    if seqs.len() * alternatives.len() > MAX_EXPANSION {
+                        return Err("too many {a,b} alternatives".into());
+                    }
+                    seqs = seqs
+                        .iter()
+                        .flat_map(|seq| {
+                            alternatives.iter().map(move |alt| {
+                                let mut s = seq.clone();
+                                s.extend(alt.iter().cloned());
+                                s
+                            })
+                        })
+                        .collect();
+                    repeatable = false;
+                    continue;
+                }
+                '}' => return Err("unmatched }".into()),
+                c => Token::Char(c),
+            };
+            repeatable = !matches!(token, Token::Star | Token::GlobStar);
+            for seq in &mut seqs {
+                seq.push(token.clone());
+            }
+        }
+        Ok(seqs)
+    }
+
+    /// After `[`: parse the class up to its `]`.
+    fn class(&mut self) -> Result<Token, String> {
+        let negated = matches!(self.chars.peek(), Some('!' | '^'));
+        if negated {
+            self.chars.next();
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.chars.next() {
+                None => return Err("unclosed [".into()),
+                // A `]` right after `[` or `[!` is literal.
+                Some(']') if !first => break,
+                Some('\\') => self.chars.next().ok_or("unclosed [")?,
+                Some(c) => c,
+            };
+            first = false;
+            let mut ahead = self.chars.clone();
+            if ahead.next() == Some('-') && ahead.peek().is_some_and(|&h| h != ']') {
+                self.chars.next();
+                let hi = self.chars.next().ok_or("unclosed [")?;
+                if hi < c {
+                    return Err(format!("invalid range {c}-{hi}"));
+                }
+                ranges.push((c, hi));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        Ok(Token::Class { negated, ranges })
+    }
+
+    /// After `{`: parse `n}`.
+    fn count(&mut self) -> Result<usize, String> {
+        let mut digits = String::new();
+        while let Some(c) = self.chars.next_if(char::is_ascii_digit) {
+            digits.push(c);
+        }
+        if self.chars.next() != Some('}') {
+            return Err("expected } after {n".into());
+        }
+        digits
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=MAX_EXPANSION).contains(n))
+            .ok_or_else(|| format!("{{n}} count must be in 1..={MAX_EXPANSION}"))
+    }
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("empty glob".into());
+        }
+        let body = s.strip_prefix('/').unwrap_or(s);
+        let mut parser = Parser {
+            chars: body.chars().peekable(),
+        };
+        let sequences = parser
+            .sequence(false)
+            .map_err(|e| format!("invalid glob {s}: {e}"))?;
+        Ok(Self {
+            source: s.to_string(),
+            whole_path: s.contains('/'),
+            sequences,
+        })
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
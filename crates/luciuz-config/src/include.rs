@@ -0,0 +1,194 @@
+//! `include = [...]`: the main config file plus drop-ins, merged into one
+//! table. Tables merge key by key, arrays of tables (`[[proxy.routes]]`)
+//! are appended in include order, and any other key set in two files must
+//! have the same value in both.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::report::{Code, Report};
+use crate::Glob;
+
+/// Read `file` (whose text is `raw`) and every file it includes, depth
+/// first, and merge them. `None` when one of them does not parse.
+pub(crate) fn read(file: &str, raw: &str, report: &mut Report) -> Option<Table> {
+    let path = Path::new(file);
+    let mut reader = Reader {
+        report,
+        seen: HashSet::from([canonical(path)]),
+        parsed: true,
+    };
+    let mut merged = Table::new();
+    reader.file(path, raw.to_string(), &mut merged);
+    reader.parsed.then_some(merged)
+}
+
+struct Reader<'r> {
+    report: &'r mut Report,
+    /// Canonical paths of the files read so far.
+    seen: HashSet<PathBuf>,
+    parsed: bool,
+}
+
+impl Reader<'_> {
+    fn file(&mut self, path: &Path, text: String, merged: &mut Table) {
+        let Some(source) = self.report.add_source(&path.display().to_string(), text) else {
+            self.parsed = false;
+            return;
+        };
+        let mut table = match self.report.source_text(source).parse::<Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                self.report.syntax(source, e.message(), e.span());
+                self.parsed = false;
+                return;
+            }
+        };
+        let includes = table.remove("include");
+        merge(merged, table, "", source, "", self.report);
+
+        let patterns = match includes {
+            None => return,
+            Some(Value::Array(a)) if a.iter().all(Value::is_str) => a,
+            Some(_) => {
+                self.report.error_in(
+                    source,
+                    Code::Syntax,
+                    "include",
+                    "include must be an array of file paths or patterns",
+                );
+                return;
+            }
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for (i, pattern) in patterns.iter().filter_map(Value::as_str).enumerate() {
+            let at = format!("include[{i}]");
+            match expand(dir, pattern) {
+                Ok(files) => {
+                    for file in files {
+                        self.include(source, &at, &file, merged);
+                    }
+                }
+                Err(message) => self.report.error_in(source, Code::Include, &at, message),
+            }
+        }
+    }
+
+    fn include(&mut self, from: usize, at: &str, path: &Path, merged: &mut Table) {
+        if !self.seen.insert(canonical(path)) {
+            self.report.error_in(
+                from,
+                Code::Include,
+                at,
+                format!("{at}: {} is included more than once", path.display()),
+            );
+            return;
+        }
+        match std::fs::read_to_string(path) {
+            Ok(text) => self.file(path, text, merged),
+            Err(e) => self.report.error_in(
+                from,
+                Code::Include,
+                at,
+                format!("{at}: cannot read {}: {e}", path.display()),
+            ),
+        }
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The files `pattern` names, relative to `dir`. Wildcards are allowed in
+/// the file name only; matches are sorted by name and skip hidden files,
+/// and a missing directory matches nothing.
+fn expand(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let wild = |s: &str| s.contains(['*', '?', '[', '{']);
+    let full = dir.join(pattern);
+    if !wild(pattern) {
+        return Ok(vec![full]);
+    }
+    let (Some(parent), Some(name)) = (full.parent(), full.file_name().and_then(|n| n.to_str()))
+    else {
+        return Err(format!("include: invalid pattern {pattern:?}"));
+    };
+    if wild(&parent.to_string_lossy()) {
+        return Err(format!(
+            "include: {pattern:?} has wildcards outside the file name"
+        ));
+    }
+    let glob: Glob = name
+        .parse()
+        .map_err(|e| format!("include: invalid pattern {pattern:?}: {e}"))?;
+    let entries = match std::fs::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("include: cannot read {}: {e}", parent.display())),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && glob.matches(&name)
+        })
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Merge `from`, the table at key path `local` in `source`, into `into`,
+/// the merged table at `at`.
+fn merge(into: &mut Table, from: Table, at: &str, source: usize, local: &str, r: &mut Report) {
+    for (key, value) in from {
+        let path = join(at, &key);
+        let local = join(local, &key);
+        match (into.get_mut(&key), value) {
+            (None, value) => {
+                r.set_origin(&path, source, &local);
+                into.insert(key, value);
+            }
+            (Some(Value::Table(into)), Value::Table(from)) => {
+                merge(into, from, &path, source, &local, r);
+            }
+            (Some(Value::Array(into)), Value::Array(from)) if tables(into) && tables(&from) => {
+                for (i, table) in from.into_iter().enumerate() {
+                    r.set_origin(
+                        &format!("{path}[{}]", into.len()),
+                        source,
+                        &format!("{local}[{i}]"),
+                    );
+                    into.push(table);
+                }
+            }
+            (Some(existing), value) if *existing == value => {}
+            (Some(_), _) => {
+                let first = r.position(&path);
+                r.error_in(
+                    source,
+                    Code::Redefined,
+                    &local,
+                    format!("{path} is already set in {first}"),
+                );
+            }
+        }
+    }
+}
+
+/// A non-empty array of tables, e.g. `[[proxy.routes]]`.
+fn tables(a: &[Value]) -> bool {
+    !a.is_empty() && a.iter().all(Value::is_table)
+}
+
+pub(crate) fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
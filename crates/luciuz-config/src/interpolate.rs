@@ -0,0 +1,103 @@
+//! `${NAME}`, `${NAME:-default}` and `${file:/run/secrets/x}` in string
+//! values, so secrets can stay out of the config files. `$${` stands for a
+//! literal `${`.
+
+use std::path::Path;
+
+use toml::{Table, Value};
+
+use crate::include::join;
+use crate::report::{Code, Report};
+
+/// Resolve the references in every string of the merged config. A relative
+/// `file:` path is taken from the directory of the file the value is in.
+pub(crate) fn interpolate(merged: &mut Table, r: &mut Report) {
+    for (key, value) in merged.iter_mut() {
+        walk(value, key, r);
+    }
+}
+
+fn walk(value: &mut Value, path: &str, r: &mut Report) {
+    match value {
+        Value::String(s) if s.contains('$') => {
+            let (source, _) = r.origin(path);
+            let dir = Path::new(r.source_file(source))
+                .parent()
+                .unwrap_or(Path::new(""))
+                .to_path_buf();
+            let mut refs = Vec::new();
+            match expand(s, &dir, &mut refs) {
+                Ok(expanded) => *s = expanded,
+                Err(message) => r.error(Code::Interpolation, path, format!("{path}: {message}")),
+            }
+            if !refs.is_empty() {
+                r.set_interpolated(path, refs);
+            }
+        }
+        Value::Array(a) => {
+            for (i, v) in a.iter_mut().enumerate() {
+                walk(v, &format!("{path}[{i}]"), r);
+            }
+        }
+        Value::Table(t) => {
+            for (key, v) in t.iter_mut() {
+                walk(v, &join(path, key), r);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Expand `s`, noting each reference resolved in `refs` ("env NAME" or
+/// "file PATH").
+fn expand(s: &str, dir: &Path, refs: &mut Vec<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(tail) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("${") {
+            let end = tail.find('}').ok_or("unterminated \"${\"")?;
+            out.push_str(&resolve(&tail[..end], dir, refs)?);
+            rest = &tail[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn resolve(reference: &str, dir: &Path, refs: &mut Vec<String>) -> Result<String, String> {
+    if let Some(file) = reference.strip_prefix("file:") {
+        let path = dir.join(file);
+        refs.push(format!("file {}", path.display()));
+        return std::fs::read_to_string(&path)
+            .map(|text| text.trim_end_matches(['\n', '\r']).to_string())
+            .map_err(|e| format!("cannot read {}: {e}", path.display()));
+    }
+    let (name, default) = match reference.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (reference, None),
+    };
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("invalid reference \"${{{reference}}}\""));
+    }
+    refs.push(format!("env {name}"));
+    match (std::env::var(name), default) {
+        (Ok(value), _) => Ok(value),
+        (Err(_), Some(default)) => Ok(default.to_string()),
+        (Err(std::env::VarError::NotUnicode(_)), None) => {
+            Err(format!("environment variable {name} is not valid UTF-8"))
+        }
+        (Err(std::env::VarError::NotPresent), None) => {
+            Err(format!("environment variable {name} is not set"))
+        }
+    }
+}
@@ -1,175 +1,950 @@
+mod audit;
+mod dump;
+mod glob;
+mod include;
+mod interpolate;
+mod migrate;
 mod model;
-pub use model::Config;
+mod net;
+mod report;
+mod schema;
+mod units;
+pub use audit::{audit, rule, Audit, Finding, Risk, Rule, RULES};
+pub use dump::{dump, Dump};
+pub use glob::Glob;
+pub use migrate::{migrate, Change, Migrated, CONFIG_VERSION};
+pub use model::{
+    Acme, AcmeChallenge, Admin, Affinity, AffinityMode, Algorithm, AuditConfig, CacheRule,
+    Compression, Config, Control, ErrorFormat, ErrorPages, FileCache, ForwardAuth, Listener,
+    Listing, LogLevel, Profile, Proxy, ProxyProtocol, ProxyProtocolVersion, ProxyRoute, SameSite,
+    SplitOverride, SplitTarget, StaticSite, Symlinks, TrafficSplit, TryFile, Upload, UploadUser,
+    UpstreamPool,
+};
+pub use net::{any_contains, Cidr};
+pub use report::{Code, Diagnostic, Report, Severity, Span};
+pub use schema::schema;
+pub use units::{format_duration, parse_duration, parse_size};
 
 use luciuz_core::{error::LuciuzError, Result};
 
+/// Load and validate `path`. Any error fails the load; use [`load`] to also
+/// get the warnings, or [`check_path`] for the full report.
 pub fn load_from_path(path: &str) -> Result<Config> {
+    load(path).map(|(cfg, _)| cfg)
+}
+
+/// Load and validate `path`, returning the config with its report (which
+/// then only holds warnings). All errors are joined in the error message.
+pub fn load(path: &str) -> Result<(Config, Report)> {
+    match check_path(path)? {
+        (Some(cfg), report) if !report.has_errors() => Ok((cfg, report)),
+        (_, report) => Err(LuciuzError::Config(report.summary())),
+    }
+}
+
+/// Parse and validate `path` and the files it includes, collecting every
+/// error and warning. The config is `None` when a file does not parse or
+/// the merged result does not fit the model; only reading `path` itself
+/// fails outright.
+pub fn check_path(path: &str) -> Result<(Option<Config>, Report)> {
     let raw = std::fs::read_to_string(path).map_err(|e| LuciuzError::Io(e.to_string()))?;
+    Ok(check_str(path, &raw))
+}
 
-    let cfg: Config = toml::from_str(&raw).map_err(|e| LuciuzError::Config(e.to_string()))?;
+/// [`check_path`] on `raw`, reported as coming from `file`; includes are
+/// resolved relative to `file`'s directory.
+pub fn check_str(file: &str, raw: &str) -> (Option<Config>, Report) {
+    let mut report = Report::new(file);
+    let Some(mut merged) = include::read(file, raw, &mut report) else {
+        report.locate();
+        return (None, report);
+    };
+    report.set_merged(merged.clone());
+    migrate::warn_deprecated(&merged, &mut report);
+    interpolate::interpolate(&mut merged, &mut report);
+    let cfg = match serde_path_to_error::deserialize::<_, Config>(toml::Value::Table(merged)) {
+        Ok(cfg) => {
+            validate(&cfg, &mut report);
+            Some(cfg)
+        }
+        Err(e) => {
+            let path = e.path().to_string();
+            let path = if path == "." { "" } else { path.as_str() };
+            report.invalid(path, e.inner().message());
+            None
+        }
+    };
+    report.locate();
+    (cfg, report)
+}
 
-    validate(&cfg)?;
-    Ok(cfg)
+/// Validate `cfg` again, after code changed some of its values (e.g. a
+/// reload keeping restart-only settings). Diagnostics name `file` but have
+/// no line.
+pub fn revalidate(file: &str, cfg: &Config) -> Report {
+    let mut report = Report::new(file);
+    validate(cfg, &mut report);
+    report
 }
 
-fn validate(cfg: &Config) -> Result<()> {
-    let http_listen_empty = cfg.server.http_listen.trim().is_empty();
+fn validate(cfg: &Config, r: &mut Report) {
+    if cfg.version == 0 || cfg.version > CONFIG_VERSION {
+        r.error(
+            Code::OutOfRange,
+            "version",
+            format!(
+                "version {} is not a config version this luciuz reads (1 to {CONFIG_VERSION})",
+                cfg.version
+            ),
+        );
+    }
+
+    let server = &cfg.server;
+    if server.http_listen.is_none()
+        && !(cfg.acme.enabled && cfg.acme.challenge == AcmeChallenge::TlsAlpn01)
+    {
+        r.error(
+            Code::Missing,
+            "server.http_listen",
+            "server.http_listen is empty (required unless acme.challenge=tls-alpn-01)",
+        );
+    }
+
+    validate_profile(cfg, r);
+
+    if let Some(s) = &cfg.static_site {
+        validate_static_site(s, r);
+    }
+
+    if let Some(proxy) = &cfg.proxy {
+        validate_proxy(proxy, r);
+    }
+
+    if let Some(e) = &cfg.errors {
+        validate_error_pages("errors", e, r);
+    }
 
-    if http_listen_empty {
-        let ok = cfg.acme.enabled && cfg.acme.challenge == "tls-alpn-01";
-        if !ok {
-            return Err(LuciuzError::Config(
-                "server.http_listen is empty (required unless acme.challenge=tls-alpn-01)".into(),
-            ));
+    if let Some(c) = &cfg.compression {
+        validate_compression(c, r);
+    }
+
+    if let Some(c) = cfg
+        .control
+        .as_ref()
+        .filter(|c| !c.listen.ip().is_loopback())
+    {
+        r.error(
+            Code::Malformed,
+            "control.listen",
+            format!(
+                "control.listen must be a loopback address (got: {})",
+                c.listen
+            ),
+        );
+    }
+
+    if server.hsts && server.hsts_max_age.is_zero() {
+        r.error(
+            Code::OutOfRange,
+            "server.hsts_max_age",
+            "server.hsts_max_age must be > 0 when hsts=true",
+        );
+    }
+
+    if cfg.acme.enabled {
+        if cfg.acme.domains.is_empty() {
+            r.error(
+                Code::Missing,
+                "acme.domains",
+                "acme.enabled=true but acme.domains is empty",
+            );
+        }
+        if cfg.acme.email.trim().is_empty() {
+            r.error(
+                Code::Missing,
+                "acme.email",
+                "acme.enabled=true but acme.email is empty",
+            );
         }
     }
-    if cfg.server.https_listen.trim().is_empty() {
-        return Err(LuciuzError::Config("server.https_listen is empty".into()));
+
+    for (i, net) in server.trusted_proxies.iter().enumerate() {
+        if !net.is_any() {
+            continue;
+        }
+        if server.profile == Profile::AdminPanel {
+            r.error(
+                Code::Conflict,
+                format!("server.trusted_proxies[{i}]"),
+                format!(
+                    "server.trusted_proxies lets any client set its own address ({net}), \
+                     and so pass admin.allow"
+                ),
+            );
+        } else {
+            r.warn(
+                Code::TrustsAnyone,
+                format!("server.trusted_proxies[{i}]"),
+                format!("server.trusted_proxies lets any client set its own address ({net})"),
+            );
+        }
     }
 
-    match cfg.server.profile.as_str() {
-        "static_site" | "public_api" | "admin_panel" => {}
-        other => {
-            return Err(LuciuzError::Config(format!(
-                "server.profile invalid: {other} (allowed: static_site|public_api|admin_panel)"
-            )))
+    if let Some(pp) = &server.proxy_protocol {
+        if pp.listeners.is_empty() {
+            r.error(
+                Code::Missing,
+                "server.proxy_protocol.listeners",
+                "server.proxy_protocol.listeners is empty",
+            );
+        }
+        if pp.allowed_sources.is_empty() {
+            r.error(
+                Code::Missing,
+                "server.proxy_protocol.allowed_sources",
+                "server.proxy_protocol.allowed_sources is empty",
+            );
+        }
+        if pp.header_timeout.is_zero() {
+            r.error(
+                Code::OutOfRange,
+                "server.proxy_protocol.header_timeout",
+                "server.proxy_protocol.header_timeout must be > 0",
+            );
         }
     }
 
-    if cfg.server.profile == "static_site" {
-        let s = cfg.static_site.as_ref().ok_or_else(|| {
-            LuciuzError::Config(
-                "server.profile=static_site but [static_site] section is missing".into(),
-            )
-        })?;
+    let by = server.forwarded_by.trim();
+    if by.is_empty() || by.contains([' ', ';', ',', '"']) {
+        r.error(
+            Code::Malformed,
+            "server.forwarded_by",
+            format!("server.forwarded_by is not a valid Forwarded node: {by}"),
+        );
+    }
 
-        if s.root.trim().is_empty() {
-            return Err(LuciuzError::Config("static_site.root is empty".into()));
+    if let Some(host) = &server.canonical_host {
+        if host.trim().is_empty() {
+            r.error(
+                Code::Missing,
+                "server.canonical_host",
+                "server.canonical_host is empty",
+            );
+        } else if host.contains(' ') {
+            r.error(
+                Code::Malformed,
+                "server.canonical_host",
+                "server.canonical_host must not contain spaces",
+            );
         }
+    }
 
-        if s.index.trim().is_empty() {
-            return Err(LuciuzError::Config("static_site.index is empty".into()));
+    for (i, name) in cfg.audit.allow.iter().enumerate() {
+        if audit::rule(name).is_none() {
+            r.error(
+                Code::InvalidChoice,
+                format!("audit.allow[{i}]"),
+                format!("audit.allow: unknown rule {name:?} (see luciuz audit --list-rules)"),
+            );
         }
+    }
+}
+
+/// The profile's required sections, and the sections it leaves unused.
+fn validate_profile(cfg: &Config, r: &mut Report) {
+    let profile = cfg.server.profile;
+    let routes = cfg.proxy.as_ref().map_or(0, |p| p.routes.len());
+    match profile {
+        Profile::StaticSite if cfg.static_site.is_none() => r.error(
+            Code::Missing,
+            "server.profile",
+            "server.profile=static_site but [static_site] section is missing",
+        ),
+        Profile::PublicApi if cfg.proxy.is_none() => r.error(
+            Code::Missing,
+            "server.profile",
+            "server.profile=public_api but [proxy] section is missing",
+        ),
+        Profile::PublicApi if routes == 0 => r.error(
+            Code::Missing,
+            "proxy",
+            "server.profile=public_api but proxy.routes is empty",
+        ),
+        Profile::Site if cfg.static_site.is_none() && routes == 0 => r.error(
+            Code::Missing,
+            "server.profile",
+            "server.profile=site needs a [static_site] section or proxy.routes",
+        ),
+        Profile::AdminPanel if cfg.admin.is_none() => r.error(
+            Code::Missing,
+            "server.profile",
+            "server.profile=admin_panel but [admin] section is missing (the panel would be \
+             public)",
+        ),
+        _ => {}
+    }
+
+    match &cfg.admin {
+        Some(admin) if profile == Profile::AdminPanel => validate_admin(cfg, admin, r),
+        Some(_) => r.warn(
+            Code::Ignored,
+            "admin",
+            format!("[admin] is not used with server.profile={profile}"),
+        ),
+        None => {}
+    }
 
-        if let Some(cc) = &s.cache_control {
-            if cc.trim().is_empty() {
-                return Err(LuciuzError::Config(
-                    "static_site.cache_control is empty".into(),
-                ));
+    if cfg.static_site.is_some() && profile == Profile::PublicApi {
+        r.warn(
+            Code::Ignored,
+            "static_site",
+            format!("[static_site] is not served with server.profile={profile}"),
+        );
+    }
+    if cfg.proxy.is_some() && profile == Profile::StaticSite {
+        r.warn(
+            Code::Ignored,
+            "proxy",
+            format!("[proxy] is not served with server.profile={profile}"),
+        );
+    }
+}
+
+fn validate_proxy(proxy: &model::Proxy, r: &mut Report) {
+    for (i, route) in proxy.routes.iter().enumerate() {
+        validate_route(proxy, route, i, r);
+    }
+
+    for (name, pool) in &proxy.pools {
+        let at = format!("proxy.pools.{name}");
+        if pool.targets.is_empty() {
+            r.error(
+                Code::Missing,
+                format!("{at}.targets"),
+                format!("{at}.targets is empty"),
+            );
+        }
+        for (i, t) in pool.targets.iter().enumerate() {
+            if !(t.starts_with("http://") || t.starts_with("https://")) {
+                r.error(
+                    Code::Malformed,
+                    format!("{at}.targets[{i}]"),
+                    format!("{at}.targets must start with http:// or https:// (got: {t})"),
+                );
             }
         }
+        if let Some(a) = &pool.affinity {
+            validate_affinity(&at, a, r);
+        }
+        validate_send_proxy_protocol(&at, pool.send_proxy_protocol, &pool.targets, r);
+
+        let used = proxy.routes.iter().any(|route| {
+            route.pool.as_deref() == Some(name.as_str())
+                || route
+                    .split
+                    .as_ref()
+                    .is_some_and(|s| s.targets.iter().any(|t| &t.pool == name))
+        });
+        if !used {
+            r.warn(
+                Code::Unused,
+                at.clone(),
+                format!("{at} is not used by any route"),
+            );
+        }
+    }
+}
+
+fn validate_route(proxy: &model::Proxy, route: &model::ProxyRoute, i: usize, r: &mut Report) {
+    let at = format!("proxy.routes[{i}]");
+    if route.prefix.trim().is_empty() {
+        r.error(
+            Code::Missing,
+            format!("{at}.prefix"),
+            format!("{at}.prefix is empty"),
+        );
+    } else if !route.prefix.starts_with('/') {
+        r.error(
+            Code::Malformed,
+            format!("{at}.prefix"),
+            format!("{at}.prefix must start with '/' (got: {})", route.prefix),
+        );
     }
 
-    if cfg.server.profile == "public_api" {
-        let p = cfg.proxy.as_ref().ok_or_else(|| {
-            LuciuzError::Config("server.profile=public_api but [proxy] section is missing".into())
-        })?;
+    let has_upstream = !route.upstream.trim().is_empty();
+    if has_upstream
+        && !(route.upstream.starts_with("http://") || route.upstream.starts_with("https://"))
+    {
+        r.error(
+            Code::Malformed,
+            format!("{at}.upstream"),
+            format!(
+                "{at}.upstream must start with http:// or https:// (got: {})",
+                route.upstream
+            ),
+        );
+    }
+    let targets = [has_upstream, route.pool.is_some(), route.split.is_some()];
+    match targets.iter().filter(|t| **t).count() {
+        1 => {}
+        0 => r.error(
+            Code::Missing,
+            at.clone(),
+            format!("{at} needs one of upstream, pool or split"),
+        ),
+        _ => r.error(
+            Code::Conflict,
+            at.clone(),
+            format!("{at} must set only one of upstream, pool or split"),
+        ),
+    }
 
-        if p.routes.is_empty() {
-            return Err(LuciuzError::Config("proxy.routes is empty".into()));
+    let mut known_pool = |path: String, name: &str| {
+        if !proxy.pools.contains_key(name) {
+            r.error(
+                Code::UnknownReference,
+                path,
+                format!("{at} references unknown pool: {name}"),
+            );
+        }
+    };
+    if let Some(pool) = &route.pool {
+        known_pool(format!("{at}.pool"), pool);
+    }
+    if let Some(split) = &route.split {
+        for (j, t) in split.targets.iter().enumerate() {
+            known_pool(format!("{at}.split.targets[{j}].pool"), &t.pool);
         }
+    }
 
-        for r in &p.routes {
-            if r.prefix.trim().is_empty() {
-                return Err(LuciuzError::Config("proxy.routes[].prefix is empty".into()));
-            }
-            if !r.prefix.starts_with('/') {
-                return Err(LuciuzError::Config(
-                    "proxy.routes[].prefix must start with '/'".into(),
-                ));
+    if route.send_proxy_protocol.is_some() && !has_upstream {
+        r.error(
+            Code::Conflict,
+            format!("{at}.send_proxy_protocol"),
+            format!("{at}.send_proxy_protocol only applies to upstream (set it on the pool)"),
+        );
+    } else {
+        validate_send_proxy_protocol(
+            &at,
+            route.send_proxy_protocol,
+            std::slice::from_ref(&route.upstream),
+            r,
+        );
+    }
+
+    if let Some(split) = &route.split {
+        if split.targets.is_empty() {
+            r.error(
+                Code::Missing,
+                format!("{at}.split.targets"),
+                format!("{at}.split.targets is empty"),
+            );
+        } else if split.targets.iter().all(|t| t.weight == 0) {
+            r.error(
+                Code::OutOfRange,
+                format!("{at}.split.targets"),
+                format!("{at}.split weights must not all be 0"),
+            );
+        }
+        for (j, o) in split.overrides.iter().enumerate() {
+            let keys = [&o.header, &o.cookie, &o.query];
+            if keys.iter().filter(|k| k.is_some()).count() != 1 {
+                r.error(
+                    Code::Conflict,
+                    format!("{at}.split.overrides[{j}]"),
+                    format!("{at}.split.overrides needs exactly one of header, cookie or query"),
+                );
             }
-            if r.upstream.trim().is_empty() {
-                return Err(LuciuzError::Config(
-                    "proxy.routes[].upstream is empty".into(),
-                ));
+            if !split.targets.iter().any(|t| t.pool == o.pool) {
+                r.error(
+                    Code::UnknownReference,
+                    format!("{at}.split.overrides[{j}].pool"),
+                    format!("{at}.split.overrides pool {} is not a split target", o.pool),
+                );
             }
-            if !(r.upstream.starts_with("http://") || r.upstream.starts_with("https://")) {
-                return Err(LuciuzError::Config(
-                    "proxy.routes[].upstream must start with http:// or https://".into(),
-                ));
+        }
+        if let Some(cookie) = &split.sticky_cookie {
+            if cookie.trim().is_empty() || cookie.contains([' ', ';', '=', ',']) {
+                r.error(
+                    Code::Malformed,
+                    format!("{at}.split.sticky_cookie"),
+                    format!("{at}.split.sticky_cookie is not a valid cookie name"),
+                );
             }
         }
     }
 
-    // --- proxy validation (optional)
-    if let Some(proxy) = &cfg.proxy {
-        for (i, r) in proxy.routes.iter().enumerate() {
-            if r.prefix.trim().is_empty() {
-                return Err(LuciuzError::Config(format!(
-                    "proxy.routes[{i}].prefix is empty"
-                )));
+    if let Some(e) = &route.errors {
+        validate_error_pages(&format!("{at}.errors"), e, r);
+    }
+}
+
+fn validate_static_site(s: &model::StaticSite, r: &mut Report) {
+    if s.root.trim().is_empty() {
+        r.error(
+            Code::Missing,
+            "static_site.root",
+            "static_site.root is empty",
+        );
+    }
+
+    if s.index.trim().is_empty() {
+        r.error(
+            Code::Missing,
+            "static_site.index",
+            "static_site.index is empty",
+        );
+    }
+
+    if s.cache_control
+        .as_ref()
+        .is_some_and(|cc| cc.trim().is_empty())
+    {
+        r.error(
+            Code::Missing,
+            "static_site.cache_control",
+            "static_site.cache_control is empty",
+        );
+    }
+
+    for (i, p) in s.precompressed.iter().enumerate() {
+        if s.precompressed[..i].contains(p) {
+            r.error(
+                Code::Duplicate,
+                format!("static_site.precompressed[{i}]"),
+                format!("static_site.precompressed lists {p} twice"),
+            );
+        }
+    }
+
+    for (i, rule) in s.cache_rules.iter().enumerate() {
+        let at = format!("static_site.cache_rules[{i}]");
+        if let Err(e) = rule.pattern.parse::<Glob>() {
+            r.error(
+                Code::Malformed,
+                format!("{at}.pattern"),
+                format!("{at}.pattern: {e}"),
+            );
+        }
+        if rule.cache_control.trim().is_empty() {
+            r.error(
+                Code::Missing,
+                format!("{at}.cache_control"),
+                format!("{at}.cache_control is empty"),
+            );
+        }
+    }
+
+    for (i, t) in s.try_files.iter().enumerate() {
+        let at = format!("static_site.try_files[{i}]");
+        let path = t.path();
+        if !(path.starts_with('/') || path.starts_with("$uri")) {
+            r.error(
+                Code::Malformed,
+                at.clone(),
+                format!("{at} must start with '/' or $uri (got: {path})"),
+            );
+        }
+        for (j, p) in t.exclude().iter().enumerate() {
+            if !p.starts_with('/') {
+                r.error(
+                    Code::Malformed,
+                    format!("{at}.exclude[{j}]"),
+                    format!("{at}.exclude entries must start with '/' (got: {p})"),
+                );
             }
-            if !r.prefix.starts_with('/') {
-                return Err(LuciuzError::Config(format!(
-                    "proxy.routes[{i}].prefix must start with '/' (got: {})",
-                    r.prefix
-                )));
+        }
+    }
+
+    if s.not_found.as_ref().is_some_and(|f| f.trim().is_empty()) {
+        r.error(
+            Code::Missing,
+            "static_site.not_found",
+            "static_site.not_found is empty",
+        );
+    }
+
+    for (i, h) in s.hidden_allow.iter().enumerate() {
+        if !h.starts_with('.') || h.contains('/') {
+            r.error(
+                Code::Malformed,
+                format!("static_site.hidden_allow[{i}]"),
+                format!(
+                    "static_site.hidden_allow entries must be names starting with '.' (got: {h})"
+                ),
+            );
+        }
+    }
+
+    for (i, e) in s.deny_extensions.iter().enumerate() {
+        if e.trim_start_matches('.').is_empty() || e.contains('/') {
+            r.error(
+                Code::Malformed,
+                format!("static_site.deny_extensions[{i}]"),
+                format!("static_site.deny_extensions entry is not an extension: {e:?}"),
+            );
+        }
+    }
+
+    if let Some(c) = &s.cache {
+        if c.max_file_bytes == 0 || c.max_file_bytes > c.max_bytes {
+            r.error(
+                Code::OutOfRange,
+                "static_site.cache.max_file_bytes",
+                format!(
+                    "static_site.cache.max_file_bytes must be in 1..=max_bytes ({})",
+                    c.max_bytes
+                ),
+            );
+        }
+    }
+
+    if let Some(l) = &s.listing {
+        if l.prefixes.is_empty() {
+            r.error(
+                Code::Missing,
+                "static_site.listing.prefixes",
+                "static_site.listing.prefixes is empty",
+            );
+        }
+        for (i, p) in l.prefixes.iter().enumerate() {
+            if !p.starts_with('/') {
+                r.error(
+                    Code::Malformed,
+                    format!("static_site.listing.prefixes[{i}]"),
+                    format!("static_site.listing.prefixes entries must start with '/' (got: {p})"),
+                );
             }
-            if r.upstream.trim().is_empty() {
-                return Err(LuciuzError::Config(format!(
-                    "proxy.routes[{i}].upstream is empty"
-                )));
+        }
+        for (i, h) in l.hide.iter().enumerate() {
+            if let Err(e) = h.parse::<Glob>() {
+                r.error(
+                    Code::Malformed,
+                    format!("static_site.listing.hide[{i}]"),
+                    format!("static_site.listing.hide[{i}]: {e}"),
+                );
             }
         }
     }
 
-    if cfg.server.hsts && cfg.server.hsts_max_age == 0 {
-        return Err(LuciuzError::Config(
-            "server.hsts_max_age must be > 0 when hsts=true".into(),
-        ));
+    if let Some(u) = &s.upload {
+        validate_upload(u, r);
     }
 
-    if cfg.acme.enabled {
-        if cfg.acme.domains.is_empty() {
-            return Err(LuciuzError::Config(
-                "acme.enabled=true but acme.domains is empty".into(),
-            ));
+    if s.is_archive() {
+        // These work on the file system; an archive root is served from memory.
+        let unsupported = [
+            (s.listing.is_some(), "listing"),
+            (s.cache.is_some(), "cache"),
+            (s.upload.is_some(), "upload"),
+            (s.symlinks != Symlinks::Follow, "symlinks"),
+        ];
+        for (_, key) in unsupported.iter().filter(|(set, _)| *set) {
+            r.error(
+                Code::Conflict,
+                format!("static_site.{key}"),
+                format!(
+                    "static_site.{key} is not supported with an archive root ({})",
+                    s.root
+                ),
+            );
         }
-        if cfg.acme.email.trim().is_empty() {
-            return Err(LuciuzError::Config(
-                "acme.enabled=true but acme.email is empty".into(),
-            ));
+    }
+}
+
+fn validate_upload(u: &model::Upload, r: &mut Report) {
+    let prefix = u.prefix.trim_end_matches('/');
+    if !u.prefix.starts_with('/') || prefix.is_empty() || prefix.contains(['{', '}', '*']) {
+        r.error(
+            Code::Malformed,
+            "static_site.upload.prefix",
+            format!(
+                "static_site.upload.prefix must be a path below '/' (got: {})",
+                u.prefix
+            ),
+        );
+    }
+    if u.max_body_bytes == 0 {
+        r.error(
+            Code::OutOfRange,
+            "static_site.upload.max_body_bytes",
+            "static_site.upload.max_body_bytes must be > 0",
+        );
+    }
+    if u.users.is_empty() {
+        r.error(
+            Code::Missing,
+            "static_site.upload.users",
+            "static_site.upload.users is empty",
+        );
+    }
+    validate_users("static_site.upload.users", &u.users, r);
+}
+
+/// Basic authentication accounts at `at`.
+fn validate_users(at: &str, users: &[model::UploadUser], r: &mut Report) {
+    for (i, user) in users.iter().enumerate() {
+        let at_user = format!("{at}[{i}]");
+        if user.name.is_empty() || user.name.contains(':') {
+            r.error(
+                Code::Malformed,
+                format!("{at_user}.name"),
+                format!("{at_user}.name must be non-empty, without ':'"),
+            );
+        } else if users[..i].iter().any(|o| o.name == user.name) {
+            r.error(
+                Code::Duplicate,
+                format!("{at_user}.name"),
+                format!("{at} lists {} twice", user.name),
+            );
+        }
+        let argon2id = password_hash::PasswordHash::new(&user.password_hash)
+            .is_ok_and(|h| h.algorithm.as_str() == "argon2id" && h.hash.is_some());
+        if !argon2id {
+            r.error(
+                Code::Malformed,
+                format!("{at_user}.password_hash"),
+                format!(
+                    "{at_user}.password_hash must be an Argon2id PHC string \
+                     ($argon2id$v=19$...); make one with luciuz hash-password"
+                ),
+            );
+        }
+    }
+}
+
+/// `[admin]`: an allowlist that is not everyone, and some authentication.
+fn validate_admin(cfg: &Config, admin: &model::Admin, r: &mut Report) {
+    if admin.allow.is_empty() {
+        r.error(
+            Code::Missing,
+            "admin.allow",
+            "admin.allow is empty (list the networks the panel is reached from)",
+        );
+    }
+    for (i, net) in admin.allow.iter().enumerate() {
+        if net.is_any() {
+            r.error(
+                Code::OutOfRange,
+                format!("admin.allow[{i}]"),
+                format!("admin.allow lets any client reach the panel ({net})"),
+            );
         }
+    }
 
-        match cfg.acme.challenge.as_str() {
-            "http-01" | "tls-alpn-01" => {}
-            other => {
-                return Err(LuciuzError::Config(format!(
-                    "acme.challenge invalid: {other} (allowed: http-01|tls-alpn-01)"
-                )));
-            }
+    if admin.users.is_empty() && admin.client_ca.is_none() && admin.forward_auth.is_none() {
+        r.error(
+            Code::Missing,
+            "admin",
+            "[admin] authenticates no one: set users, client_ca or forward_auth",
+        );
+    }
+    if !admin.users.is_empty() && admin.forward_auth.is_some() {
+        r.error(
+            Code::Conflict,
+            "admin.forward_auth",
+            "admin.users and admin.forward_auth both authenticate with the Authorization \
+             header; keep one",
+        );
+    }
+    if !admin.users.is_empty() && !cfg.acme.enabled {
+        r.error(
+            Code::Conflict,
+            "admin.users",
+            "admin.users needs TLS, which luciuz serves with acme.enabled=true (Basic auth \
+             would send passwords in cleartext)",
+        );
+    }
+    validate_users("admin.users", &admin.users, r);
+
+    if let Some(ca) = &admin.client_ca {
+        if ca.trim().is_empty() {
+            r.error(Code::Missing, "admin.client_ca", "admin.client_ca is empty");
+        } else if !cfg.acme.enabled {
+            r.error(
+                Code::Conflict,
+                "admin.client_ca",
+                "admin.client_ca needs TLS, which luciuz serves with acme.enabled=true",
+            );
+        } else if cfg.acme.challenge == AcmeChallenge::TlsAlpn01 {
+            r.error(
+                Code::Conflict,
+                "admin.client_ca",
+                "admin.client_ca cannot be used with acme.challenge=tls-alpn-01: the CA's \
+                 validation connects without a client certificate",
+            );
         }
     }
 
-    // --- proxy validation (optional)
-    if let Some(proxy) = &cfg.proxy {
-        for r in &proxy.routes {
-            if !r.prefix.starts_with('/') {
-                return Err(LuciuzError::Config(format!(
-                    "proxy.routes.prefix must start with '/': {}",
-                    r.prefix
-                )));
+    if let Some(fa) = &admin.forward_auth {
+        if !(fa.url.starts_with("http://") || fa.url.starts_with("https://")) {
+            r.error(
+                Code::Malformed,
+                "admin.forward_auth.url",
+                format!(
+                    "admin.forward_auth.url must start with http:// or https:// (got: {})",
+                    fa.url
+                ),
+            );
+        }
+        for (i, name) in fa.copy_headers.iter().enumerate() {
+            if !is_token(name) {
+                r.error(
+                    Code::Malformed,
+                    format!("admin.forward_auth.copy_headers[{i}]"),
+                    format!("admin.forward_auth.copy_headers: {name:?} is not a header name"),
+                );
             }
+        }
+        if fa.timeout.is_zero() {
+            r.error(
+                Code::OutOfRange,
+                "admin.forward_auth.timeout",
+                "admin.forward_auth.timeout must be > 0",
+            );
+        }
+    }
 
-            if r.upstream.trim().is_empty() {
-                return Err(LuciuzError::Config(format!(
-                    "proxy.routes.upstream is empty for prefix {}",
-                    r.prefix
-                )));
-            }
+    if admin.rate_limit == 0 {
+        r.error(
+            Code::OutOfRange,
+            "admin.rate_limit",
+            "admin.rate_limit must be > 0",
+        );
+    }
+    if admin.max_body_bytes == 0 {
+        r.error(
+            Code::OutOfRange,
+            "admin.max_body_bytes",
+            "admin.max_body_bytes must be > 0",
+        );
+    }
+}
+
+/// An HTTP token (RFC 9110), as header names are.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+fn validate_compression(c: &model::Compression, r: &mut Report) {
+    if c.algorithms.is_empty() {
+        r.error(
+            Code::Missing,
+            "compression.algorithms",
+            "compression.algorithms is empty",
+        );
+    }
+    for (i, a) in c.algorithms.iter().enumerate() {
+        if c.algorithms[..i].contains(a) {
+            r.error(
+                Code::Duplicate,
+                format!("compression.algorithms[{i}]"),
+                format!("compression.algorithms lists {a} twice"),
+            );
+        }
+    }
+    for (i, m) in c.mime_types.iter().enumerate() {
+        if m.split_once('/')
+            .is_none_or(|(t, s)| t.is_empty() || s.is_empty())
+        {
+            r.error(
+                Code::Malformed,
+                format!("compression.mime_types[{i}]"),
+                format!("compression.mime_types entry is not a media type: {m}"),
+            );
         }
     }
+    let levels = [
+        ("gzip_level", c.gzip_level, model::GZIP_LEVELS),
+        ("brotli_level", c.brotli_level, model::BROTLI_LEVELS),
+        ("zstd_level", c.zstd_level, model::ZSTD_LEVELS),
+    ];
+    for (name, level, range) in levels {
+        if !range.contains(&level) {
+            r.error(
+                Code::OutOfRange,
+                format!("compression.{name}"),
+                format!(
+                    "compression.{name} must be in {}..={} (got: {level})",
+                    range.start(),
+                    range.end()
+                ),
+            );
+        }
+    }
+}
 
-    if let Some(host) = &cfg.server.canonical_host {
-        if host.trim().is_empty() {
-            return Err(LuciuzError::Config("server.canonical_host is empty".into()));
+fn validate_error_pages(at: &str, e: &model::ErrorPages, r: &mut Report) {
+    for (code, path) in &e.pages {
+        if !code.parse::<u16>().is_ok_and(|c| (400..=599).contains(&c)) {
+            r.error(
+                Code::OutOfRange,
+                format!("{at}.pages.{code}"),
+                format!("{at}.pages key must be a 4xx/5xx status code (got: {code})"),
+            );
         }
-        if host.contains(' ') {
-            return Err(LuciuzError::Config(
-                "server.canonical_host must not contain spaces".into(),
-            ));
+        if path.trim().is_empty() {
+            r.error(
+                Code::Missing,
+                format!("{at}.pages.{code}"),
+                format!("{at}.pages.{code} is empty"),
+            );
         }
     }
+    for (i, c) in e.intercept_status.iter().enumerate() {
+        if !(400..=599).contains(c) {
+            r.error(
+                Code::OutOfRange,
+                format!("{at}.intercept_status[{i}]"),
+                format!("{at}.intercept_status must only list 4xx/5xx codes (got: {c})"),
+            );
+        }
+    }
+}
 
-    Ok(())
+fn validate_send_proxy_protocol(
+    at: &str,
+    version: Option<ProxyProtocolVersion>,
+    targets: &[String],
+    r: &mut Report,
+) {
+    if version.is_none() {
+        return;
+    }
+    // The header is written before any TLS handshake, which the PROXY
+    // protocol client does not do.
+    if let Some(t) = targets.iter().find(|t| !t.starts_with("http://")) {
+        r.error(
+            Code::Conflict,
+            format!("{at}.send_proxy_protocol"),
+            format!("{at}.send_proxy_protocol requires http:// targets (got: {t})"),
+        );
+    }
+}
+
+fn validate_affinity(pool: &str, a: &model::Affinity, r: &mut Report) {
+    let at = format!("{pool}.affinity");
+    if a.name.trim().is_empty() || a.name.contains([' ', ';', '=', ',']) {
+        r.error(
+            Code::Malformed,
+            format!("{at}.name"),
+            format!("{at}.name is not a valid cookie or header name"),
+        );
+    }
+    if a.same_site == SameSite::None && !a.secure {
+        r.error(
+            Code::Conflict,
+            format!("{at}.same_site"),
+            format!("{at}.same_site=None requires secure=true"),
+        );
+    }
+    if a.secret.as_ref().is_some_and(|s| s.len() < 32) {
+        r.error(
+            Code::OutOfRange,
+            format!("{at}.secret"),
+            format!("{at}.secret must be at least 32 bytes"),
+        );
+    }
 }
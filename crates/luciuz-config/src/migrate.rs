@@ -0,0 +1,238 @@
+//! Config format versions and `luciuz config migrate`, which rewrites older
+//! files to [`CONFIG_VERSION`]. Only the renamed keys and their values are
+//! edited in place, so comments and layout are kept.
+
+use std::ops::Range;
+use std::time::Duration;
+
+use luciuz_core::{error::LuciuzError, Result};
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+use crate::include::join;
+use crate::report::{Code, Report};
+use crate::units::format_duration;
+
+/// The config format this build writes. Files without `version` are
+/// version 1.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Keys renamed by version 2, as (table, old key, new key): durations
+/// lost their `_secs` suffix since they take units. `*` in a table path
+/// stands for any key, `[]` for any array index. The old keys are still
+/// read, with a warning, until version 3.
+pub(crate) const RENAMED: &[(&str, &str, &str)] = &[("timeouts", "handler_secs", "handler")];
+
+/// One file as `luciuz config migrate` rewrites it.
+#[derive(Debug, Clone)]
+pub struct Migrated {
+    pub file: String,
+    /// The new content; the old one when `changes` is empty.
+    pub text: String,
+    pub changes: Vec<Change>,
+}
+
+/// One edit, explained.
+#[derive(Debug, Clone)]
+pub struct Change {
+    /// Line in the original file.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Rewrite `path` and the files it includes to [`CONFIG_VERSION`]: renamed
+/// keys get their new names, seconds given as bare numbers get units, and
+/// `version` is set in `path` (and updated where an include has one).
+pub fn migrate(path: &str) -> Result<Vec<Migrated>> {
+    let (_, report) = crate::check_path(path)?;
+    let mut out = Vec::new();
+    for (i, file) in report.files().enumerate() {
+        let (text, changes) = migrate_str(report.source_text(i), i == 0)
+            .map_err(|e| LuciuzError::Config(format!("{file}: {e}")))?;
+        out.push(Migrated {
+            file: file.to_string(),
+            text,
+            changes,
+        });
+    }
+    Ok(out)
+}
+
+fn migrate_str(text: &str, main: bool) -> std::result::Result<(String, Vec<Change>), String> {
+    let doc = ImDocument::parse(text).map_err(|e| e.message().to_string())?;
+    let root = doc.as_table();
+    let mut edits: Vec<(Range<usize>, String, String)> = Vec::new();
+
+    match root.get_key_value("version") {
+        Some((_, item)) => {
+            let version = item
+                .as_integer()
+                .ok_or("version must be an integer".to_string())?;
+            if version > CONFIG_VERSION.into() {
+                return Err(format!(
+                    "version {version} is newer than this luciuz reads ({CONFIG_VERSION})"
+                ));
+            }
+            if version < CONFIG_VERSION.into() {
+                let span = item.span().expect("parsed values have spans");
+                edits.push((
+                    span,
+                    CONFIG_VERSION.to_string(),
+                    format!("version {version} updated to {CONFIG_VERSION}"),
+                ));
+            }
+        }
+        None if main => {
+            // Before the first key or table, after any header comment.
+            let mut at = 0;
+            for line in text.split_inclusive('\n') {
+                let trimmed = line.trim();
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    break;
+                }
+                at += line.len();
+            }
+            let blank = if text[at..].starts_with('[') {
+                "\n"
+            } else {
+                ""
+            };
+            edits.push((
+                at..at,
+                format!("version = {CONFIG_VERSION}\n{blank}"),
+                format!("version = {CONFIG_VERSION} added"),
+            ));
+        }
+        None => {}
+    }
+
+    let mut tables = Vec::new();
+    collect(root, String::new(), &mut tables);
+    for (path, table) in tables {
+        for (_, old, new) in RENAMED.iter().filter(|(t, _, _)| matches(t, &path)) {
+            let Some((key, item)) = table.get_key_value(old) else {
+                continue;
+            };
+            let span = key.span().expect("parsed keys have spans");
+            if table.contains_key(new) {
+                let line = text[..span.start].matches('\n').count() + 1;
+                return Err(format!(
+                    "line {line}: {} and {} are both set; remove one and migrate again",
+                    join(&path, old),
+                    join(&path, new)
+                ));
+            }
+            let mut message = format!("{} renamed to {}", join(&path, old), join(&path, new));
+            if let (Some(secs), Some(value)) = (item.as_integer(), item.span()) {
+                let units = format_duration(Duration::from_secs(secs.max(0) as u64));
+                message.push_str(&format!("; {secs} written as \"{units}\""));
+                edits.push((value, format!("\"{units}\""), String::new()));
+            }
+            edits.push((span, new.to_string(), message));
+        }
+    }
+
+    let mut out = text.to_string();
+    let mut changes = Vec::new();
+    edits.sort_by_key(|(range, _, _)| std::cmp::Reverse(range.start));
+    for (range, replacement, message) in edits {
+        if !message.is_empty() {
+            changes.push(Change {
+                line: text[..range.start].matches('\n').count() + 1,
+                message,
+            });
+        }
+        out.replace_range(range, &replacement);
+    }
+    changes.reverse();
+    Ok((out, changes))
+}
+
+/// Every table under `table` (inline ones and array elements included),
+/// with its key path.
+fn collect<'a>(table: &'a dyn TableLike, path: String, out: &mut Vec<(String, &'a dyn TableLike)>) {
+    for (key, item) in table.iter() {
+        let at = join(&path, key);
+        match item {
+            Item::Table(t) => collect(t, at, out),
+            Item::ArrayOfTables(a) => {
+                for (i, t) in a.iter().enumerate() {
+                    collect(t, format!("{at}[{i}]"), out);
+                }
+            }
+            Item::Value(v) => collect_value(v, at, out),
+            Item::None => {}
+        }
+    }
+    out.push((path, table));
+}
+
+fn collect_value<'a>(value: &'a Value, path: String, out: &mut Vec<(String, &'a dyn TableLike)>) {
+    match value {
+        Value::InlineTable(t) => collect(t, path, out),
+        Value::Array(a) => {
+            for (i, v) in a.iter().enumerate() {
+                collect_value(v, format!("{path}[{i}]"), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether the table at `path` (`proxy.routes[0].split`) is `pattern`
+/// (`proxy.routes[].split`).
+fn matches(pattern: &str, path: &str) -> bool {
+    let mut general = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            _ if in_index => continue,
+            _ => {}
+        }
+        general.push(c);
+    }
+    let (mut want, mut have) = (pattern.split('.'), general.split('.'));
+    loop {
+        match (want.next(), have.next()) {
+            (None, None) => return true,
+            (Some(w), Some(h)) if w == h || w == "*" => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Warn about each key of `merged` renamed by a newer config version.
+pub(crate) fn warn_deprecated(merged: &toml::Table, r: &mut Report) {
+    walk(merged, "", r);
+}
+
+fn walk(table: &toml::Table, path: &str, r: &mut Report) {
+    for (_, old, new) in RENAMED.iter().filter(|(t, _, _)| matches(t, path)) {
+        if table.contains_key(*old) {
+            r.warn(
+                Code::Deprecated,
+                join(path, old),
+                format!(
+                    "{} is deprecated, use {} (`luciuz config migrate` renames it)",
+                    join(path, old),
+                    join(path, new)
+                ),
+            );
+        }
+    }
+    for (key, value) in table {
+        let at = join(path, key);
+        match value {
+            toml::Value::Table(t) => walk(t, &at, r),
+            toml::Value::Array(a) => {
+                for (i, v) in a.iter().enumerate() {
+                    if let toml::Value::Table(t) = v {
+                        walk(t, &format!("{at}[{i}]"), r);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,7 +1,143 @@
-use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::net::Cidr;
+use crate::units::{duration, size};
+
+/// A closed set of config strings: the enum, its serde names, `as_str`,
+/// `ALL` (the names in order) and `Display`.
+macro_rules! choice {
+    ($(#[$doc:meta])* $name:ident { $($(#[$vdoc:meta])* $variant:ident = $text:literal,)+ }) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+        pub enum $name {
+            $($(#[$vdoc])* #[serde(rename = $text)] $variant,)+
+        }
+
+        impl $name {
+            pub const ALL: &'static [&'static str] = &[$($text),+];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $text,)+
+                }
+            }
+        }
 
-#[derive(Debug, Clone, Deserialize)]
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+choice!(
+    /// What the HTTPS listener serves.
+    Profile {
+        StaticSite = "static_site",
+        PublicApi = "public_api",
+        /// Static files and proxy routes, reachable only as `[admin]` allows.
+        AdminPanel = "admin_panel",
+        /// Static files and proxy routes on one host.
+        Site = "site",
+    }
+);
+
+choice!(
+    AcmeChallenge {
+        /// Needs port 80.
+        Http01 = "http-01",
+        /// Answered on 443 during the TLS handshake.
+        TlsAlpn01 = "tls-alpn-01",
+    }
+);
+
+choice!(
+    LogLevel {
+        Trace = "trace",
+        Debug = "debug",
+        Info = "info",
+        Warn = "warn",
+        Error = "error",
+    }
+);
+
+choice!(
+    /// Body of generated error responses.
+    ErrorFormat {
+        /// HTML or problem details, chosen by `Accept`.
+        Auto = "auto",
+        Html = "html",
+        Problem = "problem",
+    }
+);
+
+choice!(
+    /// A content coding, for response compression and precompressed files.
+    Algorithm {
+        Gzip = "gzip",
+        Brotli = "br",
+        Zstd = "zstd",
+    }
+);
+
+choice!(
+    /// How symlinks under a static root are treated.
+    Symlinks {
+        Follow = "follow",
+        Deny = "deny",
+        /// Link and target have the same owner.
+        OwnerMatch = "owner_match",
+        /// Target inside root.
+        WithinRootOnly = "within_root_only",
+    }
+);
+
+choice!(
+    ProxyProtocolVersion {
+        V1 = "v1",
+        V2 = "v2",
+    }
+);
+
+choice!(
+    Listener {
+        Http = "http",
+        Https = "https",
+    }
+);
+
+choice!(
+    AffinityMode {
+        /// Luciuz-issued, HMAC-signed cookie.
+        Cookie = "cookie",
+        HashCookie = "hash_cookie",
+        HashHeader = "hash_header",
+    }
+);
+
+choice!(
+    SameSite {
+        Strict = "Strict",
+        Lax = "Lax",
+        /// Requires `secure`.
+        None = "None",
+    }
+);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    /// Version of the config format the file is written for. Files without
+    /// it are version 1; `luciuz config migrate` updates them.
+    #[serde(default = "default_version")]
+    pub version: u32,
     pub server: Server,
     #[serde(default)]
     pub telemetry: Telemetry,
@@ -10,20 +146,113 @@ pub struct Config {
     pub timeouts: Option<TimeoutsConfig>,
     pub static_site: Option<StaticSite>,
     pub proxy: Option<Proxy>,
+    pub errors: Option<ErrorPages>,
+    pub compression: Option<Compression>,
+    pub control: Option<Control>,
+    /// Access control of `server.profile = "admin_panel"`.
+    pub admin: Option<Admin>,
+    #[serde(default)]
+    pub audit: AuditConfig,
+}
+
+fn default_version() -> u32 {
+    1
+}
+
+/// Local control API; off unless configured.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Control {
+    /// Loopback address for `POST /reload` and `GET /status`,
+    /// e.g. "127.0.0.1:9901".
+    pub listen: SocketAddr,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// Who may reach an `admin_panel`: clients from `allow`, authenticated by
+/// `users`, `forward_auth` or a `client_ca` certificate.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Admin {
+    /// Client networks let through, e.g. ["10.0.0.0/8", "203.0.113.7/32"];
+    /// anyone else gets a 403.
+    pub allow: Vec<Cidr>,
+
+    /// Accounts allowed in (HTTP Basic authentication).
+    #[serde(default)]
+    pub users: Vec<UploadUser>,
+
+    /// PEM file of the CA(s) client certificates must chain to; the TLS
+    /// handshake fails without one. Needs `[acme]` with `http-01`.
+    pub client_ca: Option<String>,
+
+    /// Ask another service whether each request may go through.
+    pub forward_auth: Option<ForwardAuth>,
+
+    /// Requests per minute allowed from one client address; beyond it, 429.
+    #[serde(default = "default_admin_rate_limit")]
+    pub rate_limit: u32,
+
+    /// Largest accepted request body.
+    #[serde(default = "default_admin_max_body_bytes", deserialize_with = "size")]
+    pub max_body_bytes: usize,
+}
+
+fn default_admin_rate_limit() -> u32 {
+    120
+}
+
+fn default_admin_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForwardAuth {
+    /// Called with the request's method, URI and credentials in
+    /// `X-Forwarded-*`, `Cookie` and `Authorization` headers: a 2xx lets the
+    /// request through, any other response is returned to the client.
+    pub url: String,
+
+    /// Response headers of a 2xx passed on to the panel, e.g. ["Remote-User"];
+    /// the client's own values of these headers are dropped.
+    #[serde(default)]
+    pub copy_headers: Vec<String>,
+
+    #[serde(default = "default_forward_auth_timeout", with = "duration")]
+    pub timeout: Duration,
+}
+
+fn default_forward_auth_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// `luciuz audit` settings.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditConfig {
+    /// Rules not reported, by id ("A006") or name ("no-canonical-host").
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Server {
     /// HTTP listen address (used for ACME HTTP-01 + redirect only in the MVP).
-    #[serde(default = "default_http_listen")]
-    pub http_listen: String,
+    /// `""` disables the HTTP listener.
+    #[serde(default = "default_http_listen", with = "optional_addr")]
+    pub http_listen: Option<SocketAddr>,
 
     /// HTTPS listen address (the real service).
     #[serde(default = "default_https_listen")]
-    pub https_listen: String,
+    pub https_listen: SocketAddr,
 
     #[serde(default = "default_profile")]
-    pub profile: String,
+    pub profile: Profile,
+
+    /// Serve the built-in landing page at `/` when no static site is mounted.
+    #[serde(default = "default_landing_page")]
+    pub landing_page: bool,
 
     /// Canonical host (e.g. "luciuz.com"). If set, any other Host redirects to it.
     #[serde(default)]
@@ -33,9 +262,9 @@ pub struct Server {
     #[serde(default)]
     pub hsts: bool,
 
-    /// HSTS max-age in seconds.
-    #[serde(default = "default_hsts_max_age")]
-    pub hsts_max_age: u64,
+    /// HSTS max-age (seconds, or e.g. "180d").
+    #[serde(default = "default_hsts_max_age", with = "duration")]
+    pub hsts_max_age: Duration,
 
     /// Add includeSubDomains directive.
     #[serde(default)]
@@ -48,41 +277,218 @@ pub struct Server {
     /// Enable baseline security headers on HTTPS responses.
     #[serde(default = "default_security_headers")]
     pub security_headers: bool,
+
+    /// Peers (CIDRs) allowed to set forwarding headers (`Forwarded`, `X-Forwarded-*`).
+    /// Those headers are stripped from any other peer, which is then the client.
+    #[serde(default)]
+    pub trusted_proxies: Vec<Cidr>,
+
+    /// Node identifier sent as `by=` in the RFC 7239 `Forwarded` header
+    /// (an IP, "ip:port", or an obfuscated "_token").
+    #[serde(default = "default_forwarded_by")]
+    pub forwarded_by: String,
+
+    /// Accept PROXY protocol (v1/v2) headers from an L4 load balancer.
+    #[serde(default)]
+    pub proxy_protocol: Option<ProxyProtocol>,
 }
 
-fn default_http_listen() -> String {
-    "127.0.0.1:8080".to_string()
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyProtocol {
+    /// Listeners expecting the header (read before TLS).
+    pub listeners: Vec<Listener>,
+
+    /// Peers (CIDRs) that must send the header. Other peers are served as
+    /// direct connections and their header, if any, is not interpreted.
+    pub allowed_sources: Vec<Cidr>,
+
+    /// Max time to receive the header after the connection is accepted.
+    #[serde(default = "default_header_timeout", with = "duration")]
+    pub header_timeout: Duration,
 }
 
-fn default_https_listen() -> String {
-    "127.0.0.1:8443".to_string()
+fn default_header_timeout() -> Duration {
+    Duration::from_secs(5)
 }
 
-fn default_profile() -> String {
-    "public_api".to_string()
+fn default_http_listen() -> Option<SocketAddr> {
+    Some(SocketAddr::from(([127, 0, 0, 1], 8080)))
 }
 
-fn default_hsts_max_age() -> u64 {
-    86400
+fn default_https_listen() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 8443))
+}
+
+/// An address, or `""` for none.
+mod optional_addr {
+    use std::net::SocketAddr;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        addr: &Option<SocketAddr>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        match addr {
+            Some(addr) => s.collect_str(addr),
+            None => s.serialize_str(""),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<SocketAddr>, D::Error> {
+        let s = String::deserialize(d)?;
+        if s.trim().is_empty() {
+            return Ok(None);
+        }
+        s.trim().parse().map(Some).map_err(serde::de::Error::custom)
+    }
+}
+
+fn default_profile() -> Profile {
+    Profile::PublicApi
+}
+
+fn default_landing_page() -> bool {
+    true
+}
+
+fn default_hsts_max_age() -> Duration {
+    Duration::from_secs(86400)
 }
 
 fn default_security_headers() -> bool {
     true
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+fn default_forwarded_by() -> String {
+    "_luciuz".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Telemetry {
     #[serde(default)]
     pub json_logs: bool,
     #[serde(default = "default_log_level")]
-    pub log_level: String,
+    pub log_level: LogLevel,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self {
+            json_logs: false,
+            log_level: default_log_level(),
+        }
+    }
+}
+
+fn default_log_level() -> LogLevel {
+    LogLevel::Info
+}
+
+/// Bodies of error responses generated by Luciuz (502, 504, 421, ...) and,
+/// optionally, of upstream error responses.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ErrorPages {
+    #[serde(default = "default_error_format")]
+    pub format: ErrorFormat,
+
+    /// HTML file per status code, e.g. "502" = "/var/www/errors/502.html".
+    /// `{{status}}` and `{{request_id}}` are replaced when served.
+    #[serde(default)]
+    pub pages: BTreeMap<String, String>,
+
+    /// Replace the body of upstream error responses (4xx/5xx) too.
+    #[serde(default)]
+    pub intercept_upstream: bool,
+
+    /// Upstream statuses to intercept; empty means all 4xx/5xx.
+    #[serde(default)]
+    pub intercept_status: Vec<u16>,
+}
+
+fn default_error_format() -> ErrorFormat {
+    ErrorFormat::Auto
+}
+
+/// Response compression, negotiated with `Accept-Encoding`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Compression {
+    /// Enabled codings, in order of preference when the client ranks them equally.
+    #[serde(default = "default_compression_algorithms")]
+    pub algorithms: Vec<Algorithm>,
+
+    /// Content types to compress ("text/html" or "text/*").
+    #[serde(default = "default_compression_mime_types")]
+    pub mime_types: Vec<String>,
+
+    /// Responses smaller than this (when the size is known) are sent as is.
+    #[serde(default = "default_compression_min_size", deserialize_with = "size")]
+    pub min_size: u64,
+
+    /// 1-9.
+    #[serde(default = "default_gzip_level")]
+    pub gzip_level: u32,
+
+    /// 0-11.
+    #[serde(default = "default_brotli_level")]
+    pub brotli_level: u32,
+
+    /// 1-22.
+    #[serde(default = "default_zstd_level")]
+    pub zstd_level: u32,
+}
+
+fn default_compression_algorithms() -> Vec<Algorithm> {
+    vec![Algorithm::Zstd, Algorithm::Brotli, Algorithm::Gzip]
+}
+
+fn default_compression_mime_types() -> Vec<String> {
+    [
+        "text/*",
+        "application/javascript",
+        "application/json",
+        "application/problem+json",
+        "application/manifest+json",
+        "application/xml",
+        "application/rss+xml",
+        "application/atom+xml",
+        "application/wasm",
+        "image/svg+xml",
+        "font/ttf",
+        "font/otf",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_compression_min_size() -> u64 {
+    1024
+}
+
+fn default_gzip_level() -> u32 {
+    6
 }
 
-fn default_log_level() -> String {
-    "info".to_string()
+fn default_brotli_level() -> u32 {
+    4
 }
 
-#[derive(Debug, Clone, Deserialize)]
+fn default_zstd_level() -> u32 {
+    3
+}
+
+pub(crate) const GZIP_LEVELS: RangeInclusive<u32> = 1..=9;
+pub(crate) const BROTLI_LEVELS: RangeInclusive<u32> = 0..=11;
+pub(crate) const ZSTD_LEVELS: RangeInclusive<u32> = 1..=22;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Acme {
     /// Enable integrated ACME.
     #[serde(default)]
@@ -105,7 +511,7 @@ pub struct Acme {
     pub cache_dir: String,
 
     #[serde(default = "default_acme_challenge")]
-    pub challenge: String,
+    pub challenge: AcmeChallenge,
 }
 
 impl Default for Acme {
@@ -125,54 +531,324 @@ fn default_acme_cache_dir() -> String {
     "./acme-cache".to_string()
 }
 
-fn default_acme_challenge() -> String {
-    "http-01".to_string()
+fn default_acme_challenge() -> AcmeChallenge {
+    AcmeChallenge::Http01
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct TimeoutsConfig {
-    #[serde(default = "default_handler_secs")]
-    pub handler_secs: u64,
+    /// Max time for a request handler to produce a response.
+    #[serde(
+        default = "default_handler_timeout",
+        with = "duration",
+        alias = "handler_secs"
+    )]
+    pub handler: Duration,
 }
 
-fn default_handler_secs() -> u64 {
-    30
+fn default_handler_timeout() -> Duration {
+    Duration::from_secs(30)
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct StaticSite {
+    /// A directory, or a `.zip` / `.tar` / `.tar.gz` / `.tgz` / `.tar.zst` /
+    /// `.tzst` archive served from memory (see `is_archive`).
     pub root: String,
 
     #[serde(default = "default_index")]
     pub index: String,
 
-    /// Optional Cache-Control header value applied on static responses.
+    /// Optional Cache-Control header value applied on static responses
+    /// that no `cache_rules` entry matches.
     /// Example: "public, max-age=3600"
     pub cache_control: Option<String>,
+
+    /// Precompressed variants to look for next to each file: "br" (`.br`),
+    /// "zstd" (`.zst`), "gzip" (`.gz`). Served when the client accepts them.
+    #[serde(default)]
+    pub precompressed: Vec<Algorithm>,
+
+    /// Cache-Control by path glob; the first matching rule wins.
+    #[serde(default)]
+    pub cache_rules: Vec<CacheRule>,
+
+    /// Ordered candidates for GET/HEAD requests, nginx `try_files` style:
+    /// `$uri` is the request path. The first existing file is served.
+    /// Example: ["$uri", "$uri/", { path = "/index.html", exclude = ["/assets/"] }]
+    #[serde(default)]
+    pub try_files: Vec<TryFile>,
+
+    /// File (relative to `root`) served with status 404 when nothing matches.
+    pub not_found: Option<String>,
+
+    /// Generated directory indexes; off unless configured.
+    pub listing: Option<Listing>,
+
+    /// Refuse paths with a segment starting with `.` (`.git/`, `.env`).
+    #[serde(default = "default_deny_hidden")]
+    pub deny_hidden: bool,
+
+    /// Hidden names still served when `deny_hidden` is on.
+    #[serde(default = "default_hidden_allow")]
+    pub hidden_allow: Vec<String>,
+
+    /// File extensions never served, e.g. ["bak", "swp", "php"], also under
+    /// a .gz, .br or .zst suffix.
+    #[serde(default)]
+    pub deny_extensions: Vec<String>,
+
+    /// Symlinks under `root`.
+    #[serde(default = "default_symlinks")]
+    pub symlinks: Symlinks,
+
+    /// In-memory cache of small, hot files; off unless configured.
+    pub cache: Option<FileCache>,
+
+    /// Authenticated PUT/DELETE/MKCOL under a dedicated prefix; off unless
+    /// configured.
+    pub upload: Option<Upload>,
+}
+
+impl StaticSite {
+    /// Whether `root` names an archive rather than a directory.
+    pub fn is_archive(&self) -> bool {
+        let root = self.root.to_ascii_lowercase();
+        [".zip", ".tar", ".tar.gz", ".tgz", ".tar.zst", ".tzst"]
+            .iter()
+            .any(|ext| root.ends_with(ext))
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileCache {
+    /// Total bytes of cached file contents (least recently used go first).
+    #[serde(default = "default_cache_max_bytes", deserialize_with = "size")]
+    pub max_bytes: u64,
+
+    /// Larger files are always read from disk.
+    #[serde(default = "default_cache_max_file_bytes", deserialize_with = "size")]
+    pub max_file_bytes: u64,
+
+    /// Trust a cached entry for this long before checking the file's size
+    /// and mtime again. 0 checks on every hit.
+    #[serde(default = "default_cache_revalidate", with = "duration")]
+    pub revalidate: Duration,
+
+    /// Log hit/miss statistics at this interval (0 disables them).
+    #[serde(default = "default_cache_stats_interval", with = "duration")]
+    pub stats_interval: Duration,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Listing {
+    /// Path prefixes whose directories (without an index.html) are listed.
+    /// Example: ["/mirror/"]
+    pub prefixes: Vec<String>,
+    /// File name globs left out of listings, on top of dotfiles.
+    #[serde(default)]
+    pub hide: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Upload {
+    /// Request path prefix of the write API, mapped onto `root`: a PUT to
+    /// `/_upload/css/site.css` writes `<root>/css/site.css`.
+    pub prefix: String,
+
+    /// Largest accepted PUT body.
+    #[serde(default = "default_max_body_bytes", deserialize_with = "size")]
+    pub max_body_bytes: usize,
+
+    /// Accounts allowed to write (HTTP Basic authentication).
+    pub users: Vec<UploadUser>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct UploadUser {
+    pub name: String,
+    /// Argon2id hash of the password as a PHC string
+    /// (`$argon2id$v=19$m=19456,t=2,p=1$...`), e.g. from `luciuz hash-password`.
+    pub password_hash: String,
+}
+
+/// A `try_files` candidate: a path template, or a table that also lists
+/// request path prefixes the candidate is skipped for.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum TryFile {
+    Path(String),
+    Rule {
+        path: String,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+}
+
+impl TryFile {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Path(path) | Self::Rule { path, .. } => path,
+        }
+    }
+
+    pub fn exclude(&self) -> &[String] {
+        match self {
+            Self::Path(_) => &[],
+            Self::Rule { exclude, .. } => exclude,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheRule {
+    /// Glob matched against the file name, or the whole path if it contains
+    /// `/`. Example: "*.[0-9a-f]{8}.js"
+    pub pattern: String,
+    /// Example: "public, max-age=31536000, immutable"
+    pub cache_control: String,
 }
 
 fn default_index() -> String {
     "index.html".to_string()
 }
 
+fn default_deny_hidden() -> bool {
+    true
+}
+
+fn default_hidden_allow() -> Vec<String> {
+    vec![".well-known".to_string()]
+}
+
+fn default_symlinks() -> Symlinks {
+    Symlinks::Follow
+}
+
+fn default_cache_max_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_cache_max_file_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_cache_revalidate() -> Duration {
+    Duration::from_secs(2)
+}
+
+fn default_cache_stats_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
 fn default_true() -> bool {
     true
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Proxy {
-    #[serde(default = "default_max_body_bytes")]
+    #[serde(default = "default_max_body_bytes", deserialize_with = "size")]
     pub max_body_bytes: usize,
 
     #[serde(default)]
     pub routes: Vec<ProxyRoute>,
+
+    /// Named upstream pools, referenced by `routes[].pool` and `routes[].split`.
+    #[serde(default)]
+    pub pools: BTreeMap<String, UpstreamPool>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamPool {
+    /// Upstream base URLs (e.g. "http://127.0.0.1:8080"), used round-robin.
+    pub targets: Vec<String>,
+
+    /// How long a target is skipped after a connection failure.
+    #[serde(default = "default_unhealthy_for", with = "duration")]
+    pub unhealthy_for: Duration,
+
+    /// Optional session affinity (sticky sessions) to a single target.
+    #[serde(default)]
+    pub affinity: Option<Affinity>,
+
+    /// Send a PROXY protocol header on each new connection to the targets.
+    /// Connections are then only reused for the same client.
+    #[serde(default)]
+    pub send_proxy_protocol: Option<ProxyProtocolVersion>,
+}
+
+fn default_unhealthy_for() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Affinity {
+    pub mode: AffinityMode,
+
+    /// Cookie name (cookie, hash_cookie) or header name (hash_header).
+    #[serde(default = "default_affinity_name")]
+    pub name: String,
+
+    /// Lifetime of the issued cookie (0 = session cookie). mode=cookie only.
+    #[serde(default = "default_affinity_ttl", with = "duration")]
+    pub ttl: Duration,
+
+    #[serde(default = "default_true")]
+    pub secure: bool,
+
+    #[serde(default = "default_true")]
+    pub http_only: bool,
+
+    /// SameSite attribute of the issued cookie.
+    #[serde(default = "default_same_site")]
+    pub same_site: SameSite,
+
+    /// HMAC key signing the issued cookie. When unset, a random key is generated
+    /// at startup, kept across reloads, and pinned clients are re-balanced
+    /// after a restart.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+fn default_affinity_name() -> String {
+    "luciuz_affinity".to_string()
+}
+
+fn default_affinity_ttl() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_same_site() -> SameSite {
+    SameSite::Lax
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ProxyRoute {
     pub prefix: String,
+
+    /// Single upstream base URL. Exactly one of `upstream`, `pool` or `split` must be set.
+    #[serde(default)]
     pub upstream: String,
 
+    /// Name of a pool declared under `[proxy.pools]`.
+    #[serde(default)]
+    pub pool: Option<String>,
+
+    /// Weighted traffic split across pools (canary deployments).
+    #[serde(default)]
+    pub split: Option<TrafficSplit>,
+
     #[serde(default = "default_true")]
     pub strip_prefix: bool,
 
@@ -181,8 +857,79 @@ pub struct ProxyRoute {
 
     #[serde(default = "default_true")]
     pub pass_x_forwarded: bool,
+
+    /// Send the RFC 7239 `Forwarded` header, next to `X-Forwarded-*`
+    /// or instead of them when `pass_x_forwarded = false`.
+    #[serde(default)]
+    pub forwarded: bool,
+
+    /// PROXY protocol for the single `upstream`; pools set it on
+    /// `[proxy.pools.<name>]`.
+    #[serde(default)]
+    pub send_proxy_protocol: Option<ProxyProtocolVersion>,
+
+    /// Error responses for this route, replacing the top-level `[errors]`.
+    #[serde(default)]
+    pub errors: Option<ErrorPages>,
+
+    /// Never compress responses of this route: it reflects request input next
+    /// to secrets (e.g. CSRF tokens), which compression can leak (BREACH).
+    #[serde(default)]
+    pub breach_sensitive: bool,
 }
 
 fn default_max_body_bytes() -> usize {
     50 * 1024 * 1024 // 50 MB
 }
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrafficSplit {
+    /// Weighted pools. Weights are relative (e.g. 99 + 1 = 1% canary); 0 disables a pool.
+    pub targets: Vec<SplitTarget>,
+
+    /// Rules that force a pool, evaluated in order before the weighted choice.
+    #[serde(default)]
+    pub overrides: Vec<SplitOverride>,
+
+    /// Optional cookie that keeps a client on the pool it was first assigned to.
+    #[serde(default)]
+    pub sticky_cookie: Option<String>,
+
+    /// Lifetime of the sticky cookie.
+    #[serde(default = "default_sticky_ttl", with = "duration")]
+    pub sticky_ttl: Duration,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SplitTarget {
+    pub pool: String,
+    pub weight: u32,
+}
+
+/// Forces `pool` when the request carries a matching header, cookie or query parameter.
+/// Exactly one of `header`, `cookie` or `query` must be set. Without `value`, presence is enough.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SplitOverride {
+    #[serde(default)]
+    pub header: Option<String>,
+
+    #[serde(default)]
+    pub cookie: Option<String>,
+
+    #[serde(default)]
+    pub query: Option<String>,
+
+    #[serde(default)]
+    pub value: Option<String>,
+
+    pub pool: String,
+}
+
+fn default_sticky_ttl() -> Duration {
+    Duration::from_secs(86400)
+}
+
+include!(concat!(env!("OUT_DIR"), "/schema_defs.rs"));
@@ -0,0 +1,684 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use serde::{Serialize, Serializer};
+use toml_edit::{ImDocument, Item, Table, TableLike, Value};
+
+/// Stable identifier of a kind of problem, for scripts and CI filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    /// TOML syntax error, or a value of the wrong type.
+    Syntax,
+    /// A required value is missing or empty.
+    Missing,
+    /// A value outside its list of allowed choices.
+    InvalidChoice,
+    /// A number outside its allowed range.
+    OutOfRange,
+    /// The same entry listed twice.
+    Duplicate,
+    /// A malformed address, URL, network, pattern or name.
+    Malformed,
+    /// Settings that cannot be combined.
+    Conflict,
+    /// A reference to something not declared (e.g. a pool).
+    UnknownReference,
+    /// A key the config model does not have (often a typo).
+    UnknownKey,
+    /// A key set to different values in two files.
+    Redefined,
+    /// A `${...}` reference that cannot be resolved.
+    Interpolation,
+    /// An include that cannot be read, or a file included twice.
+    Include,
+    /// A section the selected profile does not use.
+    Ignored,
+    /// A declaration nothing refers to.
+    Unused,
+    /// A setting that trusts every client.
+    TrustsAnyone,
+    /// A key renamed by a newer config version, still read for now.
+    Deprecated,
+}
+
+impl Code {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::Syntax => "E001",
+            Code::Missing => "E002",
+            Code::InvalidChoice => "E003",
+            Code::OutOfRange => "E004",
+            Code::Duplicate => "E005",
+            Code::Malformed => "E006",
+            Code::Conflict => "E007",
+            Code::UnknownReference => "E008",
+            Code::UnknownKey => "E009",
+            Code::Redefined => "E010",
+            Code::Interpolation => "E011",
+            Code::Include => "E012",
+            Code::Ignored => "W001",
+            Code::Unused => "W002",
+            Code::TrustsAnyone => "W003",
+            Code::Deprecated => "W004",
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Code {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// Where a diagnostic points in its file. Lines and columns start
+/// at 1; `start`/`end` are byte offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    /// Config key path, e.g. `proxy.routes[0].prefix`; empty when unknown.
+    pub path: String,
+    /// The file `span` is in: the main config or one of its includes.
+    pub file: String,
+    pub span: Option<Span>,
+}
+
+/// One file read for the config.
+#[derive(Debug, Clone)]
+struct Source {
+    file: String,
+    text: String,
+    /// `None` when the file does not parse.
+    doc: Option<ImDocument<String>>,
+}
+
+/// Every problem found in a config file and the files it includes, in
+/// file order.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The main config file.
+    pub file: String,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The main file first, then includes in the order they were merged.
+    sources: Vec<Source>,
+    /// For each subtree of the merged config taken from one file: the
+    /// source index and the subtree's key path in that file.
+    origins: BTreeMap<String, (usize, String)>,
+    merged: Option<toml::Table>,
+    /// Merged key path → the `${...}` references its value was built from.
+    interpolated: BTreeMap<String, Vec<String>>,
+}
+
+impl Report {
+    pub(crate) fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            diagnostics: Vec::new(),
+            sources: Vec::new(),
+            origins: BTreeMap::new(),
+            merged: None,
+            interpolated: BTreeMap::new(),
+        }
+    }
+
+    /// Register a file read for the config and return its index, or record
+    /// its parse error and return `None`.
+    pub(crate) fn add_source(&mut self, file: &str, text: String) -> Option<usize> {
+        let index = self.sources.len();
+        let doc = match ImDocument::parse(text.clone()) {
+            Ok(doc) => Some(doc),
+            Err(e) => {
+                let (message, at) = (e.message().to_string(), e.span());
+                self.sources.push(Source {
+                    file: file.to_string(),
+                    text,
+                    doc: None,
+                });
+                self.syntax(index, &message, at);
+                return None;
+            }
+        };
+        self.sources.push(Source {
+            file: file.to_string(),
+            text,
+            doc,
+        });
+        Some(index)
+    }
+
+    pub(crate) fn source_file(&self, source: usize) -> &str {
+        &self.sources[source].file
+    }
+
+    pub(crate) fn source_text(&self, source: usize) -> &str {
+        &self.sources[source].text
+    }
+
+    /// Record that the merged subtree at `path` comes from `local` in
+    /// `source`.
+    pub(crate) fn set_origin(&mut self, path: &str, source: usize, local: &str) {
+        self.origins
+            .insert(path.to_string(), (source, local.to_string()));
+    }
+
+    /// The source index and local key path that merged `path` comes from.
+    pub(crate) fn origin(&self, path: &str) -> (usize, String) {
+        let mut prefix = path;
+        loop {
+            if let Some((source, local)) = self.origins.get(prefix) {
+                let rest = &path[prefix.len()..];
+                let local = match (local.is_empty(), rest.strip_prefix('.')) {
+                    (true, Some(rest)) => rest.to_string(),
+                    _ => format!("{local}{rest}"),
+                };
+                return (*source, local);
+            }
+            match parent(prefix) {
+                Some(p) => prefix = p,
+                None => return (0, path.to_string()),
+            }
+        }
+    }
+
+    /// `file:line:column` of the merged key `path`.
+    pub(crate) fn position(&self, path: &str) -> String {
+        let (source, local) = self.origin(path);
+        let file = &self.sources[source].file;
+        match self.find_in(source, &local) {
+            Some(s) => format!("{file}:{}:{}", s.line, s.column),
+            None => file.clone(),
+        }
+    }
+
+    /// `file:line` of merged `path`, when a file sets it.
+    pub(crate) fn line_of(&self, path: &str) -> Option<String> {
+        lookup(self.merged.as_ref()?, path)?;
+        let (source, local) = self.origin(path);
+        let file = &self.sources[source].file;
+        Some(match self.find_in(source, &local) {
+            Some(s) => format!("{file}:{}", s.line),
+            None => file.clone(),
+        })
+    }
+
+    /// Whether merged `path` comes from an included file.
+    pub(crate) fn is_included(&self, path: &str) -> bool {
+        self.origin(path).0 > 0
+    }
+
+    pub(crate) fn set_interpolated(&mut self, path: &str, refs: Vec<String>) {
+        self.interpolated.insert(path.to_string(), refs);
+    }
+
+    /// The `${...}` references merged `path` (or, for an array, its
+    /// elements) was built from.
+    pub(crate) fn interpolated(&self, path: &str) -> Vec<&str> {
+        self.interpolated
+            .range(path.to_string()..)
+            .take_while(|(p, _)| p.starts_with(path))
+            .filter(|(p, _)| p.len() == path.len() || p[path.len()..].starts_with('['))
+            .flat_map(|(_, refs)| refs.iter().map(String::as_str))
+            .collect()
+    }
+
+    pub(crate) fn set_merged(&mut self, merged: toml::Table) {
+        self.merged = Some(merged);
+    }
+
+    /// The config after includes are merged, before `${...}` references
+    /// are resolved and defaults applied; `None` if a file does not parse.
+    pub fn merged(&self) -> Option<&toml::Table> {
+        self.merged.as_ref()
+    }
+
+    /// Every file read, the main config first.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.sources.iter().map(|s| s.file.as_str())
+    }
+
+    pub(crate) fn error(
+        &mut self,
+        code: Code,
+        path: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.push(Severity::Error, code, path.into(), message.into());
+    }
+
+    pub(crate) fn warn(&mut self, code: Code, path: impl Into<String>, message: impl Into<String>) {
+        self.push(Severity::Warning, code, path.into(), message.into());
+    }
+
+    /// Record an error about `local`, a key path in `source` rather than
+    /// in the merged config.
+    pub(crate) fn error_in(
+        &mut self,
+        source: usize,
+        code: Code,
+        local: &str,
+        message: impl Into<String>,
+    ) {
+        let span = self.find_in(source, local);
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            path: local.to_string(),
+            file: self.sources[source].file.clone(),
+            span,
+        });
+    }
+
+    fn push(&mut self, severity: Severity, code: Code, path: String, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            message,
+            path,
+            file: String::new(),
+            span: None,
+        });
+    }
+
+    /// Record a parse error in `source` at byte range `at`. When the file
+    /// parsed as TOML, the message is prefixed with the key it is about.
+    pub(crate) fn syntax(&mut self, source: usize, message: &str, at: Option<Range<usize>>) {
+        let message = one_line(message);
+        let code = classify(&message);
+        let path = match (&self.sources[source].doc, &at) {
+            (Some(doc), Some(at)) => path_at(doc.as_table(), at.start),
+            _ => String::new(),
+        };
+        let message = if path.is_empty() {
+            message
+        } else {
+            format!("{path}: {message}")
+        };
+        let span = at.map(|r| span(&self.sources[source].text, r));
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            path,
+            file: self.sources[source].file.clone(),
+            span,
+        });
+    }
+
+    /// Record an error from turning the merged config into the model, at
+    /// merged key `path`.
+    pub(crate) fn invalid(&mut self, path: &str, message: &str) {
+        let message = one_line(message);
+        let code = classify(&message);
+        // Unknown keys at the top level are reported on the root table.
+        let path = match (code, message.split('`').nth(1)) {
+            (Code::UnknownKey, Some(key)) if path.is_empty() => key.to_string(),
+            _ => path.to_string(),
+        };
+        let message = if path.is_empty() {
+            message
+        } else {
+            format!("{path}: {message}")
+        };
+        self.push(Severity::Error, code, path, message);
+    }
+
+    /// Point each diagnostic about the merged config at its key in the file
+    /// it came from (or the closest enclosing table when the key is
+    /// absent), then sort them in file order.
+    pub(crate) fn locate(&mut self) {
+        for i in 0..self.diagnostics.len() {
+            if self.diagnostics[i].file.is_empty() {
+                let (source, local) = self.origin(&self.diagnostics[i].path);
+                self.diagnostics[i].span = self.find_in(source, &local);
+                self.diagnostics[i].file = self.sources[source].file.clone();
+            }
+        }
+        let order: BTreeMap<&str, usize> = self
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.file.as_str(), i))
+            .collect();
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        diagnostics.sort_by_key(|d| {
+            (
+                order.get(d.file.as_str()).copied().unwrap_or(0),
+                d.span.map_or(usize::MAX, |s| s.start),
+            )
+        });
+        self.diagnostics = diagnostics;
+    }
+
+    fn find_in(&self, source: usize, local: &str) -> Option<Span> {
+        let s = self.sources.get(source)?;
+        let range = find(s.doc.as_ref()?.as_table(), local)?;
+        Some(span(&s.text, range))
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Warning)
+    }
+
+    /// `file:line:column` of `d`.
+    pub fn location(&self, d: &Diagnostic) -> String {
+        let file = if d.file.is_empty() {
+            &self.file
+        } else {
+            &d.file
+        };
+        match d.span {
+            Some(s) => format!("{file}:{}:{}", s.line, s.column),
+            None => file.clone(),
+        }
+    }
+
+    /// The errors on one line, for logs and error values.
+    pub fn summary(&self) -> String {
+        self.errors()
+            .map(|d| format!("{}: {} [{}]", self.location(d), d.message, d.code))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Compiler-style rendering: each diagnostic with its source line and
+    /// the span underlined, then a count.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for d in &self.diagnostics {
+            out.push_str(&format!("{}[{}]: {}\n", d.severity, d.code, d.message));
+            let source = self.sources.iter().find(|s| s.file == d.file);
+            let (Some(span), Some(source)) = (d.span, source) else {
+                out.push_str(&format!(" --> {}\n\n", self.location(d)));
+                continue;
+            };
+            let text = &source.text;
+            let number = span.line.to_string();
+            let gutter = " ".repeat(number.len());
+            let line_start = text[..span.start].rfind('\n').map_or(0, |n| n + 1);
+            let line = text[line_start..]
+                .lines()
+                .next()
+                .unwrap_or("")
+                .trim_end_matches('\r');
+            let width = text[span.start..span.end]
+                .lines()
+                .next()
+                .map_or(0, |l| l.chars().count())
+                .max(1);
+            out.push_str(&format!("{gutter}--> {}\n", self.location(d)));
+            out.push_str(&format!("{gutter} |\n"));
+            out.push_str(&format!("{number} | {line}\n"));
+            out.push_str(&format!(
+                "{gutter} | {}{}\n\n",
+                " ".repeat(span.column - 1),
+                "^".repeat(width)
+            ));
+        }
+        let errors = self.errors().count();
+        let warnings = self.warnings().count();
+        out.push_str(&match (errors, warnings) {
+            (0, 0) => format!("{}: ok", self.file),
+            (0, w) => format!("{}: ok, {}", self.file, plural(w, "warning")),
+            (e, 0) => format!("{}: {}", self.file, plural(e, "error")),
+            (e, w) => format!(
+                "{}: {}, {}",
+                self.file,
+                plural(e, "error"),
+                plural(w, "warning")
+            ),
+        });
+        out.push('\n');
+        out
+    }
+}
+
+/// The parser splits some messages over lines ("what\nexpected").
+fn one_line(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+fn classify(message: &str) -> Code {
+    if message.starts_with("unknown field") {
+        Code::UnknownKey
+    } else if message.starts_with("unknown variant") {
+        Code::InvalidChoice
+    } else if message.starts_with("missing field") {
+        Code::Missing
+    } else {
+        Code::Syntax
+    }
+}
+
+fn span(text: &str, range: Range<usize>) -> Span {
+    let start = range.start.min(text.len());
+    let before = &text[..start];
+    let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+    Span {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        start,
+        end: range.end.clamp(start, text.len()),
+    }
+}
+
+/// `a.b[0]` → `a.b`, `a.b` → `a`, `a` → ``; `None` for the root.
+fn parent(path: &str) -> Option<&str> {
+    if path.is_empty() {
+        return None;
+    }
+    let cut = if path.ends_with(']') {
+        path.rfind('[')
+    } else {
+        path.rfind('.')
+    };
+    Some(&path[..cut.unwrap_or(0)])
+}
+
+fn plural(n: usize, word: &str) -> String {
+    if n == 1 {
+        format!("1 {word}")
+    } else {
+        format!("{n} {word}s")
+    }
+}
+
+/// One step of a key path: `routes` or `[0]`.
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+fn segments(path: &str) -> Vec<Segment<'_>> {
+    let mut out = Vec::new();
+    for part in path.split('.').filter(|p| !p.is_empty()) {
+        let (key, mut rest) = part.split_once('[').map_or((part, ""), |(k, r)| (k, r));
+        if !key.is_empty() {
+            out.push(Segment::Key(key));
+        }
+        while let Some((index, tail)) = rest.split_once(']') {
+            if let Ok(i) = index.parse() {
+                out.push(Segment::Index(i));
+            }
+            rest = tail.trim_start_matches('[');
+        }
+    }
+    out
+}
+
+/// The value at `path` in `table`.
+pub(crate) fn lookup<'a>(table: &'a toml::Table, path: &str) -> Option<&'a toml::Value> {
+    let mut segments = segments(path).into_iter();
+    let Some(Segment::Key(first)) = segments.next() else {
+        return None;
+    };
+    let mut value = table.get(first)?;
+    for segment in segments {
+        value = match (value, segment) {
+            (toml::Value::Table(t), Segment::Key(k)) => t.get(k)?,
+            (toml::Value::Array(a), Segment::Index(i)) => a.get(i)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// The deepest node of `path` found in `doc`, as a byte range.
+fn find(doc: &Table, path: &str) -> Option<Range<usize>> {
+    #[derive(Clone, Copy)]
+    enum Node<'a> {
+        Item(&'a Item),
+        Table(&'a Table),
+        Value(&'a Value),
+    }
+    let mut node = Node::Table(doc);
+    let mut best = None;
+    for segment in segments(path) {
+        let next = match (node, segment) {
+            (Node::Table(t), Segment::Key(k)) => t.get(k).map(Node::Item),
+            (Node::Item(i), Segment::Key(k)) => {
+                i.as_table_like().and_then(|t| t.get(k)).map(Node::Item)
+            }
+            (Node::Value(Value::InlineTable(t)), Segment::Key(k)) => t.get(k).map(Node::Value),
+            (Node::Item(Item::ArrayOfTables(a)), Segment::Index(n)) => a.get(n).map(Node::Table),
+            (Node::Item(Item::Value(Value::Array(a))), Segment::Index(n))
+            | (Node::Value(Value::Array(a)), Segment::Index(n)) => a.get(n).map(Node::Value),
+            _ => None,
+        };
+        let Some(next) = next else {
+            break;
+        };
+        let span = match next {
+            Node::Item(Item::Value(v)) | Node::Value(v) => v.span(),
+            Node::Item(Item::Table(t)) | Node::Table(t) => table_span(t),
+            Node::Item(Item::ArrayOfTables(a)) => a.get(0).and_then(table_span),
+            Node::Item(i) => i.span(),
+        };
+        best = span.or(best);
+        node = next;
+    }
+    best
+}
+
+/// A table's header; for a table only implied by its children (`[proxy]`
+/// from `[[proxy.routes]]`), its first child's.
+fn table_span(t: &Table) -> Option<Range<usize>> {
+    t.span().or_else(|| {
+        t.iter().find_map(|(_, item)| match item {
+            Item::Table(t) => table_span(t),
+            Item::ArrayOfTables(a) => a.get(0).and_then(table_span),
+            item => item.span(),
+        })
+    })
+}
+
+/// Key path of the innermost key or value at byte `at` in `doc`.
+fn path_at(doc: &Table, at: usize) -> String {
+    fn walk(table: &dyn TableLike, at: usize, path: &mut String) -> bool {
+        for (key, item) in table.iter() {
+            let len = path.len();
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(key);
+            let on_key = table
+                .get_key_value(key)
+                .and_then(|(k, _)| k.span())
+                .is_some_and(|s| s.contains(&at));
+            if on_key || in_item(item, at, path) {
+                return true;
+            }
+            path.truncate(len);
+        }
+        false
+    }
+    fn in_item(item: &Item, at: usize, path: &mut String) -> bool {
+        match item {
+            Item::Table(t) => walk(t, at, path) || t.span().is_some_and(|s| s.contains(&at)),
+            Item::ArrayOfTables(a) => a.iter().enumerate().any(|(i, t)| {
+                let len = path.len();
+                path.push_str(&format!("[{i}]"));
+                let found = walk(t, at, path) || t.span().is_some_and(|s| s.contains(&at));
+                if !found {
+                    path.truncate(len);
+                }
+                found
+            }),
+            Item::Value(v) => in_value(v, at, path),
+            Item::None => false,
+        }
+    }
+    fn in_value(v: &Value, at: usize, path: &mut String) -> bool {
+        if !v.span().is_some_and(|s| s.contains(&at)) {
+            return false;
+        }
+        match v {
+            Value::Array(a) => {
+                for (i, v) in a.iter().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("[{i}]"));
+                    if in_value(v, at, path) {
+                        return true;
+                    }
+                    path.truncate(len);
+                }
+            }
+            Value::InlineTable(t) => {
+                walk(t, at, path);
+            }
+            _ => {}
+        }
+        true
+    }
+    let mut path = String::new();
+    walk(doc, at, &mut path);
+    path
+}
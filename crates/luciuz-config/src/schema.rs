@@ -0,0 +1,296 @@
+//! JSON Schema of `luciuz.toml`, for editors (taplo, VS Code) and config
+//! generators. Types, doc comments and defaults come from the model (see
+//! `build.rs`); ranges and patterns that only validation knows are in
+//! [`LIMITS`].
+
+use serde_json::{json, Map, Value};
+
+use crate::migrate::{CONFIG_VERSION, RENAMED};
+use crate::model::{BROTLI_LEVELS, DEFS, GZIP_LEVELS, ZSTD_LEVELS};
+
+/// A struct or enum of the model.
+pub(crate) struct Def {
+    pub(crate) name: &'static str,
+    pub(crate) doc: &'static str,
+    pub(crate) body: Body,
+}
+
+pub(crate) enum Body {
+    Struct(&'static [Field]),
+    /// (config string, doc) per value.
+    Choice(&'static [(&'static str, &'static str)]),
+    /// One of several shapes (`TryFile`).
+    Untagged(&'static [Shape]),
+}
+
+pub(crate) struct Field {
+    pub(crate) name: &'static str,
+    pub(crate) doc: &'static str,
+    pub(crate) shape: Shape,
+    pub(crate) required: bool,
+    pub(crate) default: DefaultValue,
+}
+
+pub(crate) enum Shape {
+    Bool,
+    /// An unsigned integer up to this maximum.
+    Integer(u64),
+    String,
+    Duration,
+    Size,
+    Address,
+    /// An address, or `""` for none.
+    AddressOrEmpty,
+    Network,
+    Option(&'static Shape),
+    List(&'static Shape),
+    Map(&'static Shape),
+    Object(&'static [Field]),
+    /// Another definition, by name.
+    Def(&'static str),
+}
+
+pub(crate) enum DefaultValue {
+    /// Required, or absent by default (`Option`).
+    None,
+    /// `#[serde(default)]`: the type's empty value.
+    TypeDefault,
+    Fn(fn() -> Value),
+}
+
+/// (definition, field, extra schema) for a check done by validation.
+pub(crate) type Limit = (&'static str, &'static str, fn() -> Value);
+
+pub(crate) static LIMITS: &[Limit] = &[
+    ("Config", "version", || range(1..=CONFIG_VERSION)),
+    ("Compression", "gzip_level", || range(GZIP_LEVELS)),
+    ("Compression", "brotli_level", || range(BROTLI_LEVELS)),
+    ("Compression", "zstd_level", || range(ZSTD_LEVELS)),
+    (
+        "ErrorPages",
+        "pages",
+        || json!({ "propertyNames": { "pattern": "^[45][0-9][0-9]$" } }),
+    ),
+    (
+        "ErrorPages",
+        "intercept_status",
+        || json!({ "items": { "type": "integer", "minimum": 400, "maximum": 599 } }),
+    ),
+    ("ProxyRoute", "prefix", || json!({ "pattern": "^/" })),
+    ("Upload", "prefix", || json!({ "pattern": "^/." })),
+    (
+        "UploadUser",
+        "password_hash",
+        || json!({ "pattern": "^\\$argon2id\\$" }),
+    ),
+    ("Affinity", "secret", || json!({ "minLength": 32 })),
+];
+
+const DURATION: &str = "^[0-9]+$|^([0-9]+(ms|s|m|h|d))+$";
+
+const SIZE: &str = "^[0-9]+ *([bB]|[kKmMgG]([bB]|[iI][bB])?)?$";
+
+fn range(r: std::ops::RangeInclusive<u32>) -> Value {
+    json!({ "minimum": r.start(), "maximum": r.end() })
+}
+
+/// The JSON Schema (draft-07) of the config file.
+pub fn schema() -> Value {
+    for (d, name, _) in LIMITS {
+        assert!(
+            matches!(&def(d).body, Body::Struct(fields) if fields.iter().any(|f| f.name == *name)),
+            "LIMITS names {d}.{name}, which is not in the config model"
+        );
+    }
+    let mut root = def_schema(def("Config"));
+    root["properties"]["include"] = json!({
+        "description": "More config files to merge, relative to this one; wildcards are allowed in the file name.",
+        "type": "array",
+        "items": { "type": "string" },
+    });
+    let mut definitions: Map<String, Value> = DEFS
+        .iter()
+        .filter(|d| d.name != "Config")
+        .map(|d| (d.name.to_string(), def_schema(d)))
+        .collect();
+    // Keys renamed by a config version are still read: list them, marked.
+    for (table, old, new) in RENAMED {
+        let owner = def_at(table);
+        let properties = match definitions.get_mut(owner) {
+            Some(d) => &mut d["properties"],
+            None => &mut root["properties"],
+        };
+        let mut alias = properties[*new].clone();
+        if let Value::Object(alias) = &mut alias {
+            alias.remove("default");
+        }
+        alias["description"] = json!(format!(
+            "Deprecated: renamed to `{new}` in version {CONFIG_VERSION} \
+             (`luciuz config migrate` renames it)."
+        ));
+        alias["deprecated"] = json!(true);
+        properties[*old] = alias;
+    }
+    root["$schema"] = json!("http://json-schema.org/draft-07/schema#");
+    root["title"] = json!("luciuz.toml");
+    root["definitions"] = Value::Object(definitions);
+    root
+}
+
+fn def(name: &str) -> &'static Def {
+    DEFS.iter()
+        .find(|d| d.name == name)
+        .unwrap_or_else(|| panic!("no {name} in the config model"))
+}
+
+/// The definition of the table at `path` (`proxy.pools.*.affinity`, as in
+/// `RENAMED`).
+fn def_at(path: &str) -> &'static str {
+    let mut owner = "Config";
+    let mut map_value = None;
+    for part in path.split('.') {
+        let mut shape = match map_value.take() {
+            Some(value) if part == "*" => value,
+            _ => {
+                let Body::Struct(fields) = &def(owner).body else {
+                    panic!("{owner} in {path} is not a table");
+                };
+                let name = part.trim_end_matches("[]");
+                &fields
+                    .iter()
+                    .find(|f| f.name == name)
+                    .unwrap_or_else(|| panic!("no {name} in {owner}, for {path}"))
+                    .shape
+            }
+        };
+        loop {
+            match shape {
+                Shape::Option(s) | Shape::List(s) => shape = s,
+                Shape::Map(s) => {
+                    map_value = Some(*s);
+                    break;
+                }
+                Shape::Def(name) => {
+                    owner = name;
+                    break;
+                }
+                _ => panic!("{part} in {path} is not a table"),
+            }
+        }
+    }
+    owner
+}
+
+fn def_schema(d: &Def) -> Value {
+    let mut out = match &d.body {
+        Body::Struct(fields) => object(d.name, fields),
+        Body::Choice(values) => {
+            let mut out = json!({
+                "type": "string",
+                "enum": values.iter().map(|(v, _)| *v).collect::<Vec<_>>(),
+            });
+            if values.iter().any(|(_, doc)| !doc.is_empty()) {
+                out["enumDescriptions"] = json!(values.iter().map(|(_, d)| *d).collect::<Vec<_>>());
+            }
+            out
+        }
+        Body::Untagged(shapes) => json!({
+            "anyOf": shapes.iter().map(|s| shape(d.name, s)).collect::<Vec<_>>(),
+        }),
+    };
+    if !d.doc.is_empty() {
+        out["description"] = json!(d.doc);
+    }
+    out
+}
+
+fn object(owner: &str, fields: &[Field]) -> Value {
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|f| (f.name.to_string(), field(owner, f)))
+        .collect();
+    let mut out = json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    });
+    let required: Vec<_> = fields
+        .iter()
+        .filter(|f| f.required)
+        .map(|f| f.name)
+        .collect();
+    if !required.is_empty() {
+        out["required"] = json!(required);
+    }
+    out
+}
+
+fn field(owner: &str, f: &Field) -> Value {
+    let mut out = shape(owner, &f.shape);
+    if !f.doc.is_empty() {
+        if out.get("$ref").is_some() {
+            out = json!({ "allOf": [out] });
+        }
+        out["description"] = json!(f.doc);
+    }
+    let default = match &f.default {
+        DefaultValue::None => None,
+        DefaultValue::TypeDefault => type_default(&f.shape),
+        DefaultValue::Fn(f) => Some(f()).filter(|v| !v.is_null()),
+    };
+    if let Some(default) = default {
+        out["default"] = default;
+    }
+    for (_, _, extra) in LIMITS
+        .iter()
+        .filter(|(d, name, _)| *d == owner && *name == f.name)
+    {
+        if let Value::Object(extra) = extra() {
+            for (k, v) in extra {
+                out[k] = v;
+            }
+        }
+    }
+    out
+}
+
+fn shape(owner: &str, s: &Shape) -> Value {
+    let number = json!({ "type": "integer", "minimum": 0 });
+    match s {
+        Shape::Bool => json!({ "type": "boolean" }),
+        Shape::Integer(max) => json!({
+            "type": "integer",
+            "minimum": 0,
+            "maximum": (*max).min(i64::MAX as u64),
+        }),
+        Shape::String | Shape::Address | Shape::AddressOrEmpty | Shape::Network => {
+            json!({ "type": "string" })
+        }
+        Shape::Duration => json!({
+            "anyOf": [number, { "type": "string", "pattern": DURATION }],
+        }),
+        Shape::Size => json!({
+            "anyOf": [number, { "type": "string", "pattern": SIZE }],
+        }),
+        // TOML has no null: an absent key is the only way to say none.
+        Shape::Option(s) => shape(owner, s),
+        Shape::List(s) => json!({ "type": "array", "items": shape(owner, s) }),
+        Shape::Map(s) => json!({ "type": "object", "additionalProperties": shape(owner, s) }),
+        Shape::Object(fields) => object(owner, fields),
+        Shape::Def(name) => json!({ "$ref": format!("#/definitions/{name}") }),
+    }
+}
+
+/// The value `#[serde(default)]` gives a field of shape `s`, when it is a
+/// plain value.
+fn type_default(s: &Shape) -> Option<Value> {
+    Some(match s {
+        Shape::Bool => json!(false),
+        Shape::Integer(_) | Shape::Size => json!(0),
+        Shape::String => json!(""),
+        Shape::Duration => json!("0s"),
+        Shape::List(_) => json!([]),
+        Shape::Map(_) => json!({}),
+        _ => return None,
+    })
+}
@@ -0,0 +1,155 @@
+//! Durations ("30s", "5m", "1h30m") and sizes ("512KiB", "50MiB") in the
+//! config. A bare integer keeps its historical unit: seconds or bytes.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Serializer;
+
+/// Parse "90", "1500ms", "30s", "5m", "1h30m" or "7d".
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let err = || format!("invalid duration {s:?} (e.g. \"30s\", \"5m\", \"1h30m\", \"7d\")");
+    let mut total = Duration::ZERO;
+    let mut rest = s;
+    if rest.is_empty() {
+        return Err(err());
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(err)?;
+        let n: u64 = rest[..digits].parse().map_err(|_| err())?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(n),
+            "s" => Duration::from_secs(n),
+            "m" => Duration::from_secs(n.saturating_mul(60)),
+            "h" => Duration::from_secs(n.saturating_mul(3600)),
+            "d" => Duration::from_secs(n.saturating_mul(86400)),
+            _ => return Err(err()),
+        };
+        total = total.saturating_add(part);
+        rest = &rest[unit_len..];
+    }
+    Ok(total)
+}
+
+/// `d` in the form [`parse_duration`] reads: 90 seconds is "1m30s",
+/// 1.5 seconds "1s500ms", 180 days "180d". Below a millisecond is dropped.
+pub fn format_duration(d: Duration) -> String {
+    if d.is_zero() {
+        return "0s".to_string();
+    }
+    let mut out = String::new();
+    let mut secs = d.as_secs();
+    for (unit, len) in [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)] {
+        if secs >= len {
+            out.push_str(&format!("{}{unit}", secs / len));
+            secs %= len;
+        }
+    }
+    if d.subsec_millis() > 0 {
+        out.push_str(&format!("{}ms", d.subsec_millis()));
+    }
+    out
+}
+
+/// Parse "1048576", "512KiB", "50MiB", "1GB" (KB/MB/GB are powers of
+/// 1000, KiB/MiB/GiB of 1024; case-insensitive).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let err = || format!("invalid size {s:?} (e.g. \"4096\", \"512KiB\", \"50MiB\", \"1GB\")");
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n: u64 = s[..digits].parse().map_err(|_| err())?;
+    let factor: u64 = match s[digits..].trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1000 * 1000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "gib" => 1 << 30,
+        _ => return Err(err()),
+    };
+    n.checked_mul(factor).ok_or_else(err)
+}
+
+/// `with` for durations: written as [`format_duration`] strings, read
+/// from an integer number of seconds or a string accepted by
+/// [`parse_duration`].
+pub(crate) mod duration {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&format_duration(*d))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        super::duration_from(d)
+    }
+}
+
+fn duration_from<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+    struct V;
+    impl Visitor<'_> for V {
+        type Value = Duration;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a duration: seconds, or a string like \"30s\", \"5m\", \"1h30m\"")
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Duration, E> {
+            u64::try_from(v)
+                .map(Duration::from_secs)
+                .map_err(|_| E::custom(format!("invalid duration {v}: must not be negative")))
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+            Ok(Duration::from_secs(v))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Duration, E> {
+            parse_duration(v).map_err(E::custom)
+        }
+    }
+    d.deserialize_any(V)
+}
+
+/// `deserialize_with` for sizes: an integer number of bytes, or a string
+/// accepted by [`parse_size`].
+pub(crate) fn size<'de, D, T>(d: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    struct V<T>(PhantomData<T>);
+    impl<T: TryFrom<u64>> Visitor<'_> for V<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a size: bytes, or a string like \"512KiB\", \"50MiB\"")
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+            let v = u64::try_from(v)
+                .map_err(|_| E::custom(format!("invalid size {v}: must not be negative")))?;
+            self.visit_u64(v)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+            T::try_from(v).map_err(|_| E::custom(format!("size {v} is too large")))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            self.visit_u64(parse_size(v).map_err(E::custom)?)
+        }
+    }
+    d.deserialize_any(V(PhantomData))
+}
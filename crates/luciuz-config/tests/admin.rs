@@ -0,0 +1,100 @@
+//! An `admin_panel` is never left open to anyone.
+
+fn errors(toml: &str) -> Vec<String> {
+    let (_, report) = luciuz_config::check_str("luciuz.toml", toml);
+    report
+        .errors()
+        .map(|d| format!("{} {}", d.path, d.message))
+        .collect()
+}
+
+const PANEL: &str = "[server]\nprofile = \"admin_panel\"\nhttp_listen = \"127.0.0.1:8080\"\n";
+
+const ACME: &str = "\n[acme]\nenabled = true\nemail = \"ops@example.com\"\n\
+                    domains = [\"admin.example.com\"]\n";
+
+const USER: &str = "users = [{ name = \"ops\", password_hash = \
+                    \"$argon2id$v=19$m=19456,t=2,p=1$3coYMyD9TEDe6UX2GEPQ2A$kWvMbvbAq9NlB5jCwvTdaXVrseKwg+PnkQzuORW918g\" }]\n";
+
+#[test]
+fn a_panel_needs_an_admin_section() {
+    assert_eq!(
+        errors(PANEL),
+        [
+            "server.profile server.profile=admin_panel but [admin] section is missing \
+          (the panel would be public)"
+        ]
+    );
+}
+
+#[test]
+fn a_panel_needs_an_allowlist_that_is_not_everyone() {
+    let open = format!("{PANEL}{ACME}\n[admin]\nallow = [\"10.0.0.0/8\", \"::/0\"]\n{USER}");
+    assert_eq!(
+        errors(&open),
+        ["admin.allow[1] admin.allow lets any client reach the panel (::/0)"]
+    );
+    let closed = format!("{PANEL}{ACME}\n[admin]\nallow = [\"10.0.0.0/8\"]\n{USER}");
+    assert!(errors(&closed).is_empty());
+}
+
+#[test]
+fn a_panel_needs_authentication() {
+    let anonymous = format!("{PANEL}\n[admin]\nallow = [\"10.0.0.0/8\"]\n");
+    assert_eq!(
+        errors(&anonymous),
+        ["admin [admin] authenticates no one: set users, client_ca or forward_auth"]
+    );
+    let forward = format!(
+        "{PANEL}\n[admin]\nallow = [\"10.0.0.0/8\"]\n\
+         forward_auth = {{ url = \"http://127.0.0.1:4181/verify\" }}\n"
+    );
+    assert!(errors(&forward).is_empty());
+}
+
+#[test]
+fn client_certificates_need_acme_over_http_01() {
+    let plain = format!("{PANEL}\n[admin]\nallow = [\"10.0.0.0/8\"]\nclient_ca = \"/ca.pem\"\n");
+    assert!(errors(&plain)[0].contains("needs TLS"));
+    let alpn = format!(
+        "{PANEL}\n[acme]\nenabled = true\nemail = \"ops@example.com\"\n\
+         domains = [\"admin.example.com\"]\nchallenge = \"tls-alpn-01\"\n\n\
+         [admin]\nallow = [\"10.0.0.0/8\"]\nclient_ca = \"/ca.pem\"\n"
+    );
+    assert!(errors(&alpn)[0].contains("tls-alpn-01"));
+}
+
+#[test]
+fn passwords_are_argon2id_hashes() {
+    let sha256 = format!(
+        "{PANEL}{ACME}\n[admin]\nallow = [\"10.0.0.0/8\"]\nusers = [{{ name = \"ops\", password_hash = \
+         \"2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b\" }}]\n"
+    );
+    let errors = errors(&sha256);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("admin.users[0].password_hash "));
+    assert!(errors[0].contains("Argon2id"));
+}
+
+#[test]
+fn passwords_need_tls() {
+    let plain = format!("{PANEL}\n[admin]\nallow = [\"10.0.0.0/8\"]\n{USER}");
+    assert_eq!(errors(&plain).len(), 1);
+    assert!(errors(&plain)[0].starts_with("admin.users admin.users needs TLS"));
+}
+
+#[test]
+fn a_panel_trusts_no_proxy_for_everyone() {
+    let panel = format!(
+        "[server]\nprofile = \"admin_panel\"\nhttp_listen = \"127.0.0.1:8080\"\n\
+         trusted_proxies = [\"10.0.0.0/8\", \"0.0.0.0/0\"]\n{ACME}\n\
+         [admin]\nallow = [\"10.0.0.0/8\"]\n{USER}"
+    );
+    assert_eq!(
+        errors(&panel),
+        [
+            "server.trusted_proxies[1] server.trusted_proxies lets any client set its own \
+             address (0.0.0.0/0), and so pass admin.allow"
+        ]
+    );
+}
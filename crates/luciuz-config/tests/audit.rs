@@ -0,0 +1,75 @@
+//! `luciuz audit` rules on small configs.
+
+fn ids(toml: &str, allow: &[&str]) -> (Vec<&'static str>, Vec<&'static str>) {
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    assert!(!report.has_errors(), "{}", report.render());
+    let allow: Vec<String> = allow.iter().map(|a| a.to_string()).collect();
+    let audit = luciuz_config::audit(&cfg, &report, &allow);
+    (
+        audit.findings.iter().map(|f| f.rule.id).collect(),
+        audit.suppressed.iter().map(|f| f.rule.id).collect(),
+    )
+}
+
+const WEAK: &str = r#"
+[server]
+http_listen = "0.0.0.0:80"
+hsts = true
+hsts_preload = true
+hsts_max_age = "30d"
+security_headers = false
+
+[proxy]
+max_body_bytes = "1GiB"
+
+[[proxy.routes]]
+prefix = "/api"
+upstream = "http://10.0.0.5:8080"
+
+[[proxy.routes]]
+prefix = "/local"
+upstream = "http://127.0.0.1:3000"
+"#;
+
+/// A route that no rule objects to.
+const LOCAL: &str = "\n[[proxy.routes]]\nprefix = \"/api\"\nupstream = \"http://127.0.0.1:3000\"\n";
+
+#[test]
+fn weak_settings_are_reported_most_risky_first() {
+    let (found, suppressed) = ids(WEAK, &[]);
+    assert_eq!(found, ["A001", "A003", "A004", "A002", "A007", "A006"]);
+    assert!(suppressed.is_empty());
+}
+
+#[test]
+fn allowed_rules_are_suppressed_by_id_or_name() {
+    let toml = format!("[audit]\nallow = [\"A006\"]\n{WEAK}");
+    let (found, suppressed) = ids(&toml, &["plaintext-upstream"]);
+    assert_eq!(found, ["A001", "A003", "A002", "A007"]);
+    assert_eq!(suppressed, ["A004", "A006"]);
+}
+
+#[test]
+fn staging_certificates_only_matter_for_public_names() {
+    let acme = |domain: &str| {
+        format!(
+            "[server]\nhttp_listen = \"0.0.0.0:80\"\nhttps_listen = \"0.0.0.0:443\"\n\
+             canonical_host = \"{domain}\"\n\n\
+             [acme]\nenabled = true\nemail = \"ops@example.com\"\ndomains = [\"{domain}\"]\n\
+             cache_dir = \"/nonexistent/luciuz-acme\"\n{LOCAL}"
+        )
+    };
+    assert_eq!(ids(&acme("shop.io"), &[]).0, ["A005"]);
+    assert!(ids(&acme("app.test"), &[]).0.is_empty());
+}
+
+#[test]
+fn unknown_allowed_rules_are_refused() {
+    let toml =
+        format!("[server]\nhttp_listen = \"0.0.0.0:80\"\n\n[audit]\nallow = [\"A999\"]\n{LOCAL}");
+    let (_, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let error = report.errors().next().expect("an error");
+    assert_eq!(error.code.as_str(), "E003");
+    assert!(error.message.contains("\"A999\""));
+}
@@ -0,0 +1,70 @@
+//! Every problem of a config is reported at once, with a code and the
+//! position it comes from.
+
+/// `code location path` of each error, then of each warning.
+fn diagnostics(toml: &str) -> (Vec<String>, Vec<String>) {
+    let (_, report) = luciuz_config::check_str("luciuz.toml", toml);
+    let line =
+        |d: &luciuz_config::Diagnostic| format!("{} {} {}", d.code, report.location(d), d.path);
+    (
+        report.errors().map(line).collect(),
+        report.warnings().map(line).collect(),
+    )
+}
+
+#[test]
+fn all_errors_are_reported_together() {
+    let (errors, warnings) = diagnostics(
+        "[server]\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = \"/srv/www\"\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\npool = \"missing\"\n\n\
+         [[proxy.routes]]\nprefix = \"/old\"\nupstream = \"ftp://10.0.0.1\"\n",
+    );
+    assert_eq!(
+        errors,
+        [
+            "E008 luciuz.toml:9:8 proxy.routes[0].pool",
+            "E006 luciuz.toml:13:12 proxy.routes[1].upstream",
+        ]
+    );
+    // Warnings come along, and do not make the config invalid.
+    assert_eq!(warnings, ["W001 luciuz.toml:4:1 static_site"]);
+}
+
+#[test]
+fn a_valid_config_has_no_errors() {
+    let (cfg, report) = luciuz_config::check_str(
+        "luciuz.toml",
+        "[server]\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\nupstream = \"http://127.0.0.1:3000\"\n",
+    );
+    assert!(cfg.is_some());
+    assert!(!report.has_errors(), "{}", report.render());
+    assert!(
+        report.render().ends_with("luciuz.toml: ok\n"),
+        "{}",
+        report.render()
+    );
+}
+
+#[test]
+fn errors_render_like_a_compiler() {
+    let (_, report) = luciuz_config::check_str(
+        "luciuz.toml",
+        "[server]\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\npool = \"missing\"\n",
+    );
+    let rendered = report.render();
+    assert!(
+        rendered.starts_with(
+            "error[E008]: proxy.routes[0] references unknown pool: missing\n \
+             --> luciuz.toml:6:8\n  |\n6 | pool = \"missing\"\n  |        ^^^^^^^^^\n"
+        ),
+        "{rendered}"
+    );
+    assert!(rendered.ends_with("luciuz.toml: 1 error\n"), "{rendered}");
+    assert_eq!(
+        report.summary(),
+        "luciuz.toml:6:8: proxy.routes[0] references unknown pool: missing [E008]"
+    );
+}
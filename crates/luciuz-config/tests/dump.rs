@@ -0,0 +1,18 @@
+//! `luciuz config dump` never prints credentials.
+
+#[test]
+fn credentials_are_redacted() {
+    let toml = "[server]\nprofile = \"admin_panel\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+                [admin]\nallow = [\"10.0.0.0/8\"]\n\
+                forward_auth = { url = \"http://127.0.0.1:4181/verify?token=s3cr3t\" }\n\n\
+                [static_site]\nroot = \"/srv/www\"\n\n\
+                [static_site.upload]\nprefix = \"/_upload\"\n\n\
+                [[static_site.upload.users]]\nname = \"ops\"\npassword_hash = \
+                \"$argon2id$v=19$m=19456,t=2,p=1$3coYMyD9TEDe6UX2GEPQ2A$kWvMbvbAq9NlB5jCwvTdaXVrseKwg+PnkQzuORW918g\"\n";
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    let dumped = luciuz_config::dump(&cfg, &report).to_toml();
+    assert!(!dumped.contains("s3cr3t"), "{dumped}");
+    assert!(!dumped.contains("$argon2id"), "{dumped}");
+    assert_eq!(dumped.matches("\"<redacted>\"").count(), 2, "{dumped}");
+}
@@ -0,0 +1,177 @@
+//! Drop-in files (`include`) and `${...}` references.
+
+use std::path::{Path, PathBuf};
+
+/// A config dir holding `files`; returns the path of the first one.
+fn files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("luciuz-include-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (file, text) in files {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+    dir.join(files[0].0)
+}
+
+/// The config, and `code location message` of each error, paths made
+/// relative to the config dir.
+fn check(main: &Path) -> (Option<luciuz_config::Config>, Vec<String>) {
+    let (cfg, report) = luciuz_config::check_path(main.to_str().unwrap()).unwrap();
+    let dir = main.parent().unwrap().to_str().unwrap().to_string() + "/";
+    let errors = report
+        .errors()
+        .map(|d| {
+            let at = report.location(d).replace(&dir, "");
+            format!("{} {at} {}", d.code, d.message.replace(&dir, ""))
+        })
+        .collect();
+    (cfg, errors)
+}
+
+const MAIN: &str = "include = [\"conf.d/*.toml\"]\n\n\
+                    [server]\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+                    [[proxy.routes]]\nprefix = \"/api\"\nupstream = \"http://127.0.0.1:3000\"\n";
+
+#[test]
+fn drop_ins_are_merged_in_name_order() {
+    let main = files(
+        "merge",
+        &[
+            ("luciuz.toml", MAIN),
+            (
+                "conf.d/20-b.toml",
+                "[[proxy.routes]]\nprefix = \"/b\"\nupstream = \"http://127.0.0.1:3002\"\n",
+            ),
+            (
+                "conf.d/10-a.toml",
+                "[server]\nhsts_max_age = \"1d\"\n\n\
+                 [[proxy.routes]]\nprefix = \"/a\"\nupstream = \"http://127.0.0.1:3001\"\n",
+            ),
+            ("conf.d/.hidden.toml", "this is not toml"),
+        ],
+    );
+    let (cfg, errors) = check(&main);
+    assert!(errors.is_empty(), "{errors:?}");
+    let cfg = cfg.unwrap();
+    assert_eq!(cfg.server.hsts_max_age.as_secs(), 86400);
+    let prefixes: Vec<_> = cfg
+        .proxy
+        .unwrap()
+        .routes
+        .iter()
+        .map(|r| r.prefix.clone())
+        .collect();
+    assert_eq!(prefixes, ["/api", "/a", "/b"]);
+    std::fs::remove_dir_all(main.parent().unwrap()).ok();
+}
+
+#[test]
+fn conflicting_values_are_refused() {
+    let main = files(
+        "conflict",
+        &[
+            ("luciuz.toml", MAIN),
+            (
+                "conf.d/a.toml",
+                "[server]\nhttp_listen = \"127.0.0.1:8080\"\n",
+            ),
+            (
+                "conf.d/b.toml",
+                "[server]\nhttp_listen = \"127.0.0.1:9090\"\n",
+            ),
+        ],
+    );
+    let (_, errors) = check(&main);
+    assert_eq!(
+        errors,
+        ["E010 conf.d/b.toml:2:15 server.http_listen is already set in luciuz.toml:4:15"]
+    );
+    std::fs::remove_dir_all(main.parent().unwrap()).ok();
+}
+
+#[test]
+fn broken_includes_are_reported() {
+    let main = files(
+        "broken",
+        &[
+            (
+                "luciuz.toml",
+                &format!(
+                    "include = [\"missing.toml\", \"conf.d/a.toml\"]\n{}",
+                    MAIN.split_once('\n').unwrap().1
+                ),
+            ),
+            ("conf.d/a.toml", "include = [\"../luciuz.toml\"]\n"),
+        ],
+    );
+    let (_, errors) = check(&main);
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(
+        errors[0].starts_with("E012 luciuz.toml:1:12 include[0]: cannot read missing.toml"),
+        "{errors:?}"
+    );
+    assert!(
+        errors[1].starts_with("E012 conf.d/a.toml:1:12 include[0]:")
+            && errors[1].ends_with("luciuz.toml is included more than once"),
+        "{errors:?}"
+    );
+    std::fs::remove_dir_all(main.parent().unwrap()).ok();
+}
+
+#[test]
+fn references_are_resolved() {
+    std::env::set_var("LUCIUZ_TEST_INCLUDE_HOST", "example.com");
+    let main = files(
+        "refs",
+        &[
+            (
+                "luciuz.toml",
+                "[server]\nhttp_listen = \"127.0.0.1:8080\"\n\
+                 canonical_host = \"${LUCIUZ_TEST_INCLUDE_HOST}\"\n\n\
+                 [acme]\nemail = \"${LUCIUZ_TEST_INCLUDE_UNSET:-ops@example.com}\"\n\
+                 cache_dir = \"$${literal}\"\n\n\
+                 [[proxy.routes]]\nprefix = \"/api\"\nupstream = \"${file:secrets/upstream}\"\n",
+            ),
+            ("secrets/upstream", "http://10.0.0.1:3000\n"),
+        ],
+    );
+    let (cfg, errors) = check(&main);
+    assert!(errors.is_empty(), "{errors:?}");
+    let cfg = cfg.unwrap();
+    assert_eq!(cfg.server.canonical_host.as_deref(), Some("example.com"));
+    assert_eq!(cfg.acme.email, "ops@example.com");
+    assert_eq!(cfg.acme.cache_dir, "${literal}");
+    assert_eq!(
+        cfg.proxy.unwrap().routes[0].upstream,
+        "http://10.0.0.1:3000"
+    );
+    std::fs::remove_dir_all(main.parent().unwrap()).ok();
+}
+
+#[test]
+fn unresolved_references_are_errors() {
+    let main = files(
+        "unresolved",
+        &[(
+            "luciuz.toml",
+            "[server]\nhttp_listen = \"127.0.0.1:8080\"\n\
+             canonical_host = \"${LUCIUZ_TEST_INCLUDE_NEVER_SET}\"\n\n\
+             [[proxy.routes]]\nprefix = \"/api\"\nupstream = \"${file:nowhere}\"\n",
+        )],
+    );
+    let (_, errors) = check(&main);
+    // The value left unresolved is then checked as it is.
+    assert_eq!(errors.len(), 3, "{errors:?}");
+    assert_eq!(
+        errors[0],
+        "E011 luciuz.toml:3:18 server.canonical_host: environment variable \
+         LUCIUZ_TEST_INCLUDE_NEVER_SET is not set"
+    );
+    assert!(
+        errors[1].starts_with("E011 luciuz.toml:7:12 proxy.routes[0].upstream: cannot read"),
+        "{errors:?}"
+    );
+    assert!(errors[2].starts_with("E006 luciuz.toml:7:12"), "{errors:?}");
+    std::fs::remove_dir_all(main.parent().unwrap()).ok();
+}
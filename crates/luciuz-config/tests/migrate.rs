@@ -0,0 +1,40 @@
+//! `luciuz config migrate` on version 1 files.
+
+/// A route that makes the config valid.
+const LOCAL: &str = "\n[[proxy.routes]]\nprefix = \"/api\"\nupstream = \"http://127.0.0.1:3000\"\n";
+
+fn migrate(name: &str, toml: &str) -> Result<Vec<luciuz_config::Migrated>, String> {
+    let dir = std::env::temp_dir().join(format!("luciuz-migrate-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("luciuz.toml");
+    std::fs::write(&path, toml).unwrap();
+    let migrated = luciuz_config::migrate(path.to_str().unwrap()).map_err(|e| e.to_string());
+    std::fs::remove_dir_all(&dir).ok();
+    migrated
+}
+
+#[test]
+fn renamed_keys_get_their_new_name_and_units() {
+    let migrated = migrate(
+        "renamed",
+        &format!("[timeouts]\nhandler_secs = 90\n{LOCAL}"),
+    )
+    .unwrap();
+    let text = &migrated[0].text;
+    assert!(text.starts_with("version = 2\n"), "{text}");
+    assert!(text.contains("handler = \"1m30s\"\n"), "{text}");
+    assert_eq!(migrated[0].changes.len(), 2);
+}
+
+#[test]
+fn old_and_new_keys_together_are_refused() {
+    let err = migrate(
+        "both",
+        &format!("[timeouts]\nhandler_secs = 30\nhandler = \"1m\"\n{LOCAL}"),
+    )
+    .unwrap_err();
+    assert!(
+        err.contains("line 2: timeouts.handler_secs and timeouts.handler are both set"),
+        "{err}"
+    );
+}
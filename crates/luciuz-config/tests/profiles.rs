@@ -0,0 +1,58 @@
+//! What each `server.profile` requires, and the sections it leaves unused.
+
+const SERVER: &str = "[server]\nhttp_listen = \"127.0.0.1:8080\"\n";
+const STATIC: &str = "\n[static_site]\nroot = \"/srv/www\"\n";
+const ROUTE: &str = "\n[[proxy.routes]]\nprefix = \"/api\"\nupstream = \"http://127.0.0.1:3000\"\n";
+
+/// `code path: message` of each error, then of each warning, for `profile`.
+fn check(profile: &str, sections: &[&str]) -> (Vec<String>, Vec<String>) {
+    let toml = format!("{SERVER}profile = \"{profile}\"\n{}", sections.concat());
+    let (_, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let line = |d: &luciuz_config::Diagnostic| format!("{} {}: {}", d.code, d.path, d.message);
+    (
+        report.errors().map(line).collect(),
+        report.warnings().map(line).collect(),
+    )
+}
+
+#[test]
+fn site_takes_static_files_routes_or_both() {
+    for sections in [&[STATIC][..], &[ROUTE], &[STATIC, ROUTE]] {
+        let (errors, warnings) = check("site", sections);
+        assert!(errors.is_empty(), "{sections:?}: {errors:?}");
+        assert!(warnings.is_empty(), "{sections:?}: {warnings:?}");
+    }
+    assert_eq!(
+        check("site", &[]).0,
+        ["E002 server.profile: server.profile=site needs a [static_site] section or proxy.routes"]
+    );
+}
+
+#[test]
+fn single_purpose_profiles_warn_about_the_other_section() {
+    let (errors, warnings) = check("static_site", &[STATIC, ROUTE]);
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(
+        warnings,
+        ["W001 proxy: [proxy] is not served with server.profile=static_site"]
+    );
+
+    let (errors, warnings) = check("public_api", &[STATIC, ROUTE]);
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(
+        warnings,
+        ["W001 static_site: [static_site] is not served with server.profile=public_api"]
+    );
+}
+
+#[test]
+fn required_sections_are_checked() {
+    assert_eq!(
+        check("static_site", &[ROUTE]).0,
+        ["E002 server.profile: server.profile=static_site but [static_site] section is missing"]
+    );
+    assert_eq!(
+        check("public_api", &[STATIC]).0,
+        ["E002 server.profile: server.profile=public_api but [proxy] section is missing"]
+    );
+}
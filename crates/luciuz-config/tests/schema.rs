@@ -0,0 +1,345 @@
+//! Keeps `docs/luciuz.schema.json` in sync with the config model.
+
+use serde_json::Value;
+
+#[test]
+fn published_schema_is_current() {
+    let published = include_str!("../../../docs/luciuz.schema.json");
+    let current = serde_json::to_string_pretty(&luciuz_config::schema()).unwrap() + "\n";
+    assert!(
+        published == current,
+        "docs/luciuz.schema.json is out of date; regenerate it with \
+         `luciuz config schema > docs/luciuz.schema.json`"
+    );
+}
+
+/// Sets every table and list of the model, so defaults alone do not hide
+/// a key the schema lacks.
+const FULL: &str = r#"
+version = 2
+
+[server]
+http_listen = ""
+https_listen = "0.0.0.0:443"
+profile = "site"
+canonical_host = "example.com"
+trusted_proxies = ["10.0.0.0/8"]
+forwarded_by = "edge"
+
+[server.proxy_protocol]
+listeners = ["https"]
+allowed_sources = ["10.0.0.0/8"]
+
+[acme]
+domains = ["example.com"]
+email = "admin@example.com"
+
+[control]
+listen = "127.0.0.1:9901"
+
+[admin]
+allow = ["10.0.0.0/8"]
+users = [{ name = "ops", password_hash = "$argon2id$v=19$m=19456,t=2,p=1$3coYMyD9TEDe6UX2GEPQ2A$kWvMbvbAq9NlB5jCwvTdaXVrseKwg+PnkQzuORW918g" }]
+client_ca = "/etc/luciuz/admin-ca.pem"
+forward_auth = { url = "http://127.0.0.1:4181/verify", copy_headers = ["Remote-User"] }
+
+[audit]
+allow = ["A006"]
+
+[errors]
+format = "problem"
+intercept_status = [502]
+pages = { 404 = "/srv/404.html" }
+
+[static_site]
+root = "/srv/www"
+try_files = ["$uri", { path = "/index.html", exclude = ["/api/"] }]
+cache_rules = [{ pattern = "*.css", cache_control = "max-age=3600" }]
+listing = { prefixes = ["/files/"] }
+cache = { max_bytes = "64MiB" }
+
+[static_site.upload]
+prefix = "/upload"
+users = [{ name = "ops", password_hash = "$argon2id$v=19$m=19456,t=2,p=1$3coYMyD9TEDe6UX2GEPQ2A$kWvMbvbAq9NlB5jCwvTdaXVrseKwg+PnkQzuORW918g" }]
+
+[proxy.pools.app]
+targets = ["http://127.0.0.1:3000"]
+affinity = { mode = "cookie", secret = "0123456789abcdef0123456789abcdef" }
+
+[proxy.pools.beta]
+targets = ["http://127.0.0.1:3001"]
+
+[[proxy.routes]]
+prefix = "/api"
+pool = "app"
+errors = { format = "html" }
+
+[proxy.routes.split]
+targets = [{ pool = "app", weight = 90 }, { pool = "beta", weight = 10 }]
+overrides = [{ header = "x-beta", pool = "beta" }]
+"#;
+
+#[test]
+fn schema_describes_every_key_of_the_model() {
+    let (cfg, report) = luciuz_config::check_str("full.toml", FULL);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    let schema = luciuz_config::schema();
+    let mut missing = Vec::new();
+    let value = serde_json::to_value(&cfg).unwrap();
+    walk(&schema, &schema, &value, "", &mut missing);
+    assert!(missing.is_empty(), "not in the schema: {missing:?}");
+}
+
+/// Note in `missing` each key of `value` that `node` has no schema for.
+fn walk(root: &Value, node: &Value, value: &Value, at: &str, missing: &mut Vec<String>) {
+    if let Some(target) = node["$ref"].as_str() {
+        let name = target.trim_start_matches("#/definitions/");
+        return walk(root, &root["definitions"][name], value, at, missing);
+    }
+    for key in ["allOf", "anyOf"] {
+        if let Some(alternatives) = node[key].as_array() {
+            // An untagged enum: follow the object alternative for tables.
+            let object = alternatives.iter().find(|a| a["type"] == "object");
+            match (key, value, object) {
+                ("anyOf", Value::Object(_), Some(object)) => walk(root, object, value, at, missing),
+                ("allOf", _, _) => walk(root, &alternatives[0], value, at, missing),
+                _ => {}
+            }
+            return;
+        }
+    }
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map {
+                let path = format!("{at}.{key}");
+                match (&node["properties"][key], &node["additionalProperties"]) {
+                    (Value::Null, Value::Object(_)) => {
+                        walk(root, &node["additionalProperties"], v, &path, missing)
+                    }
+                    (Value::Null, _) => missing.push(path),
+                    (child, _) => walk(root, child, v, &path, missing),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                walk(root, &node["items"], v, &format!("{at}[{i}]"), missing);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Example configs: the repository's own and the import fixtures, in full.
+fn example_files() -> Vec<std::path::PathBuf> {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let fixtures = root.join("crates/luciuz-import/tests/fixtures");
+    let mut files = vec![root.join("luciuz.toml")];
+    for entry in std::fs::read_dir(fixtures).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|e| e == "toml") {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// The ```toml blocks of the user docs, as (file:line, block).
+fn doc_snippets() -> Vec<(String, String)> {
+    let docs = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../docs");
+    let mut snippets = Vec::new();
+    for lang in ["en", "fr"] {
+        for entry in std::fs::read_dir(docs.join(lang)).unwrap() {
+            let path = entry.unwrap().path();
+            let text = std::fs::read_to_string(&path).unwrap();
+            let mut block: Option<(usize, String)> = None;
+            for (i, line) in text.lines().enumerate() {
+                match (&mut block, line) {
+                    (None, "```toml") => block = Some((i + 2, String::new())),
+                    (Some((start, toml)), "```") => {
+                        snippets.push((format!("{}:{start}", path.display()), toml.clone()));
+                        block = None;
+                    }
+                    (Some((_, toml)), line) => {
+                        toml.push_str(line);
+                        toml.push('\n');
+                    }
+                    (None, _) => {}
+                }
+            }
+        }
+    }
+    snippets
+}
+
+#[test]
+fn examples_match_the_schema() {
+    let schema = luciuz_config::schema();
+    let mut errors = Vec::new();
+    for path in example_files() {
+        let text = std::fs::read_to_string(&path).unwrap();
+        let value: toml::Value = toml::from_str(&text).unwrap();
+        let value = serde_json::to_value(value).unwrap();
+        let at = path.display().to_string();
+        check(&schema, &schema, &value, &at, true, &mut errors);
+        let (_, report) = luciuz_config::check_str(&at, &text);
+        errors.extend(report.errors().map(|d| format!("{at}: {}", d.message)));
+    }
+    // Snippets show a few keys at a time: required keys may be elsewhere.
+    for (at, text) in doc_snippets() {
+        let value: toml::Value =
+            toml::from_str(&text).unwrap_or_else(|e| panic!("{at}: not TOML: {e}"));
+        let value = serde_json::to_value(value).unwrap();
+        check(&schema, &schema, &value, &at, false, &mut errors);
+    }
+    assert!(errors.is_empty(), "{}", errors.join("\n"));
+}
+
+#[test]
+fn the_checker_sees_schema_errors() {
+    let schema = luciuz_config::schema();
+    let value = serde_json::json!({
+        "compression": { "gzip_level": 42 },
+        "server": { "http_listne": "" },
+        "static_site": { "cache": { "revalidate": "2 seconds" } },
+    });
+    let mut errors = Vec::new();
+    check(&schema, &schema, &value, "bad", true, &mut errors);
+    assert_eq!(errors.len(), 4, "{errors:#?}");
+}
+
+/// Note in `errors` where `value` breaks `node` (the subset of draft-07
+/// the generated schema uses).
+fn check(
+    root: &Value,
+    node: &Value,
+    value: &Value,
+    at: &str,
+    required: bool,
+    errors: &mut Vec<String>,
+) {
+    if let Some(target) = node["$ref"].as_str() {
+        let name = target.trim_start_matches("#/definitions/");
+        return check(
+            root,
+            &root["definitions"][name],
+            value,
+            at,
+            required,
+            errors,
+        );
+    }
+    for each in node["allOf"].as_array().into_iter().flatten() {
+        check(root, each, value, at, required, errors);
+    }
+    if let Some(alternatives) = node["anyOf"].as_array() {
+        let fits = alternatives.iter().any(|a| {
+            let mut errors = Vec::new();
+            check(root, a, value, at, required, &mut errors);
+            errors.is_empty()
+        });
+        if !fits {
+            errors.push(format!("{at}: {value} matches none of {}", node["anyOf"]));
+        }
+    }
+    let mut fail = |what: String| errors.push(format!("{at}: {value} {what}"));
+    if let Some(kind) = node["type"].as_str() {
+        let fits = match kind {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_i64() || value.is_u64(),
+            other => panic!("type {other} not checked"),
+        };
+        if !fits {
+            return fail(format!("is not of type {kind}"));
+        }
+    }
+    if let Some(values) = node["enum"].as_array() {
+        if !values.contains(value) {
+            fail(format!("is not one of {values:?}"));
+        }
+    }
+    if let (Some(min), Some(n)) = (node["minimum"].as_f64(), value.as_f64()) {
+        if n < min {
+            fail(format!("is below {min}"));
+        }
+    }
+    if let (Some(max), Some(n)) = (node["maximum"].as_f64(), value.as_f64()) {
+        if n > max {
+            fail(format!("is above {max}"));
+        }
+    }
+    if let (Some(min), Some(s)) = (node["minLength"].as_u64(), value.as_str()) {
+        if (s.chars().count() as u64) < min {
+            fail(format!("is shorter than {min}"));
+        }
+    }
+    if let (Some(pattern), Some(s)) = (node["pattern"].as_str(), value.as_str()) {
+        if !matches(pattern, s) {
+            fail(format!("does not match {pattern}"));
+        }
+    }
+    match value {
+        Value::Object(map) => {
+            if let Some(pattern) = node["propertyNames"]["pattern"].as_str() {
+                for key in map.keys().filter(|k| !matches(pattern, k)) {
+                    errors.push(format!("{at}.{key}: does not match {pattern}"));
+                }
+            }
+            if required {
+                for key in node["required"].as_array().into_iter().flatten() {
+                    if !map.contains_key(key.as_str().unwrap()) {
+                        errors.push(format!("{at}: {key} is required"));
+                    }
+                }
+            }
+            for (key, v) in map {
+                let path = format!("{at}.{key}");
+                match (&node["properties"][key], &node["additionalProperties"]) {
+                    (Value::Null, Value::Bool(false)) => {
+                        errors.push(format!("{path}: not in the schema"))
+                    }
+                    (Value::Null, Value::Object(_)) => check(
+                        root,
+                        &node["additionalProperties"],
+                        v,
+                        &path,
+                        required,
+                        errors,
+                    ),
+                    (Value::Null, _) => {}
+                    (child, _) => check(root, child, v, &path, required, errors),
+                }
+            }
+        }
+        Value::Array(items) if !node["items"].is_null() => {
+            for (i, v) in items.iter().enumerate() {
+                let path = format!("{at}[{i}]");
+                check(root, &node["items"], v, &path, required, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn matches(pattern: &str, s: &str) -> bool {
+    regex_automata::meta::Regex::new(pattern)
+        .unwrap()
+        .is_match(s)
+}
+
+#[test]
+fn renamed_keys_are_listed_as_deprecated() {
+    let schema = luciuz_config::schema();
+    let handler = &schema["definitions"]["TimeoutsConfig"]["properties"]["handler_secs"];
+    assert_eq!(handler["deprecated"], true);
+    assert!(handler["description"]
+        .as_str()
+        .unwrap()
+        .contains("`handler`"));
+    assert!(handler.get("default").is_none());
+    // Keys that never shipped under their `_secs` name are not listed.
+    assert!(!schema.to_string().contains("sticky_ttl_secs"));
+}
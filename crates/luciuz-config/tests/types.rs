@@ -0,0 +1,64 @@
+//! Typed values: durations, sizes, addresses and choices are parsed when the
+//! config is read, and unknown keys are refused.
+
+use std::time::Duration;
+
+const SERVER: &str = "[server]\nhttp_listen = \"127.0.0.1:8080\"\n";
+
+/// The first error as `code location: message`.
+fn error(toml: &str) -> String {
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", toml);
+    assert!(cfg.is_none());
+    let d = report.errors().next().expect("an error");
+    format!("{} {}: {}", d.code, report.location(d), d.message)
+}
+
+#[test]
+fn durations_and_sizes_take_units() {
+    let (cfg, report) = luciuz_config::check_str(
+        "luciuz.toml",
+        &format!("{SERVER}hsts_max_age = \"1h30m\"\n\n[compression]\nmin_size = \"1KiB\"\n"),
+    );
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    assert_eq!(cfg.server.hsts_max_age, Duration::from_secs(5400));
+    assert_eq!(cfg.compression.unwrap().min_size, 1024);
+
+    let (cfg, _) = luciuz_config::check_str(
+        "luciuz.toml",
+        &format!("{SERVER}\n[compression]\nmin_size = 4096\n"),
+    );
+    assert_eq!(cfg.unwrap().compression.unwrap().min_size, 4096);
+}
+
+#[test]
+fn malformed_values_are_located() {
+    assert_eq!(
+        error(&format!("{SERVER}hsts_max_age = \"3 weeks\"\n")),
+        "E001 luciuz.toml:3:16: server.hsts_max_age: invalid duration \"3 weeks\" \
+         (e.g. \"30s\", \"5m\", \"1h30m\", \"7d\")"
+    );
+    assert_eq!(
+        error(&format!("{SERVER}\n[compression]\nmin_size = -5\n")),
+        "E001 luciuz.toml:5:12: compression.min_size: invalid size -5: must not be negative"
+    );
+    assert_eq!(
+        error("[server]\nhttp_listen = \"localhost\"\n"),
+        "E001 luciuz.toml:2:15: server.http_listen: invalid socket address syntax"
+    );
+}
+
+#[test]
+fn choices_and_keys_are_checked() {
+    let choice = error("[server]\nprofile = \"blog\"\n");
+    assert!(
+        choice.starts_with("E003 luciuz.toml:2:11: server.profile: unknown variant `blog`"),
+        "{choice}"
+    );
+    let key = error(&format!("{SERVER}hsts_max_ages = \"1d\"\n"));
+    assert!(
+        key.starts_with(
+            "E009 luciuz.toml:3:17: server.hsts_max_ages: unknown field `hsts_max_ages`"
+        ),
+        "{key}"
+    );
+}
@@ -1 +1,249 @@
-// Placeholder crate (to be implemented in upcoming milestones)
+//! Control plane: the live configuration generation, and reloads triggered
+//! by SIGHUP or the local control API.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use luciuz_config::Config;
+use tokio::net::TcpListener;
+use tower::{service_fn, ServiceExt};
+use tracing::{error, info, warn};
+
+/// One loaded configuration and what was built from it.
+#[derive(Debug)]
+pub struct Generation<T> {
+    /// 1 for the startup config, +1 per successful reload.
+    pub number: u64,
+    pub config: Config,
+    pub state: T,
+    /// Keys whose changes this generation could not apply, as they need a
+    /// restart (see [`pin_restart_only`]).
+    pub restart_pending: Vec<&'static str>,
+}
+
+/// Builds a generation's state (routers, pools, ...) from a validated config.
+pub type Build<T> = Box<dyn Fn(&Config) -> anyhow::Result<T> + Send + Sync>;
+
+/// Holds the live generation behind an atomic pointer and replaces it on
+/// reload. Requests pick the generation when they start and keep it until
+/// they finish.
+pub struct Reloader<T> {
+    path: String,
+    current: ArcSwap<Generation<T>>,
+    build: Build<T>,
+    /// Serializes reloads.
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl<T: Send + Sync + 'static> Reloader<T> {
+    /// Build generation 1 from `config`, loaded from `path`.
+    pub fn new(path: &str, config: Config, build: Build<T>) -> anyhow::Result<Arc<Self>> {
+        let state = build(&config)?;
+        Ok(Arc::new(Self {
+            path: path.to_string(),
+            current: ArcSwap::from_pointee(Generation {
+                number: 1,
+                config,
+                state,
+                restart_pending: Vec::new(),
+            }),
+            build,
+            lock: tokio::sync::Mutex::new(()),
+        }))
+    }
+
+    pub fn current(&self) -> Arc<Generation<T>> {
+        self.current.load_full()
+    }
+
+    /// Load, validate and build the config file again, then swap it in.
+    /// On any error the running generation stays; the error is logged with
+    /// the generation number that was attempted.
+    ///
+    /// Settings bound to the listeners stay as they were started; they are
+    /// logged and listed in the generation's `restart_pending`.
+    pub async fn reload(self: &Arc<Self>) -> Result<u64, String> {
+        let _guard = self.lock.lock().await;
+        let old = self.current();
+        let number = old.number + 1;
+        // Reading the files and building the state block.
+        let result = tokio::task::spawn_blocking({
+            let (this, old) = (self.clone(), old.clone());
+            move || this.next_generation(&old.config, number)
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("reload task failed: {e}")));
+        match result {
+            Ok(generation) => {
+                self.current.store(Arc::new(generation));
+                info!(generation = number, path = %self.path, "config reloaded");
+                Ok(number)
+            }
+            Err(err) => {
+                error!(
+                    generation = number,
+                    serving = old.number,
+                    path = %self.path,
+                    error = %err,
+                    "config reload failed; keeping the running config"
+                );
+                Err(err)
+            }
+        }
+    }
+
+    fn next_generation(&self, old: &Config, number: u64) -> Result<Generation<T>, String> {
+        let (mut config, report) = luciuz_config::load(&self.path).map_err(|e| e.to_string())?;
+        for d in report.warnings() {
+            warn!(generation = number, code = %d.code, at = %report.location(d), "{}", d.message);
+        }
+        let pinned = pin_restart_only(old, &mut config);
+        if !pinned.is_empty() {
+            // The file was valid as written, but maybe not with the running
+            // values: e.g. admin users relying on an ACME listener that is
+            // not running.
+            let report = luciuz_config::revalidate(&self.path, &config);
+            if report.has_errors() {
+                return Err(format!(
+                    "invalid with the running values of {} (they need a restart): {}",
+                    pinned.join(", "),
+                    report.summary()
+                ));
+            }
+            warn!(
+                generation = number,
+                keys = ?pinned,
+                "config changes need a restart; keeping the running values"
+            );
+        }
+        let state = (self.build)(&config).map_err(|e| format!("{e:#}"))?;
+        Ok(Generation {
+            number,
+            config,
+            state,
+            restart_pending: pinned,
+        })
+    }
+}
+
+/// Settings bound when the process starts (listeners, whether ACME serves
+/// HTTPS, logging, the control API): copy the running values into `new` and
+/// name the keys that changed.
+pub fn pin_restart_only(old: &Config, new: &mut Config) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    if new.server.http_listen != old.server.http_listen {
+        changed.push("server.http_listen");
+        new.server.http_listen = old.server.http_listen;
+    }
+    if new.server.https_listen != old.server.https_listen {
+        changed.push("server.https_listen");
+        new.server.https_listen = old.server.https_listen;
+    }
+    if new.server.proxy_protocol != old.server.proxy_protocol {
+        changed.push("server.proxy_protocol");
+        new.server.proxy_protocol = old.server.proxy_protocol.clone();
+    }
+    // The HTTPS listener exists only with ACME; the rest of [acme] reloads.
+    if new.acme.enabled != old.acme.enabled {
+        changed.push("acme.enabled");
+        new.acme.enabled = old.acme.enabled;
+    }
+    if new.telemetry != old.telemetry {
+        changed.push("telemetry");
+        new.telemetry = old.telemetry.clone();
+    }
+    if new.control != old.control {
+        changed.push("control");
+        new.control = old.control.clone();
+    }
+    changed
+}
+
+/// A router that hands each request to the `pick`ed router of the
+/// generation live when the request arrives.
+pub fn live_router<T: Send + Sync + 'static>(
+    reloader: Arc<Reloader<T>>,
+    pick: fn(&T) -> &Router,
+) -> Router {
+    Router::new().fallback_service(service_fn(move |req: Request<Body>| {
+        let generation = reloader.current();
+        let router = pick(&generation.state).clone();
+        async move { Ok::<_, Infallible>(router.oneshot(req).await.into_response()) }
+    }))
+}
+
+/// Reload on every SIGHUP, for the life of the process.
+#[cfg(unix)]
+pub async fn reload_on_sighup<T: Send + Sync + 'static>(reloader: Arc<Reloader<T>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(err) => {
+            error!(error = %err, "cannot listen for SIGHUP; reload via the control API only");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("SIGHUP received, reloading config");
+        let _ = reloader.reload().await;
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn reload_on_sighup<T: Send + Sync + 'static>(_reloader: Arc<Reloader<T>>) {}
+
+/// Serve the control API on `listener`: `POST /reload` and `GET /status`.
+pub async fn serve<T: Send + Sync + 'static>(
+    reloader: Arc<Reloader<T>>,
+    listener: TcpListener,
+) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/reload", post(reload_handler::<T>))
+        .route("/status", get(status_handler::<T>))
+        .with_state(reloader);
+    info!(addr = %listener.local_addr()?, "control API listening");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn reload_handler<T: Send + Sync + 'static>(
+    State(reloader): State<Arc<Reloader<T>>>,
+) -> Response {
+    match reloader.reload().await {
+        Ok(generation) => Json(serde_json::json!({
+            "generation": generation,
+            "restart_pending": reloader.current().restart_pending,
+        }))
+        .into_response(),
+        Err(err) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": err,
+                "generation": reloader.current().number,
+            })),
+        )
+            .into_response(),
+    }
+}
+
+async fn status_handler<T: Send + Sync + 'static>(
+    State(reloader): State<Arc<Reloader<T>>>,
+) -> Response {
+    let generation = reloader.current();
+    Json(serde_json::json!({
+        "generation": generation.number,
+        "config": reloader.path,
+        "profile": generation.config.server.profile.as_str(),
+        "restart_pending": generation.restart_pending,
+    }))
+    .into_response()
+}
@@ -0,0 +1,84 @@
+//! Reloads apply TLS settings, keep listener settings (and say so), and
+//! refuse a config that is only valid with the values they could not apply.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use luciuz_config::Profile;
+use luciuz_control::Reloader;
+
+const ACME: &str = "[acme]\nenabled = true\nemail = \"ops@example.com\"\n\
+                    domains = [\"admin.example.com\"]\ncache_dir = \"/nonexistent/luciuz-acme\"\n";
+
+const ROUTE: &str = "\n[[proxy.routes]]\nprefix = \"/api\"\nupstream = \"http://127.0.0.1:3000\"\n";
+
+const USERS: &str = "users = [{ name = \"ops\", password_hash = \
+                     \"$argon2id$v=19$m=19456,t=2,p=1$3coYMyD9TEDe6UX2GEPQ2A$kWvMbvbAq9NlB5jCwvTdaXVrseKwg+PnkQzuORW918g\" }]\n";
+
+/// A reloader for `toml`, written to a file of its own, building nothing.
+fn start(name: &str, toml: &str) -> (PathBuf, Arc<Reloader<()>>) {
+    let dir = std::env::temp_dir().join(format!("luciuz-reload-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("luciuz.toml");
+    std::fs::write(&path, toml).unwrap();
+    let path_str = path.to_str().unwrap();
+    let (cfg, _) = luciuz_config::load(path_str).unwrap();
+    let reloader = Reloader::new(path_str, cfg, Box::new(|_| Ok(()))).unwrap();
+    (path, reloader)
+}
+
+fn panel(admin: &str) -> String {
+    format!(
+        "[server]\nprofile = \"admin_panel\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         {ACME}\n[admin]\nallow = [\"10.0.0.0/8\"]\n{admin}{ROUTE}"
+    )
+}
+
+#[tokio::test]
+async fn reloading_into_an_admin_panel_applies_its_tls() {
+    let public = format!("[server]\nhttp_listen = \"127.0.0.1:8080\"\n\n{ACME}{ROUTE}");
+    let (path, reloader) = start("into-panel", &public);
+    std::fs::write(&path, panel("client_ca = \"/etc/luciuz/admin-ca.pem\"\n")).unwrap();
+
+    assert_eq!(reloader.reload().await, Ok(2));
+    let live = reloader.current();
+    assert_eq!(live.config.server.profile, Profile::AdminPanel);
+    assert_eq!(
+        live.config.admin.as_ref().unwrap().client_ca.as_deref(),
+        Some("/etc/luciuz/admin-ca.pem")
+    );
+    assert!(live.restart_pending.is_empty());
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
+
+#[tokio::test]
+async fn listener_changes_are_kept_and_reported() {
+    let public = |addr: &str| format!("[server]\nhttp_listen = \"{addr}\"\n{ROUTE}");
+    let (path, reloader) = start("listeners", &public("127.0.0.1:8080"));
+    std::fs::write(&path, public("127.0.0.1:8081")).unwrap();
+
+    assert_eq!(reloader.reload().await, Ok(2));
+    // Still pending after a reload that changes nothing else.
+    assert_eq!(reloader.reload().await, Ok(3));
+    let live = reloader.current();
+    assert_eq!(
+        live.config.server.http_listen,
+        Some("127.0.0.1:8080".parse().unwrap())
+    );
+    assert_eq!(live.restart_pending, ["server.http_listen"]);
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
+
+#[tokio::test]
+async fn a_panel_needing_the_acme_listener_is_refused_without_it() {
+    let public = format!("[server]\nhttp_listen = \"127.0.0.1:8080\"\n{ROUTE}");
+    let (path, reloader) = start("panel-tls", &public);
+    // Valid as written, but the HTTPS listener only starts with a restart.
+    std::fs::write(&path, panel(USERS)).unwrap();
+
+    let err = reloader.reload().await.unwrap_err();
+    assert!(err.contains("acme.enabled"), "{err}");
+    assert!(err.contains("admin.users needs TLS"), "{err}");
+    assert_eq!(reloader.current().number, 1);
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
@@ -0,0 +1,737 @@
+//! Caddyfile: the first site block that serves files or proxies requests
+//! becomes the site; the site blocks that only `redir` to it give its extra
+//! names and canonical host.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::{mem, vec::IntoIter};
+
+use luciuz_config::parse_size;
+
+use crate::site::Site;
+use crate::{note, Directive, Note};
+
+type Error = (usize, String);
+
+pub(crate) fn translate(text: &str, notes: &mut Vec<Note>) -> Result<Site, Error> {
+    let mut top = parse(text)?;
+    let mut site = Site::default();
+
+    if top.first().is_some_and(|d| d.name.is_empty()) {
+        global_options(&mut site, notes, &top.remove(0));
+    }
+    top.retain(|d| {
+        let snippet = d.name.starts_with('(') || d.name == "import";
+        if snippet {
+            note(
+                notes,
+                d.line,
+                format!("{}: snippets and imports are not followed", d.name),
+            );
+        }
+        !snippet
+    });
+    // A Caddyfile with one site may leave out its braces.
+    if top.len() > 1 && top[0].block.is_none() {
+        let mut address = top.remove(0);
+        address.block = Some(mem::take(&mut top));
+        top.push(address);
+    }
+
+    let Some(main) = top.iter().position(serves) else {
+        return Err((
+            0,
+            "no site block serves files or proxies requests".to_string(),
+        ));
+    };
+    let matchers = named_matchers(notes, body(&top[main]));
+    let mut cx = Cx { notes, matchers };
+    addresses(&mut site, cx.notes, &top[main]);
+    for d in body(&top[main]) {
+        site_directive(&mut site, &mut cx, d);
+    }
+    for (i, block) in top.iter().enumerate() {
+        if i == main {
+            continue;
+        }
+        if body(block).iter().all(|d| d.name == "redir") {
+            let names = addresses(&mut site, cx.notes, block);
+            for d in body(block) {
+                redirect(&mut site, cx.notes, d, &names);
+            }
+        } else {
+            note(
+                cx.notes,
+                block.line,
+                format!(
+                    "{}: luciuz serves one site, the one at line {} is translated",
+                    block.name, top[main].line
+                ),
+            );
+        }
+    }
+    Ok(site)
+}
+
+struct Cx<'a> {
+    notes: &'a mut Vec<Note>,
+    /// `@name` -> its path patterns, for matchers of that one kind.
+    matchers: HashMap<String, Vec<String>>,
+}
+
+fn body(d: &Directive) -> &[Directive] {
+    d.block.as_deref().unwrap_or_default()
+}
+
+fn arg(d: &Directive, i: usize) -> &str {
+    d.args.get(i).map_or("", String::as_str)
+}
+
+/// The site block serves files or proxies requests.
+fn serves(d: &Directive) -> bool {
+    body(d).iter().any(|c| match c.name.as_str() {
+        "root" | "file_server" | "reverse_proxy" | "try_files" => true,
+        "handle" | "handle_path" | "route" => serves(c),
+        _ => false,
+    })
+}
+
+fn global_options(site: &mut Site, notes: &mut Vec<Note>, block: &Directive) {
+    for d in body(block) {
+        match d.name.as_str() {
+            "email" => site.acme_email = Some(arg(d, 0).to_string()),
+            "acme_ca" => note(
+                notes,
+                d.line,
+                "acme_ca: luciuz uses Let's Encrypt, staging or production (acme.prod)",
+            ),
+            _ => note(
+                notes,
+                d.line,
+                format!("{}: global option not translated", d.name),
+            ),
+        }
+    }
+}
+
+/// Listeners and names of a site block's addresses; the names.
+fn addresses(site: &mut Site, notes: &mut Vec<Note>, block: &Directive) -> Vec<String> {
+    let mut names = Vec::new();
+    let all = std::iter::once(&block.name).chain(&block.args);
+    for address in all.flat_map(|a| a.split(',')).filter(|a| !a.is_empty()) {
+        let (scheme, rest) = match address.split_once("://") {
+            Some((s, r)) => (Some(s), r),
+            None => (None, address),
+        };
+        let rest = rest.split('/').next().unwrap_or_default();
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((h, p)) if !h.ends_with(':') => (h, Some(p)),
+            _ => (rest, None),
+        };
+        if port.is_some_and(|p| p.parse::<u16>().is_err()) {
+            note(
+                notes,
+                block.line,
+                format!("{address}: not an address luciuz can bind"),
+            );
+            continue;
+        }
+        let local = host == "localhost" || host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok();
+        if host.contains('*') {
+            note(
+                notes,
+                block.line,
+                format!("{address}: wildcard names are not translated"),
+            );
+        } else if host.is_empty() || local || scheme == Some("http") || port == Some("80") {
+            let ip = match host {
+                "" | "localhost" => "127.0.0.1",
+                h => h,
+            };
+            if host.is_empty() {
+                // `:8080` listens on every interface.
+                site.listen(
+                    format!("0.0.0.0:{}", port.unwrap_or("80")),
+                    false,
+                    block.line,
+                );
+            } else {
+                site.listen(format!("{ip}:{}", port.unwrap_or("80")), false, block.line);
+            }
+            if local && scheme != Some("http") {
+                note(
+                    notes,
+                    block.line,
+                    format!(
+                        "{address}: served over plain HTTP, luciuz has no internal certificates"
+                    ),
+                );
+            }
+            if !local && !host.is_empty() {
+                names.push(host.to_ascii_lowercase());
+                site.add_domain(&host.to_ascii_lowercase());
+            }
+        } else {
+            // Caddy gets a certificate and redirects HTTP to HTTPS.
+            site.listen(
+                format!("0.0.0.0:{}", port.unwrap_or("443")),
+                true,
+                block.line,
+            );
+            site.listen("0.0.0.0:80".to_string(), false, block.line);
+            names.push(host.to_ascii_lowercase());
+            site.add_domain(&host.to_ascii_lowercase());
+        }
+    }
+    names
+}
+
+fn named_matchers(notes: &mut Vec<Note>, directives: &[Directive]) -> HashMap<String, Vec<String>> {
+    let mut matchers = HashMap::new();
+    for d in directives.iter().filter(|d| d.name.starts_with('@')) {
+        let conditions: Vec<&Directive> = match &d.block {
+            Some(block) => block.iter().collect(),
+            None => vec![d],
+        };
+        let paths = match conditions.as_slice() {
+            [c] if arg(c, 0) == "path" && d.block.is_none() => d.args[1..].to_vec(),
+            [c] if c.name == "path" => c.args.clone(),
+            _ => {
+                note(
+                    notes,
+                    d.line,
+                    format!("{}: only path matchers are translated", d.name),
+                );
+                continue;
+            }
+        };
+        matchers.insert(d.name.clone(), paths);
+    }
+    matchers
+}
+
+fn site_directive(site: &mut Site, cx: &mut Cx, d: &Directive) {
+    match d.name.as_str() {
+        n if n.starts_with('@') => {}
+        "root" => match d.args.as_slice() {
+            [path] => site.root = Some(path.clone()),
+            [m, path] if m == "*" => site.root = Some(path.clone()),
+            _ => note(
+                cx.notes,
+                d.line,
+                format!(
+                    "root {}: only the site's own root is translated",
+                    d.args.join(" ")
+                ),
+            ),
+        },
+        "file_server" => {
+            if site.root.is_none() {
+                site.root = Some(".".to_string());
+            }
+            for a in &d.args {
+                note(
+                    cx.notes,
+                    d.line,
+                    match a.as_str() {
+                        "browse" => "file_server browse: directory listings are configured in static_site.listing".to_string(),
+                        a => format!("file_server {a}: not translated"),
+                    },
+                );
+            }
+            for c in body(d) {
+                note(
+                    cx.notes,
+                    c.line,
+                    format!("file_server {}: not translated", c.name),
+                );
+            }
+        }
+        "try_files" => try_files(site, cx.notes, d),
+        "reverse_proxy" => {
+            let (prefix, upstreams) = match cx.matcher(d) {
+                Ok(Some(prefix)) => (Some(prefix), &d.args[1..]),
+                Ok(None) if is_matcher(arg(d, 0)) => (None, &d.args[1..]),
+                Ok(None) => (None, &d.args[..]),
+                Err(why) => return note(cx.notes, d.line, why),
+            };
+            match prefix {
+                Some(prefix) => reverse_proxy(site, cx.notes, d, upstreams, &prefix, false),
+                None => note(
+                    cx.notes,
+                    d.line,
+                    "reverse_proxy: luciuz proxies prefixes below '/', not the whole site",
+                ),
+            }
+        }
+        "handle" | "route" | "handle_path" => {
+            let strip = d.name == "handle_path";
+            match cx.matcher(d) {
+                Ok(None) if !strip => {
+                    for c in body(d) {
+                        site_directive(site, cx, c);
+                    }
+                }
+                Ok(Some(prefix)) => {
+                    for c in body(d) {
+                        if c.name == "reverse_proxy" && !is_matcher(arg(c, 0)) {
+                            reverse_proxy(site, cx.notes, c, &c.args, &prefix, strip);
+                        } else {
+                            note(
+                                cx.notes,
+                                c.line,
+                                format!(
+                                    "{}: only reverse_proxy is translated inside {} {}",
+                                    c.name,
+                                    d.name,
+                                    arg(d, 0)
+                                ),
+                            );
+                        }
+                    }
+                }
+                Ok(None) => note(
+                    cx.notes,
+                    d.line,
+                    "handle_path: luciuz proxies prefixes below '/', not the whole site",
+                ),
+                Err(why) => note(cx.notes, d.line, why),
+            }
+        }
+        "header" => header(site, cx, d),
+        "encode" => {
+            let mut names: Vec<&str> = d.args.iter().map(String::as_str).collect();
+            names.extend(body(d).iter().map(|c| c.name.as_str()));
+            if names.is_empty() {
+                names = vec!["zstd", "gzip"];
+            }
+            for name in names {
+                match name {
+                    "zstd" | "gzip" | "br" => {
+                        if !site.compression.contains(&name) {
+                            site.compression.push(match name {
+                                "zstd" => "zstd",
+                                "gzip" => "gzip",
+                                _ => "br",
+                            });
+                        }
+                    }
+                    _ => note(cx.notes, d.line, format!("encode {name}: not translated")),
+                }
+            }
+        }
+        "tls" => match d.args.as_slice() {
+            [email] if email.contains('@') => site.acme_email = Some(email.clone()),
+            [internal] if internal == "internal" => note(
+                cx.notes,
+                d.line,
+                "tls internal: luciuz obtains its certificates with ACME ([acme])",
+            ),
+            _ => {
+                site.certificates = true;
+                note(
+                    cx.notes,
+                    d.line,
+                    "tls: luciuz obtains its certificates with ACME ([acme])",
+                );
+            }
+        },
+        "request_body" => {
+            for c in body(d) {
+                let size = arg(c, 0);
+                match (c.name.as_str(), parse_size(size)) {
+                    ("max_size", Ok(n)) if n > 0 => {
+                        site.max_body = Some((size.to_string(), c.line))
+                    }
+                    _ => note(
+                        cx.notes,
+                        c.line,
+                        format!("request_body {} {size}: not translated", c.name),
+                    ),
+                }
+            }
+        }
+        "redir" => note(
+            cx.notes,
+            d.line,
+            format!(
+                "redir {}: only redirects to HTTPS and to the canonical host are translated",
+                d.args.join(" ")
+            ),
+        ),
+        _ => note(cx.notes, d.line, reason(&d.name)),
+    }
+}
+
+impl Cx<'_> {
+    /// The prefix directive `d`'s matcher selects; `None` for every request.
+    fn matcher(&self, d: &Directive) -> Result<Option<String>, String> {
+        let m = arg(d, 0);
+        let paths = match m {
+            "*" | "/*" => return Ok(None),
+            m if m.starts_with('/') => vec![m.to_string()],
+            m if m.starts_with('@') => match self.matchers.get(m) {
+                Some(paths) => paths.clone(),
+                None => return Err(format!("{} {m}: only path matchers are translated", d.name)),
+            },
+            _ => return Ok(None),
+        };
+        match paths.as_slice() {
+            [p] if p.ends_with('*') && p.len() > 2 && !p[..p.len() - 1].contains('*') => Ok(Some(
+                p.trim_end_matches('*').trim_end_matches('/').to_string(),
+            )),
+            _ => Err(format!(
+                "{} {m}: only prefix matchers like /api/* are translated",
+                d.name
+            )),
+        }
+    }
+}
+
+fn is_matcher(arg: &str) -> bool {
+    arg == "*" || arg.starts_with('/') || arg.starts_with('@')
+}
+
+fn reverse_proxy(
+    site: &mut Site,
+    notes: &mut Vec<Note>,
+    d: &Directive,
+    upstreams: &[String],
+    prefix: &str,
+    strip_prefix: bool,
+) {
+    let mut targets = Vec::new();
+    let to = body(d).iter().filter(|c| c.name == "to");
+    for u in upstreams.iter().chain(to.flat_map(|c| &c.args)) {
+        match u.split_once("://") {
+            Some(("http" | "https", _)) => targets.push(u.trim_end_matches('/').to_string()),
+            Some(_) => note(
+                notes,
+                d.line,
+                format!("reverse_proxy {u}: only http(s) upstreams are supported"),
+            ),
+            None if u.starts_with("unix/") => note(
+                notes,
+                d.line,
+                format!("reverse_proxy {u}: unix sockets are not supported"),
+            ),
+            None if u.starts_with(':') => targets.push(format!("http://127.0.0.1{u}")),
+            None => targets.push(format!("http://{u}")),
+        }
+    }
+    if targets.is_empty() {
+        return;
+    }
+    // Caddy passes the client's Host.
+    let mut preserve_host = true;
+    for c in body(d) {
+        let (header, val) = (arg(c, 0), arg(c, 1));
+        match (c.name.as_str(), header.to_ascii_lowercase().as_str()) {
+            ("header_up", "host") if val == "{upstream_hostport}" => preserve_host = false,
+            ("header_up", "host") if val == "{host}" => {}
+            ("header_up", "x-forwarded-for" | "x-forwarded-proto" | "x-forwarded-host") => {}
+            ("header_up", "x-real-ip") => note(
+                notes,
+                c.line,
+                "X-Real-IP: luciuz passes the client address in X-Forwarded-For",
+            ),
+            ("header_up", _) => note(
+                notes,
+                c.line,
+                format!("{header}: custom upstream headers are not supported"),
+            ),
+            ("to", _) => {}
+            // Luciuz pools are round-robin.
+            ("lb_policy", "round_robin") => {}
+            (name, _) => note(
+                notes,
+                c.line,
+                format!("reverse_proxy {name}: not translated"),
+            ),
+        }
+    }
+    let pool = prefix.trim_start_matches('/').replace('/', "_");
+    if !site.proxy(prefix, &pool, targets, strip_prefix, preserve_host) {
+        note(
+            notes,
+            d.line,
+            format!("{} {prefix}: this prefix is already proxied", d.name),
+        );
+    }
+}
+
+fn try_files(site: &mut Site, notes: &mut Vec<Note>, d: &Directive) {
+    site.try_files.clear();
+    for candidate in &d.args {
+        let translated = match candidate.strip_prefix("{path}") {
+            Some(rest) if !rest.contains('{') => Some(format!("$uri{rest}")),
+            _ if candidate.starts_with('/') && !candidate.contains('{') => Some(candidate.clone()),
+            _ => None,
+        };
+        match translated {
+            Some(c) => site.try_files.push(c),
+            None => note(
+                notes,
+                d.line,
+                format!("try_files {candidate}: only {{path}} and fixed paths are translated"),
+            ),
+        }
+    }
+}
+
+fn header(site: &mut Site, cx: &mut Cx, d: &Directive) {
+    let (glob, fields): (Option<String>, Vec<(&str, &str, usize)>) = {
+        let m = arg(d, 0);
+        let (matcher, rest) = if is_matcher(m) && m != "*" {
+            (Some(m), &d.args[1..])
+        } else if m == "*" {
+            (None, &d.args[1..])
+        } else {
+            (None, &d.args[..])
+        };
+        let mut fields: Vec<(&str, &str, usize)> = body(d)
+            .iter()
+            .map(|c| (c.name.as_str(), arg(c, 0), c.line))
+            .collect();
+        if let [name, rest @ ..] = rest {
+            fields.push((name, rest.first().map_or("", String::as_str), d.line));
+        }
+        let glob = match matcher {
+            Some(m) => match cache_glob(&cx.matchers, m) {
+                Some(glob) => Some(glob),
+                None => {
+                    return note(
+                        cx.notes,
+                        d.line,
+                        format!("header {m}: only path and extension matchers are translated"),
+                    )
+                }
+            },
+            None => None,
+        };
+        (glob, fields)
+    };
+    for (name, val, line) in fields {
+        let name = name.trim_start_matches(['+', '?']);
+        if let Some(removed) = name.strip_prefix('-') {
+            note(
+                cx.notes,
+                line,
+                format!("-{removed}: removing response headers is not supported"),
+            );
+            continue;
+        }
+        match &glob {
+            Some(glob) if name.eq_ignore_ascii_case("cache-control") => {
+                site.cache_rules.push((glob.clone(), val.to_string()))
+            }
+            Some(glob) => note(
+                cx.notes,
+                line,
+                format!(
+                    "{name} on {glob}: response headers are only translated for the whole site"
+                ),
+            ),
+            None => {
+                if let Some(why) = site.header(name, val) {
+                    note(cx.notes, line, why);
+                }
+            }
+        }
+    }
+}
+
+/// `/static/*`, `*.css` or `@name` (of such paths) as a cache rule glob.
+fn cache_glob(matchers: &HashMap<String, Vec<String>>, m: &str) -> Option<String> {
+    let paths = match m.strip_prefix('@') {
+        Some(_) => matchers.get(m)?.clone(),
+        None => vec![m.to_string()],
+    };
+    let exts: Option<Vec<&str>> = paths
+        .iter()
+        .map(|p| {
+            p.strip_prefix("*.")
+                .filter(|e| e.chars().all(|c| c.is_ascii_alphanumeric()))
+        })
+        .collect();
+    match (exts, paths.as_slice()) {
+        (Some(exts), [_]) => Some(format!("*.{}", exts[0])),
+        (Some(exts), _) => Some(format!("*.{{{}}}", exts.join(","))),
+        (None, [p]) if p.ends_with("/*") && !p[..p.len() - 2].contains('*') => {
+            Some(format!("{}**", &p[..p.len() - 1]))
+        }
+        _ => None,
+    }
+}
+
+fn redirect(site: &mut Site, notes: &mut Vec<Note>, d: &Directive, names: &[String]) {
+    let target = arg(d, 0);
+    let https = target.starts_with("https://");
+    let host = target
+        .strip_prefix("https://")
+        .or_else(|| target.strip_prefix("{scheme}://"))
+        .and_then(|t| t.strip_suffix("{uri}"));
+    let code = d.args.get(1).map_or("", String::as_str);
+    let permanent_or_temporary = matches!(
+        code,
+        "" | "permanent" | "temporary" | "301" | "302" | "307" | "308"
+    );
+    let translated = permanent_or_temporary
+        && match host {
+            Some("{host}" | "{hostport}") => site.redirect(https, None, names),
+            Some(h) if !h.contains('{') => site.redirect(https, Some(h), names),
+            _ => false,
+        };
+    if !translated {
+        note(
+            notes,
+            d.line,
+            format!(
+                "redir {}: only redirects to HTTPS and to the canonical host are translated",
+                d.args.join(" ")
+            ),
+        );
+    }
+}
+
+/// Why directive `name` is not translated.
+fn reason(name: &str) -> String {
+    let why = match name {
+        "log" => "logging is configured in [telemetry]",
+        "php_fastcgi" => "FastCGI upstreams are not supported",
+        "basicauth" | "basic_auth" | "forward_auth" => "access control is not translated",
+        "rewrite" | "uri" => "rewrites are not translated",
+        "respond" | "error" | "abort" => "fixed responses are not translated",
+        "handle_errors" => "luciuz renders its own error pages ([errors])",
+        "import" => "snippets and imports are not followed",
+        _ => "not translated",
+    };
+    format!("{name}: {why}")
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Newline,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut out = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                out.push((Token::Newline, line));
+                line += 1;
+            }
+            c if c.is_whitespace() => {}
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '"' | '`' => {
+                let start = line;
+                let mut word = String::new();
+                loop {
+                    let ch = chars
+                        .next()
+                        .ok_or((start, "unterminated string".to_string()))?;
+                    match ch {
+                        _ if ch == c => break,
+                        '\\' if c == '"' && chars.peek() == Some(&'"') => {
+                            word.push('"');
+                            chars.next();
+                        }
+                        '\n' => {
+                            line += 1;
+                            word.push(ch);
+                        }
+                        _ => word.push(ch),
+                    }
+                }
+                out.push((Token::Word(word), start));
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some(n) = chars.next_if(|n| !n.is_whitespace()) {
+                    word.push(n);
+                }
+                out.push(match word.as_str() {
+                    "{" => (Token::Open, line),
+                    "}" => (Token::Close, line),
+                    _ => (Token::Word(word), line),
+                });
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn parse(text: &str) -> Result<Vec<Directive>, Error> {
+    let mut tokens = tokenize(text)?.into_iter();
+    parse_block(&mut tokens, None)
+}
+
+fn parse_block(
+    tokens: &mut IntoIter<(Token, usize)>,
+    open: Option<usize>,
+) -> Result<Vec<Directive>, Error> {
+    let mut out = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut start = 0;
+    loop {
+        match tokens.next() {
+            None => {
+                if let Some(line) = open {
+                    return Err((line, "\"{\" is not closed".to_string()));
+                }
+                if !words.is_empty() {
+                    out.push(directive(mem::take(&mut words), start, None));
+                }
+                return Ok(out);
+            }
+            Some((Token::Word(w), line)) => {
+                if words.is_empty() {
+                    start = line;
+                }
+                words.push(w);
+            }
+            Some((Token::Newline, _)) => {
+                if !words.is_empty() {
+                    out.push(directive(mem::take(&mut words), start, None));
+                }
+            }
+            Some((Token::Open, line)) => {
+                if words.is_empty() && !(open.is_none() && out.is_empty()) {
+                    return Err((line, "unexpected \"{\"".to_string()));
+                }
+                if words.is_empty() {
+                    // The global options block.
+                    start = line;
+                    words.push(String::new());
+                }
+                let block = parse_block(tokens, Some(line))?;
+                out.push(directive(mem::take(&mut words), start, Some(block)));
+            }
+            Some((Token::Close, line)) => {
+                if open.is_none() {
+                    return Err((line, "unexpected \"}\"".to_string()));
+                }
+                if !words.is_empty() {
+                    out.push(directive(mem::take(&mut words), start, None));
+                }
+                return Ok(out);
+            }
+        }
+    }
+}
+
+fn directive(mut words: Vec<String>, line: usize, block: Option<Vec<Directive>>) -> Directive {
+    let name = words.remove(0);
+    Directive {
+        name,
+        args: words,
+        line,
+        block,
+    }
+}
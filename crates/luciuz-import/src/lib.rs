@@ -0,0 +1,110 @@
+//! `luciuz import`: translate an nginx config or a Caddyfile into
+//! `luciuz.toml`. The common subset (site names, listeners, root and index,
+//! `try_files`, proxied prefixes, security and cache headers, redirects to
+//! HTTPS or a canonical host, certificates) is translated; everything else
+//! is reported as a [`Note`], and the result always passes
+//! `luciuz_config::check_str`.
+
+mod caddy;
+mod nginx;
+mod site;
+
+use luciuz_core::{error::LuciuzError, Result};
+
+use site::Site;
+
+/// What an import produced.
+#[derive(Debug, Clone)]
+pub struct Import {
+    /// The translated `luciuz.toml`, notes included as comments.
+    pub config: String,
+    pub notes: Vec<Note>,
+}
+
+/// Something not translated, or translated differently than written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// Line in the imported file; 0 when the note is about the whole file.
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Contact address for the ACME account, when the source has none.
+    pub acme_email: Option<String>,
+}
+
+/// Translate the nginx config `text`, read from `file`.
+pub fn nginx(file: &str, text: &str, options: &Options) -> Result<Import> {
+    let mut notes = Vec::new();
+    let site = nginx::translate(text, &mut notes).map_err(|e| parse_error(file, e))?;
+    finish("nginx", file, site, notes, options)
+}
+
+/// Translate the Caddyfile `text`, read from `file`.
+pub fn caddy(file: &str, text: &str, options: &Options) -> Result<Import> {
+    let mut notes = Vec::new();
+    let site = caddy::translate(text, &mut notes).map_err(|e| parse_error(file, e))?;
+    finish("caddy", file, site, notes, options)
+}
+
+fn parse_error(file: &str, (line, message): (usize, String)) -> LuciuzError {
+    LuciuzError::Config(located(file, &Note { line, message }))
+}
+
+/// One directive (nginx) or line (Caddyfile) and its block, if any.
+#[derive(Debug, Clone)]
+pub(crate) struct Directive {
+    pub(crate) name: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) line: usize,
+    pub(crate) block: Option<Vec<Directive>>,
+}
+
+pub(crate) fn note(notes: &mut Vec<Note>, line: usize, message: impl Into<String>) {
+    notes.push(Note {
+        line,
+        message: message.into(),
+    });
+}
+
+fn finish(
+    kind: &str,
+    file: &str,
+    mut site: Site,
+    mut notes: Vec<Note>,
+    options: &Options,
+) -> Result<Import> {
+    site.settle(&mut notes, options);
+    notes.sort_by_key(|n| n.line);
+
+    let mut config = format!("# Imported from {file} by `luciuz import {kind}`.\n");
+    if notes.is_empty() {
+        config.push_str("# Everything was translated.\n");
+    } else {
+        config.push_str("#\n# Review these notes before use:\n");
+        for n in &notes {
+            config.push_str(&format!("#   {}\n", located(file, n)));
+        }
+    }
+    config.push('\n');
+    config.push_str(&site.render());
+
+    let (_, report) = luciuz_config::check_str("luciuz.toml", &config);
+    if report.has_errors() {
+        return Err(LuciuzError::Config(format!(
+            "the translation of {file} does not validate:\n{}",
+            report.render()
+        )));
+    }
+    Ok(Import { config, notes })
+}
+
+/// `file:line: message`, or `file: message` for the whole file.
+pub fn located(file: &str, n: &Note) -> String {
+    match n.line {
+        0 => format!("{file}: {}", n.message),
+        line => format!("{file}:{line}: {}", n.message),
+    }
+}
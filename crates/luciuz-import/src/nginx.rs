@@ -0,0 +1,935 @@
+//! nginx: the first `server` block that serves files or proxies requests
+//! becomes the site; the servers that only redirect to it give its HTTP
+//! listener, extra names and canonical host.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::{mem, vec::IntoIter};
+
+use crate::site::Site;
+use crate::{note, Directive, Note};
+
+type Error = (usize, String);
+
+pub(crate) fn translate(text: &str, notes: &mut Vec<Note>) -> Result<Site, Error> {
+    let top = parse(text)?;
+    let mut cx = Cx {
+        notes,
+        upstreams: HashMap::new(),
+        proxy_defaults: Vec::new(),
+        defaults_used: false,
+    };
+
+    let mut servers = Vec::new();
+    let mut inherited = Vec::new();
+    for d in &top {
+        match d.name.as_str() {
+            "http" => {
+                for d in body(d) {
+                    match d.name.as_str() {
+                        "server" => servers.push(d),
+                        "upstream" => upstream(&mut cx, d),
+                        _ => inherited.push(d),
+                    }
+                }
+            }
+            "server" => servers.push(d),
+            "upstream" => upstream(&mut cx, d),
+            "events" | "stream" | "mail" | "user" | "pid" | "daemon" | "load_module" | "env" => {
+                note(cx.notes, d.line, reason(d))
+            }
+            n if n.starts_with("worker_") => note(cx.notes, d.line, reason(d)),
+            _ => inherited.push(d),
+        }
+    }
+
+    let Some(main) = servers.iter().position(|s| serves(s) && !redirects(s)) else {
+        return Err((
+            0,
+            "no server block serves files or proxies requests".to_string(),
+        ));
+    };
+    let mut site = Site::default();
+    cx.proxy_defaults = inherited
+        .iter()
+        .copied()
+        .chain(body(servers[main]))
+        .filter(|d| is_proxy_setting(d))
+        .cloned()
+        .collect();
+    for d in inherited.iter().copied().chain(body(servers[main])) {
+        site_directive(&mut site, &mut cx, d);
+    }
+    for (i, s) in servers.iter().enumerate() {
+        if i == main {
+            continue;
+        }
+        if redirects(s) || !serves(s) {
+            redirector(&mut site, &mut cx, s);
+        } else {
+            note(
+                cx.notes,
+                s.line,
+                format!(
+                    "server: luciuz serves one site, the one at line {} is translated",
+                    servers[main].line
+                ),
+            );
+        }
+    }
+    if !cx.defaults_used {
+        for d in &cx.proxy_defaults {
+            note(
+                cx.notes,
+                d.line,
+                format!("{}: no location is proxied", d.name),
+            );
+        }
+    }
+    Ok(site)
+}
+
+struct Cx<'a> {
+    notes: &'a mut Vec<Note>,
+    /// `upstream` name -> its servers.
+    upstreams: HashMap<String, Vec<String>>,
+    /// `proxy_*` settings of the site, inherited by its locations.
+    proxy_defaults: Vec<Directive>,
+    defaults_used: bool,
+}
+
+fn body(d: &Directive) -> &[Directive] {
+    d.block.as_deref().unwrap_or_default()
+}
+
+fn arg(d: &Directive, i: usize) -> &str {
+    d.args.get(i).map_or("", String::as_str)
+}
+
+fn is_proxy_setting(d: &Directive) -> bool {
+    d.name.starts_with("proxy_") && d.name != "proxy_pass"
+}
+
+fn is_acme_challenge(location: &Directive) -> bool {
+    location
+        .args
+        .last()
+        .is_some_and(|p| p.starts_with("/.well-known/acme-challenge"))
+}
+
+/// The block serves files or proxies requests.
+fn serves(d: &Directive) -> bool {
+    body(d).iter().any(|c| match c.name.as_str() {
+        "root" | "alias" | "try_files" | "proxy_pass" => true,
+        "location" => !is_acme_challenge(c) && serves(c),
+        _ => false,
+    })
+}
+
+/// A `return` at server level answers every request.
+fn redirects(server: &Directive) -> bool {
+    body(server).iter().any(|d| d.name == "return")
+}
+
+fn site_directive(site: &mut Site, cx: &mut Cx, d: &Directive) {
+    match d.name.as_str() {
+        "listen" => listen(site, cx.notes, d),
+        "server_name" => server_name(site, cx.notes, d),
+        "root" => site.root = Some(arg(d, 0).to_string()),
+        "index" => {
+            site.index = Some(arg(d, 0).to_string());
+            if d.args.len() > 1 {
+                note(
+                    cx.notes,
+                    d.line,
+                    format!(
+                        "index {}: luciuz tries one index file",
+                        d.args[1..].join(" ")
+                    ),
+                );
+            }
+        }
+        "try_files" => try_files(site, cx.notes, d),
+        "location" => location(site, cx, d),
+        "add_header" => {
+            if let Some(why) = site.header(arg(d, 0), arg(d, 1)) {
+                note(cx.notes, d.line, why);
+            }
+        }
+        "ssl_certificate" => {
+            site.certificates = true;
+            note(
+                cx.notes,
+                d.line,
+                "ssl_certificate: luciuz obtains its certificates with ACME ([acme])",
+            );
+        }
+        "ssl_certificate_key" => site.certificates = true,
+        "client_max_body_size" => max_body(site, cx.notes, d),
+        "gzip" if arg(d, 0) == "on" => site.compression.push("gzip"),
+        "brotli" if arg(d, 0) == "on" => site.compression.push("br"),
+        "gzip" | "brotli" | "http2" => {}
+        "expires" => {
+            if let Some(cc) = expires(cx.notes, d) {
+                site.cache_control = Some(cc);
+            }
+        }
+        "error_page" => error_page(site, cx.notes, d),
+        "if" => {
+            let names = site.domains.clone();
+            for c in body(d) {
+                if c.name == "return" {
+                    redirect(site, cx.notes, c, &names);
+                } else {
+                    note(
+                        cx.notes,
+                        c.line,
+                        format!("{}: conditions are not translated", c.name),
+                    );
+                }
+            }
+        }
+        "proxy_pass" => note(
+            cx.notes,
+            d.line,
+            "proxy_pass: luciuz proxies prefixes below '/', not the whole site",
+        ),
+        _ if is_proxy_setting(d) => {}
+        _ => note(cx.notes, d.line, reason(d)),
+    }
+}
+
+fn location(site: &mut Site, cx: &mut Cx, d: &Directive) {
+    let (modifier, path) = match d.args.as_slice() {
+        [m, p] => (m.as_str(), p.as_str()),
+        [p] => ("", p.as_str()),
+        _ => ("", ""),
+    };
+    let inner = body(d);
+    match modifier {
+        _ if is_acme_challenge(d) => {}
+        "" | "^~" if path.starts_with('@') => note(
+            cx.notes,
+            d.line,
+            format!("location {path}: named locations are not translated"),
+        ),
+        "" | "^~" if path == "/" => {
+            if inner.iter().any(|c| c.name == "proxy_pass") {
+                note(
+                    cx.notes,
+                    d.line,
+                    "location /: luciuz proxies prefixes below '/', not the whole site",
+                );
+            }
+            for c in inner {
+                if c.name != "proxy_pass" && !is_proxy_setting(c) {
+                    site_directive(site, cx, c);
+                }
+            }
+        }
+        "" | "^~" if path.starts_with('/') => {
+            let prefix = path.trim_end_matches('/');
+            if inner.iter().any(|c| c.name == "proxy_pass") {
+                proxy_route(site, cx, d, prefix);
+            } else if let Some(cc) = cache_directives(cx.notes, inner) {
+                site.cache_rules.push((format!("{prefix}/**"), cc));
+            }
+        }
+        "=" if path.starts_with('/') && !path.contains(['*', '?', '[', '{']) => {
+            if let Some(cc) = cache_directives(cx.notes, inner) {
+                site.cache_rules.push((path.to_string(), cc));
+            }
+        }
+        "~" | "~*" if path.starts_with("/\\.") && inner.iter().any(|c| c.name == "deny") => {
+            // Hidden files: `static_site.deny_hidden` refuses them.
+        }
+        "~" | "~*" => match extensions(path) {
+            Some(glob) => {
+                if let Some(cc) = cache_directives(cx.notes, inner) {
+                    site.cache_rules.push((glob, cc));
+                }
+            }
+            None => note(
+                cx.notes,
+                d.line,
+                format!("location {modifier} {path}: regex locations are not translated"),
+            ),
+        },
+        _ => note(
+            cx.notes,
+            d.line,
+            format!(
+                "location {}: only prefix and file extension locations are translated",
+                d.args.join(" ")
+            ),
+        ),
+    }
+}
+
+/// The Cache-Control a static location sets (`expires` and `add_header`
+/// both send one, clients merge them); its other directives are noted.
+fn cache_directives(notes: &mut Vec<Note>, inner: &[Directive]) -> Option<String> {
+    let mut cc = Vec::new();
+    for d in inner {
+        match d.name.as_str() {
+            "expires" => cc.extend(expires(notes, d)),
+            "add_header" if arg(d, 0).eq_ignore_ascii_case("cache-control") => {
+                cc.push(arg(d, 1).to_string())
+            }
+            "add_header" => note(
+                notes,
+                d.line,
+                format!(
+                    "{}: response headers are only translated for the whole site",
+                    arg(d, 0)
+                ),
+            ),
+            "root" | "alias" | "try_files" | "index" => note(
+                notes,
+                d.line,
+                format!(
+                    "{}: only the site's own is translated, not a location's",
+                    d.name
+                ),
+            ),
+            _ => note(notes, d.line, reason(d)),
+        }
+    }
+    (!cc.is_empty()).then(|| cc.join(", "))
+}
+
+/// `\.(css|js)$` and the like, as the glob `*.{css,js}`.
+fn extensions(regex: &str) -> Option<String> {
+    let mut r = regex.strip_prefix('^').unwrap_or(regex);
+    for lead in [".*", ".+", "/.*", "/.+"] {
+        r = r.strip_prefix(lead).unwrap_or(r);
+    }
+    let r = r.strip_prefix("\\.")?.strip_suffix('$')?;
+    let alts = match r.strip_prefix('(') {
+        Some(group) => group
+            .strip_prefix("?:")
+            .unwrap_or(group)
+            .strip_suffix(')')?,
+        None => r,
+    };
+    let exts: Vec<&str> = alts.split('|').collect();
+    if exts
+        .iter()
+        .any(|e| e.is_empty() || !e.chars().all(|c| c.is_ascii_alphanumeric()))
+    {
+        return None;
+    }
+    Some(match exts.as_slice() {
+        [one] => format!("*.{one}"),
+        _ => format!("*.{{{}}}", exts.join(",")),
+    })
+}
+
+fn proxy_route(site: &mut Site, cx: &mut Cx, location: &Directive, prefix: &str) {
+    let inner = body(location);
+    let pass = inner.iter().find(|c| c.name == "proxy_pass").unwrap();
+    let url = arg(pass, 0);
+    let untranslated = |why: &str| format!("proxy_pass {url}: {why}");
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return note(cx.notes, pass.line, untranslated("not an http(s) URL"));
+    };
+    if url.contains('$') {
+        return note(
+            cx.notes,
+            pass.line,
+            untranslated("upstreams built from variables are not translated"),
+        );
+    }
+    if !matches!(scheme, "http" | "https") {
+        return note(cx.notes, pass.line, untranslated("not an http(s) URL"));
+    }
+    if rest.starts_with("unix:") {
+        return note(
+            cx.notes,
+            pass.line,
+            untranslated("unix sockets are not supported"),
+        );
+    }
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    // With a path, nginx replaces the location prefix by it.
+    let strip_prefix = !path.is_empty();
+    let path = path.trim_end_matches('/');
+    let targets = match cx.upstreams.get(authority) {
+        Some(servers) => servers
+            .iter()
+            .map(|s| format!("{scheme}://{s}{path}"))
+            .collect(),
+        None => vec![format!("{scheme}://{authority}{path}")],
+    };
+
+    // nginx inherits proxy_* settings a location does not set itself.
+    cx.defaults_used = true;
+    let inherited: Vec<&Directive> = cx
+        .proxy_defaults
+        .iter()
+        .filter(|d| !inner.iter().any(|c| c.name == d.name))
+        .collect();
+    let mut preserve_host = false;
+    let mut notes = Vec::new();
+    for d in inner.iter().chain(inherited) {
+        match d.name.as_str() {
+            "proxy_pass" => {}
+            "proxy_set_header" => {
+                let (header, val) = (arg(d, 0), arg(d, 1));
+                match header.to_ascii_lowercase().as_str() {
+                    "host" if matches!(val, "$host" | "$http_host") => preserve_host = true,
+                    "host" if val == "$proxy_host" => {}
+                    "host" => note(
+                        &mut notes,
+                        d.line,
+                        format!("Host {val}: luciuz sends the client's Host or the upstream's"),
+                    ),
+                    "x-forwarded-for" | "x-forwarded-proto" | "x-forwarded-host" => {}
+                    "x-real-ip" => note(
+                        &mut notes,
+                        d.line,
+                        "X-Real-IP: luciuz passes the client address in X-Forwarded-For",
+                    ),
+                    "upgrade" | "connection" => note(
+                        &mut notes,
+                        d.line,
+                        format!("{header}: luciuz does not proxy WebSocket upgrades"),
+                    ),
+                    _ => note(
+                        &mut notes,
+                        d.line,
+                        format!("{header}: custom upstream headers are not supported"),
+                    ),
+                }
+            }
+            _ => note(&mut notes, d.line, reason(d)),
+        }
+    }
+    cx.notes.append(&mut notes);
+    if !site.proxy(prefix, authority, targets, strip_prefix, preserve_host) {
+        note(
+            cx.notes,
+            location.line,
+            format!("location {prefix}: this prefix is already proxied"),
+        );
+    }
+}
+
+/// A server that only redirects (to HTTPS, or from `www.`): its listeners
+/// and names are the site's.
+fn redirector(site: &mut Site, cx: &mut Cx, server: &Directive) {
+    let names: Vec<String> = body(server)
+        .iter()
+        .filter(|d| d.name == "server_name")
+        .flat_map(|d| d.args.iter().map(|n| n.to_ascii_lowercase()))
+        .collect();
+    for d in body(server) {
+        match d.name.as_str() {
+            "listen" => listen(site, cx.notes, d),
+            "server_name" => server_name(site, cx.notes, d),
+            "return" => redirect(site, cx.notes, d, &names),
+            "if" => {
+                for c in body(d) {
+                    if c.name == "return" {
+                        redirect(site, cx.notes, c, &names);
+                    } else {
+                        note(
+                            cx.notes,
+                            c.line,
+                            format!("{}: conditions are not translated", c.name),
+                        );
+                    }
+                }
+            }
+            "location" if is_acme_challenge(d) => {}
+            "location" if d.args.len() == 1 && arg(d, 0) == "/" => {
+                for c in body(d) {
+                    if c.name == "return" {
+                        redirect(site, cx.notes, c, &names);
+                    } else {
+                        note(cx.notes, c.line, reason(c));
+                    }
+                }
+            }
+            "ssl_certificate" | "ssl_certificate_key" | "http2" => site.certificates = true,
+            _ => note(cx.notes, d.line, reason(d)),
+        }
+    }
+}
+
+fn redirect(site: &mut Site, notes: &mut Vec<Note>, d: &Directive, names: &[String]) {
+    let (code, target) = (arg(d, 0), arg(d, 1));
+    if d.args.len() == 1 && matches!(code, "403" | "404" | "444") {
+        // Requests for names the site does not serve.
+        return;
+    }
+    let https = target.starts_with("https://");
+    let host = target
+        .strip_prefix("https://")
+        .or_else(|| target.strip_prefix("$scheme://"))
+        .and_then(|t| t.strip_suffix("$request_uri"))
+        .filter(|_| matches!(code, "301" | "302" | "307" | "308"));
+    let translated = match host {
+        Some("$host" | "$server_name" | "$http_host") => site.redirect(https, None, names),
+        Some(h) if !h.contains('$') => site.redirect(https, Some(h), names),
+        _ => false,
+    };
+    if !translated {
+        note(
+            notes,
+            d.line,
+            format!(
+                "return {}: only redirects to HTTPS and to the canonical host are translated",
+                d.args.join(" ")
+            ),
+        );
+    }
+}
+
+fn listen(site: &mut Site, notes: &mut Vec<Note>, d: &Directive) {
+    let spec = arg(d, 0);
+    if spec.starts_with("unix:") {
+        return note(
+            notes,
+            d.line,
+            format!("listen {spec}: unix sockets are not supported"),
+        );
+    }
+    let Some(addr) = listen_addr(spec) else {
+        return note(
+            notes,
+            d.line,
+            format!("listen {spec}: not an address luciuz can bind"),
+        );
+    };
+    let mut tls = false;
+    for flag in &d.args[1..] {
+        match flag.as_str() {
+            "ssl" => tls = true,
+            "http2" | "default_server" | "default" => {}
+            f if f.starts_with("ipv6only=") => {}
+            f => note(notes, d.line, format!("listen {spec} {f}: not translated")),
+        }
+    }
+    site.listen(addr, tls, d.line);
+}
+
+/// `80`, `*:80`, `127.0.0.1`, `[::]:443`, `localhost:8080` as `ip:port`.
+fn listen_addr(spec: &str) -> Option<String> {
+    if spec.bytes().all(|b| b.is_ascii_digit()) {
+        return Some(format!("0.0.0.0:{spec}"));
+    }
+    let (host, port) = match spec.strip_prefix('[') {
+        Some(rest) => {
+            let (ip, port) = rest.split_once(']')?;
+            ip.parse::<Ipv6Addr>().ok()?;
+            (
+                &spec[..ip.len() + 2],
+                port.strip_prefix(':').unwrap_or("80"),
+            )
+        }
+        None => spec.split_once(':').unwrap_or((spec, "80")),
+    };
+    port.parse::<u16>().ok()?;
+    let host = match host {
+        "*" => "0.0.0.0",
+        "localhost" => "127.0.0.1",
+        h if h.starts_with('[') || h.parse::<Ipv4Addr>().is_ok() => h,
+        _ => return None,
+    };
+    Some(format!("{host}:{port}"))
+}
+
+fn server_name(site: &mut Site, notes: &mut Vec<Note>, d: &Directive) {
+    for name in &d.args {
+        let name = name.to_ascii_lowercase();
+        if name.starts_with('~') || name.starts_with('.') || name.contains('*') {
+            note(
+                notes,
+                d.line,
+                format!("server_name {name}: wildcard and regex names are not translated"),
+            );
+        } else if name != "_" && name != "localhost" && name.parse::<Ipv4Addr>().is_err() {
+            site.add_domain(&name);
+        }
+    }
+}
+
+fn try_files(site: &mut Site, notes: &mut Vec<Note>, d: &Directive) {
+    site.try_files.clear();
+    for (i, candidate) in d.args.iter().enumerate() {
+        let fixed = |s: &str| !s.contains('$');
+        if i + 1 == d.args.len() && candidate.starts_with('=') {
+            // Luciuz answers 404 when nothing matches.
+            if candidate != "=404" {
+                note(
+                    notes,
+                    d.line,
+                    format!("try_files {candidate}: luciuz answers 404 when nothing matches"),
+                );
+            }
+        } else if candidate.strip_prefix("$uri").is_some_and(fixed)
+            || candidate.starts_with('/') && fixed(candidate)
+        {
+            site.try_files.push(candidate.clone());
+        } else {
+            note(
+                notes,
+                d.line,
+                format!("try_files {candidate}: only $uri and fixed paths are translated"),
+            );
+        }
+    }
+}
+
+fn error_page(site: &mut Site, notes: &mut Vec<Note>, d: &Directive) {
+    let Some((uri, codes)) = d.args.split_last() else {
+        return;
+    };
+    let others: Vec<&str> = codes
+        .iter()
+        .map(String::as_str)
+        .filter(|c| *c != "404")
+        .collect();
+    if codes.len() > others.len() {
+        match uri.strip_prefix('/') {
+            Some(page) if others.iter().all(|c| !c.starts_with('=')) => {
+                site.not_found = Some(page.to_string())
+            }
+            _ => note(
+                notes,
+                d.line,
+                format!("error_page 404 {uri}: only a page under root is translated"),
+            ),
+        }
+    }
+    if !others.is_empty() && others.iter().all(|c| !c.starts_with('=')) {
+        note(
+            notes,
+            d.line,
+            format!(
+                "error_page {}: luciuz renders its own error pages ([errors])",
+                others.join(" ")
+            ),
+        );
+    }
+}
+
+fn max_body(site: &mut Site, notes: &mut Vec<Note>, d: &Directive) {
+    let spec = arg(d, 0);
+    let (digits, unit) = spec.split_at(
+        spec.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(spec.len()),
+    );
+    let unit = match unit {
+        "" => "",
+        "k" | "K" => "KiB",
+        "m" | "M" => "MiB",
+        "g" | "G" => "GiB",
+        _ => {
+            return note(
+                notes,
+                d.line,
+                format!("client_max_body_size {spec}: not a size"),
+            )
+        }
+    };
+    match digits.parse::<u64>() {
+        Ok(0) => note(
+            notes,
+            d.line,
+            "client_max_body_size 0: luciuz always limits bodies, proxy.max_body_bytes keeps its default",
+        ),
+        Ok(n) => site.max_body = Some((format!("{n}{unit}"), d.line)),
+        Err(_) => note(
+            notes,
+            d.line,
+            format!("client_max_body_size {spec}: not a size"),
+        ),
+    }
+}
+
+/// The Cache-Control `expires` sets, if any.
+fn expires(notes: &mut Vec<Note>, d: &Directive) -> Option<String> {
+    let spec = arg(d, 0);
+    match spec {
+        "off" => None,
+        "epoch" => Some("no-cache".to_string()),
+        "max" => Some("max-age=315360000".to_string()),
+        s if s.starts_with('-') => Some("no-cache".to_string()),
+        s => match seconds(s) {
+            Some(secs) if d.args.len() == 1 => Some(format!("max-age={secs}")),
+            _ => {
+                note(
+                    notes,
+                    d.line,
+                    format!("expires {}: not translated", d.args.join(" ")),
+                );
+                None
+            }
+        },
+    }
+}
+
+/// An nginx time (`30d`, `1h30m`, `600`) in seconds.
+fn seconds(s: &str) -> Option<u64> {
+    let mut total = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = if rest.starts_with("ms") {
+            2
+        } else {
+            rest.len().min(1)
+        };
+        let factor = match &rest[..unit_len] {
+            "" | "s" => 1,
+            "ms" => 0,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86_400,
+            "w" => 7 * 86_400,
+            "M" => 30 * 86_400,
+            "y" => 365 * 86_400,
+            _ => return None,
+        };
+        total += n * factor;
+        rest = &rest[unit_len..];
+    }
+    Some(total)
+}
+
+fn upstream(cx: &mut Cx, d: &Directive) {
+    let mut servers = Vec::new();
+    for s in body(d) {
+        let addr = arg(s, 0);
+        match s.name.as_str() {
+            "server" if addr.starts_with("unix:") => note(
+                cx.notes,
+                s.line,
+                format!("server {addr}: unix sockets are not supported"),
+            ),
+            "server" => {
+                servers.push(addr.to_string());
+                for p in &s.args[1..] {
+                    note(
+                        cx.notes,
+                        s.line,
+                        format!("server {addr} {p}: pool targets take no parameters"),
+                    );
+                }
+            }
+            _ => note(
+                cx.notes,
+                s.line,
+                format!("{}: upstream settings are not translated", s.name),
+            ),
+        }
+    }
+    cx.upstreams.insert(arg(d, 0).to_string(), servers);
+}
+
+/// Why directive `d` is not translated.
+fn reason(d: &Directive) -> String {
+    let name = d.name.as_str();
+    let why = match name {
+        "events" | "user" | "pid" | "daemon" | "load_module" | "env" | "stream" | "mail" => {
+            "process settings do not apply, luciuz runs as its own service"
+        }
+        n if n.starts_with("worker_") => {
+            "process settings do not apply, luciuz runs as its own service"
+        }
+        "access_log" | "error_log" | "log_format" | "log_not_found" | "open_log_file_cache" => {
+            "logging is configured in [telemetry]"
+        }
+        "include" if arg(d, 0).ends_with("mime.types") => {
+            "content types come from the file extension"
+        }
+        "include" if arg(d, 0).contains("letsencrypt") => "luciuz uses its own TLS settings",
+        "include" => "included files are not followed, import them separately",
+        n if n.starts_with("ssl_") => "luciuz uses its own TLS settings",
+        "default_type" | "types" | "charset" => "content types come from the file extension",
+        "sendfile"
+        | "tcp_nopush"
+        | "tcp_nodelay"
+        | "keepalive_timeout"
+        | "keepalive_requests"
+        | "server_tokens"
+        | "types_hash_max_size"
+        | "server_names_hash_bucket_size"
+        | "multi_accept"
+        | "aio"
+        | "directio" => "connection tuning is not translated",
+        n if n.starts_with("gzip_") || n.starts_with("brotli_") => {
+            "compression settings beyond on/off are not translated"
+        }
+        n if n.starts_with("proxy_") => {
+            "upstream settings beyond proxy_pass and Host are not translated"
+        }
+        n if n.starts_with("fastcgi_") || n.starts_with("uwsgi_") || n.starts_with("scgi_") => {
+            "FastCGI, uwsgi and SCGI upstreams are not supported"
+        }
+        "allow" | "deny" | "auth_basic" | "auth_basic_user_file" => {
+            "access control is not translated"
+        }
+        "limit_req" | "limit_req_zone" | "limit_conn" | "limit_conn_zone" => {
+            "rate limits are not translated"
+        }
+        "rewrite" => "rewrites are not translated",
+        "return" => "only a redirecting server's return is translated",
+        "autoindex" => "directory listings are configured in static_site.listing",
+        _ => "not translated",
+    };
+    format!("{name}: {why}")
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Semicolon,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut out = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            ';' => out.push((Token::Semicolon, line)),
+            '{' => out.push((Token::Open, line)),
+            '}' => out.push((Token::Close, line)),
+            '"' | '\'' => {
+                let start = line;
+                let mut word = String::new();
+                loop {
+                    let ch = chars
+                        .next()
+                        .ok_or((start, "unterminated string".to_string()))?;
+                    match ch {
+                        _ if ch == c => break,
+                        '\\' => match chars.next() {
+                            Some(n @ ('"' | '\'' | '\\')) => word.push(n),
+                            Some(n) => {
+                                word.push('\\');
+                                word.push(n);
+                            }
+                            None => return Err((start, "unterminated string".to_string())),
+                        },
+                        '\n' => {
+                            line += 1;
+                            word.push(ch);
+                        }
+                        _ => word.push(ch),
+                    }
+                }
+                out.push((Token::Word(word), start));
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some(&n) = chars.peek() {
+                    if n.is_whitespace() || n == ';' || n == '}' || n == '{' && !word.ends_with('$')
+                    {
+                        break;
+                    }
+                    chars.next();
+                    word.push(n);
+                    match n {
+                        // `${name}` is a variable, not a block.
+                        '{' => {
+                            while let Some(m) = chars.next_if(|&m| m != '\n') {
+                                word.push(m);
+                                if m == '}' {
+                                    break;
+                                }
+                            }
+                        }
+                        '\\' => word.extend(chars.next_if(|&m| m != '\n')),
+                        _ => {}
+                    }
+                }
+                out.push((Token::Word(word), line));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn parse(text: &str) -> Result<Vec<Directive>, Error> {
+    let mut tokens = tokenize(text)?.into_iter();
+    parse_block(&mut tokens, None)
+}
+
+fn parse_block(
+    tokens: &mut IntoIter<(Token, usize)>,
+    open: Option<usize>,
+) -> Result<Vec<Directive>, Error> {
+    let mut out = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut start = 0;
+    let unterminated = |words: &[String], start| {
+        Err((
+            start,
+            format!("\"{}\" is not terminated by \";\"", words[0]),
+        ))
+    };
+    loop {
+        match tokens.next() {
+            None => {
+                if let Some(line) = open {
+                    return Err((line, "\"{\" is not closed".to_string()));
+                }
+                if !words.is_empty() {
+                    return unterminated(&words, start);
+                }
+                return Ok(out);
+            }
+            Some((Token::Word(w), line)) => {
+                if words.is_empty() {
+                    start = line;
+                }
+                words.push(w);
+            }
+            Some((Token::Semicolon, line)) => {
+                if words.is_empty() {
+                    return Err((line, "unexpected \";\"".to_string()));
+                }
+                out.push(directive(mem::take(&mut words), start, None));
+            }
+            Some((Token::Open, line)) => {
+                if words.is_empty() {
+                    return Err((line, "unexpected \"{\"".to_string()));
+                }
+                let block = parse_block(tokens, Some(line))?;
+                out.push(directive(mem::take(&mut words), start, Some(block)));
+            }
+            Some((Token::Close, line)) => {
+                if open.is_none() {
+                    return Err((line, "unexpected \"}\"".to_string()));
+                }
+                if !words.is_empty() {
+                    return unterminated(&words, start);
+                }
+                return Ok(out);
+            }
+        }
+    }
+}
+
+fn directive(mut words: Vec<String>, line: usize, block: Option<Vec<Directive>>) -> Directive {
+    let name = words.remove(0);
+    Directive {
+        name,
+        args: words,
+        line,
+        block,
+    }
+}
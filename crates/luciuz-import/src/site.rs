@@ -0,0 +1,402 @@
+//! The one site an import produces, before it is written as `luciuz.toml`.
+
+use std::time::Duration;
+
+use luciuz_config::{format_duration, CONFIG_VERSION};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table};
+
+use crate::{note, Note, Options};
+
+/// Headers `server.security_headers` sends, with their values.
+const SECURITY_HEADERS: &[(&str, &str)] = &[
+    ("x-content-type-options", "nosniff"),
+    ("referrer-policy", "strict-origin-when-cross-origin"),
+    ("x-frame-options", "DENY"),
+    ("cross-origin-opener-policy", "same-origin"),
+    ("cross-origin-resource-policy", "same-site"),
+];
+
+#[derive(Debug, Default)]
+pub(crate) struct Site {
+    /// Every listener of the source; [`Site::settle`] keeps one per kind.
+    pub(crate) listens: Vec<Listen>,
+    /// The source configures certificates.
+    pub(crate) certificates: bool,
+    /// Names to request certificates for.
+    pub(crate) domains: Vec<String>,
+    pub(crate) canonical_host: Option<String>,
+    pub(crate) acme_email: Option<String>,
+    pub(crate) root: Option<String>,
+    pub(crate) index: Option<String>,
+    pub(crate) try_files: Vec<String>,
+    pub(crate) not_found: Option<String>,
+    pub(crate) cache_control: Option<String>,
+    /// (glob, Cache-Control value).
+    pub(crate) cache_rules: Vec<(String, String)>,
+    pub(crate) routes: Vec<Route>,
+    pub(crate) pools: Vec<Pool>,
+    pub(crate) hsts: Option<Hsts>,
+    /// A size `luciuz_config::parse_size` reads, and its line.
+    pub(crate) max_body: Option<(String, usize)>,
+    pub(crate) compression: Vec<&'static str>,
+
+    http_listen: Option<String>,
+    https_listen: Option<String>,
+    email: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct Listen {
+    /// `ip:port`.
+    pub(crate) addr: String,
+    pub(crate) tls: bool,
+    pub(crate) line: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct Route {
+    pub(crate) prefix: String,
+    pub(crate) target: Target,
+    pub(crate) strip_prefix: bool,
+    pub(crate) preserve_host: bool,
+}
+
+#[derive(Debug)]
+pub(crate) enum Target {
+    Upstream(String),
+    Pool(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct Pool {
+    pub(crate) name: String,
+    pub(crate) targets: Vec<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Hsts {
+    pub(crate) max_age: u64,
+    pub(crate) include_subdomains: bool,
+    pub(crate) preload: bool,
+}
+
+impl Site {
+    pub(crate) fn listen(&mut self, addr: String, tls: bool, line: usize) {
+        if !self.listens.iter().any(|l| l.addr == addr && l.tls == tls) {
+            self.listens.push(Listen { addr, tls, line });
+        }
+    }
+
+    pub(crate) fn add_domain(&mut self, name: &str) {
+        if !self.domains.iter().any(|d| d == name) {
+            self.domains.push(name.to_string());
+        }
+    }
+
+    /// Add `route`, unless its prefix is already routed.
+    pub(crate) fn add_route(&mut self, route: Route) -> bool {
+        if self.routes.iter().any(|r| r.prefix == route.prefix) {
+            return false;
+        }
+        self.routes.push(route);
+        true
+    }
+
+    /// Route `prefix` to `targets`: one upstream, or a pool named `name`.
+    pub(crate) fn proxy(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        targets: Vec<String>,
+        strip_prefix: bool,
+        preserve_host: bool,
+    ) -> bool {
+        let target = match targets.as_slice() {
+            [one] => Target::Upstream(one.clone()),
+            _ => {
+                if !self.pools.iter().any(|p| p.name == name) {
+                    self.pools.push(Pool {
+                        name: name.to_string(),
+                        targets,
+                    });
+                }
+                Target::Pool(name.to_string())
+            }
+        };
+        self.add_route(Route {
+            prefix: prefix.to_string(),
+            target,
+            strip_prefix,
+            preserve_host,
+        })
+    }
+
+    /// A redirect of every request of a server named `names` to `host`
+    /// (`None`: the request's own), keeping the path. Luciuz does it
+    /// itself when it is to HTTPS on the same name (with ACME), or from
+    /// `www.example.com` to `example.com` (`canonical_host`).
+    pub(crate) fn redirect(&mut self, https: bool, host: Option<&str>, names: &[String]) -> bool {
+        match host {
+            None => https,
+            Some(h) if names.iter().any(|n| n == h) => https,
+            Some(h) if !names.is_empty() && names.iter().all(|n| *n == format!("www.{h}")) => {
+                self.canonical_host = Some(h.to_string());
+                self.add_domain(h);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Apply a response header the source sends on the whole site; the
+    /// reason it is not translated, if it is not.
+    pub(crate) fn header(&mut self, name: &str, val: &str) -> Option<String> {
+        let lower = name.to_ascii_lowercase();
+        if let Some((_, sent)) = SECURITY_HEADERS.iter().find(|(h, _)| *h == lower) {
+            return (!val.eq_ignore_ascii_case(sent))
+                .then(|| format!("{name}: luciuz sends \"{sent}\" (server.security_headers)"));
+        }
+        match lower.as_str() {
+            "strict-transport-security" => {
+                let mut hsts = Hsts {
+                    max_age: 0,
+                    include_subdomains: false,
+                    preload: false,
+                };
+                let mut max_age = None;
+                for part in val.split(';').map(str::trim) {
+                    match part.split_once('=') {
+                        Some((k, v)) if k.eq_ignore_ascii_case("max-age") => {
+                            max_age = v.trim_matches('"').parse().ok()
+                        }
+                        None if part.eq_ignore_ascii_case("includeSubDomains") => {
+                            hsts.include_subdomains = true
+                        }
+                        None if part.eq_ignore_ascii_case("preload") => hsts.preload = true,
+                        _ => {}
+                    }
+                }
+                match max_age {
+                    Some(secs) if secs > 0 => {
+                        hsts.max_age = secs;
+                        self.hsts = Some(hsts);
+                        None
+                    }
+                    _ => Some(format!("{name}: max-age not understood in \"{val}\"")),
+                }
+            }
+            "cache-control" => {
+                self.cache_control = Some(val.to_string());
+                None
+            }
+            "x-xss-protection" => Some(format!("{name}: obsolete, not sent by luciuz")),
+            _ => Some(format!("{name}: custom response headers are not supported")),
+        }
+    }
+
+    /// Settle what the source leaves open: one listener per kind, and
+    /// ACME when the site has certificates.
+    pub(crate) fn settle(&mut self, notes: &mut Vec<Note>, options: &Options) {
+        self.http_listen = self.pick(false, notes);
+        self.https_listen = self.pick(true, notes);
+
+        let tls = self.https_listen.is_some() || self.certificates;
+        if tls && self.domains.is_empty() {
+            note(
+                notes,
+                0,
+                "HTTPS not translated: no server name to request a certificate for",
+            );
+            self.https_listen = None;
+        } else if tls {
+            if self.https_listen.is_none() {
+                self.https_listen = Some("0.0.0.0:443".to_string());
+            }
+            self.email = match self.acme_email.clone().or(options.acme_email.clone()) {
+                Some(email) => email,
+                None => {
+                    let email = format!("hostmaster@{}", self.canonical());
+                    note(
+                        notes,
+                        0,
+                        format!(
+                            "acme.email: set to {email}; use the contact address for \
+                             Let's Encrypt (or pass --acme-email)"
+                        ),
+                    );
+                    email
+                }
+            };
+            note(
+                notes,
+                0,
+                "acme.prod = false: certificates come from Let's Encrypt staging until \
+                 you set acme.prod = true",
+            );
+        }
+        if let (Some((size, line)), true) = (&self.max_body, self.routes.is_empty()) {
+            note(
+                notes,
+                *line,
+                format!("body size {size}: only proxied requests carry bodies to limit"),
+            );
+        }
+        if self.https_listen.is_none() && self.http_listen.is_none() {
+            note(notes, 0, "no listener left: http_listen set to 0.0.0.0:80");
+            self.http_listen = Some("0.0.0.0:80".to_string());
+        }
+    }
+
+    /// The first IPv4 listener of a kind, else the first one.
+    fn pick(&self, tls: bool, notes: &mut Vec<Note>) -> Option<String> {
+        let mut of_kind = self.listens.iter().filter(|l| l.tls == tls);
+        let chosen = of_kind
+            .clone()
+            .find(|l| !l.addr.starts_with('['))
+            .or_else(|| of_kind.next())?;
+        for l in self.listens.iter().filter(|l| l.tls == tls) {
+            if l.addr != chosen.addr {
+                note(
+                    notes,
+                    l.line,
+                    format!(
+                        "listen {}: luciuz takes one address per listener, {} is used",
+                        l.addr, chosen.addr
+                    ),
+                );
+            }
+        }
+        Some(chosen.addr.clone())
+    }
+
+    fn canonical(&self) -> &str {
+        self.canonical_host
+            .as_deref()
+            .or(self.domains.first().map(String::as_str))
+            .unwrap_or("localhost")
+    }
+
+    /// `luciuz.toml` for the settled site.
+    pub(crate) fn render(&self) -> String {
+        let mut doc = DocumentMut::new();
+        doc["version"] = value(i64::from(CONFIG_VERSION));
+
+        let mut server = Table::new();
+        server["http_listen"] = value(self.http_listen.clone().unwrap_or_default());
+        if let Some(addr) = &self.https_listen {
+            server["https_listen"] = value(addr);
+        }
+        let profile = match (self.root.is_some(), self.routes.is_empty()) {
+            (true, false) => "site",
+            (true, true) => "static_site",
+            (false, _) => "public_api",
+        };
+        server["profile"] = value(profile);
+        if let Some(host) = &self.canonical_host {
+            server["canonical_host"] = value(host);
+        }
+        if let Some(h) = &self.hsts {
+            server["hsts"] = value(true);
+            server["hsts_max_age"] = value(format_duration(Duration::from_secs(h.max_age)));
+            server["hsts_include_subdomains"] = value(h.include_subdomains);
+            server["hsts_preload"] = value(h.preload);
+        }
+        push(&mut doc, "server", server);
+
+        if self.https_listen.is_some() {
+            let mut acme = Table::new();
+            acme["enabled"] = value(true);
+            acme["prod"] = value(false);
+            acme["email"] = value(&self.email);
+            acme["domains"] = value(self.domains.iter().collect::<Array>());
+            let challenge = if self.http_listen.is_some() {
+                "http-01"
+            } else {
+                "tls-alpn-01"
+            };
+            acme["challenge"] = value(challenge);
+            push(&mut doc, "acme", acme);
+        }
+
+        if let Some(root) = &self.root {
+            let mut s = Table::new();
+            s["root"] = value(root);
+            if let Some(index) = &self.index {
+                s["index"] = value(index);
+            }
+            if !self.try_files.is_empty() {
+                s["try_files"] = value(self.try_files.iter().collect::<Array>());
+            }
+            if let Some(page) = &self.not_found {
+                s["not_found"] = value(page);
+            }
+            if let Some(cc) = &self.cache_control {
+                s["cache_control"] = value(cc);
+            }
+            if !self.cache_rules.is_empty() {
+                let mut rules = ArrayOfTables::new();
+                for (pattern, cc) in &self.cache_rules {
+                    let mut t = Table::new();
+                    t["pattern"] = value(pattern);
+                    t["cache_control"] = value(cc);
+                    t.decor_mut().set_prefix("\n");
+                    rules.push(t);
+                }
+                s["cache_rules"] = Item::ArrayOfTables(rules);
+            }
+            push(&mut doc, "static_site", s);
+        }
+
+        if !self.routes.is_empty() {
+            let mut proxy = Table::new();
+            proxy.set_implicit(true);
+            if let Some((size, _)) = &self.max_body {
+                proxy["max_body_bytes"] = value(size);
+            }
+            if !self.pools.is_empty() {
+                let mut pools = Table::new();
+                pools.set_implicit(true);
+                for p in &self.pools {
+                    let mut t = Table::new();
+                    t["targets"] = value(p.targets.iter().collect::<Array>());
+                    t.decor_mut().set_prefix("\n");
+                    pools[&p.name] = Item::Table(t);
+                }
+                proxy["pools"] = Item::Table(pools);
+            }
+            let mut routes = ArrayOfTables::new();
+            for r in &self.routes {
+                let mut t = Table::new();
+                t["prefix"] = value(&r.prefix);
+                match &r.target {
+                    Target::Upstream(url) => t["upstream"] = value(url),
+                    Target::Pool(name) => t["pool"] = value(name),
+                }
+                t["strip_prefix"] = value(r.strip_prefix);
+                t["preserve_host"] = value(r.preserve_host);
+                t.decor_mut().set_prefix("\n");
+                routes.push(t);
+            }
+            proxy["routes"] = Item::ArrayOfTables(routes);
+            if self.max_body.is_some() {
+                proxy.set_implicit(false);
+                proxy.decor_mut().set_prefix("\n");
+            }
+            doc["proxy"] = Item::Table(proxy);
+        }
+
+        if !self.compression.is_empty() {
+            let mut c = Table::new();
+            c["algorithms"] = value(self.compression.iter().copied().collect::<Array>());
+            push(&mut doc, "compression", c);
+        }
+
+        doc.to_string()
+    }
+}
+
+/// Append table `t` as `[key]`, after a blank line.
+fn push(doc: &mut DocumentMut, key: &str, mut t: Table) {
+    t.decor_mut().set_prefix("\n");
+    doc[key] = Item::Table(t);
+}
@@ -0,0 +1,131 @@
+//! Real-world nginx configs and Caddyfiles in `tests/fixtures`, each with
+//! its expected translation next to it (`<fixture>.toml`). After an intended
+//! change, rewrite those with `LUCIUZ_BLESS=1 cargo test -p luciuz-import`.
+
+use std::path::Path;
+
+use luciuz_import::{Import, Options};
+
+fn import(name: &str) -> Import {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let text = std::fs::read_to_string(&path).unwrap();
+    let options = Options::default();
+    let result = if name.ends_with("Caddyfile") {
+        luciuz_import::caddy(name, &text, &options)
+    } else {
+        luciuz_import::nginx(name, &text, &options)
+    };
+    let import = result.unwrap_or_else(|e| panic!("{name}: {e}"));
+
+    let (_, report) = luciuz_config::check_str("luciuz.toml", &import.config);
+    assert!(!report.has_errors(), "{name}:\n{}", report.render());
+
+    let expected = path.with_file_name(format!("{name}.toml"));
+    if std::env::var_os("LUCIUZ_BLESS").is_some() {
+        std::fs::write(&expected, &import.config).unwrap();
+    } else {
+        assert!(
+            std::fs::read_to_string(&expected).is_ok_and(|e| e == import.config),
+            "the translation of {name} changed; if intended, rerun with LUCIUZ_BLESS=1 \
+             and review the diff of {}",
+            expected.display()
+        );
+    }
+    import
+}
+
+fn notes(import: &Import) -> Vec<String> {
+    import
+        .notes
+        .iter()
+        .map(|n| format!("{}: {}", n.line, n.message))
+        .collect()
+}
+
+#[test]
+fn certbot_managed_nginx_site() {
+    let import = import("certbot.nginx.conf");
+    // The HTTP server's redirects to HTTPS are what luciuz does with ACME.
+    assert!(!notes(&import).iter().any(|n| n.contains("return")));
+    assert!(import
+        .config
+        .contains("domains = [\"example.com\", \"www.example.com\"]"));
+}
+
+#[test]
+fn nginx_conf_with_an_upstream_pool() {
+    let import = import("shop.nginx.conf");
+    assert!(import.config.contains("pool = \"app_servers\""));
+    assert!(notes(&import).contains(
+        &"53: X-Real-IP: luciuz passes the client address in X-Forwarded-For".to_string()
+    ));
+}
+
+#[test]
+fn nginx_single_page_app() {
+    let import = import("spa.nginx.conf");
+    assert!(!import.config.contains("[acme]"));
+    assert!(import
+        .config
+        .contains("pattern = \"*.{js,css,woff2,svg,png}\""));
+}
+
+#[test]
+fn caddyfile_with_www_redirect() {
+    let import = import("site.Caddyfile");
+    assert!(import.config.contains("canonical_host = \"example.net\""));
+    assert!(import.config.contains("strip_prefix = true"));
+}
+
+#[test]
+fn caddyfile_without_site_braces() {
+    let import = import("app.Caddyfile");
+    assert!(import.config.contains("email = \"ops@example.io\""));
+    assert!(!notes(&import).iter().any(|n| n.contains("acme.email")));
+}
+
+#[test]
+fn acme_email_option_fills_in_a_missing_address() {
+    let text = "server {\n    listen 443 ssl;\n    server_name example.com;\n    root /srv;\n}\n";
+    let options = Options {
+        acme_email: Some("admin@example.com".to_string()),
+    };
+    let import = luciuz_import::nginx("site.conf", text, &options).unwrap();
+    assert!(import.config.contains("email = \"admin@example.com\""));
+}
+
+#[test]
+fn syntax_errors_name_their_line() {
+    let err = luciuz_import::nginx(
+        "bad.conf",
+        "server {\n    root /srv\n}\n",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "configuration error: bad.conf:2: \"root\" is not terminated by \";\""
+    );
+
+    let err = luciuz_import::caddy(
+        "Caddyfile",
+        "example.com {\n    root * /srv\n",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "configuration error: Caddyfile:1: \"{\" is not closed"
+    );
+}
+
+#[test]
+fn a_config_serving_nothing_is_refused() {
+    let text = "server {\n    listen 80;\n    return 301 https://example.com$request_uri;\n}\n";
+    let err = luciuz_import::nginx("redirect.conf", text, &Options::default()).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("redirect.conf: no server block serves"));
+}
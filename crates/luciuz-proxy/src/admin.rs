@@ -0,0 +1,299 @@
+//! `server.profile = "admin_panel"`: only clients from `admin.allow` get in,
+//! and only once authenticated (Basic users or forward auth; client
+//! certificates are checked by the TLS listener). Responses carry strict
+//! headers; requests are rate limited per client and their bodies capped.
+//! Every refusal is logged to the `luciuz::audit` target.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use luciuz_config::{any_contains, Cidr, Config};
+use tracing::{info, warn};
+
+use crate::basic_auth::Users;
+use crate::{ClientInfo, RequestId};
+
+static X_ROBOTS_TAG: HeaderName = HeaderName::from_static("x-robots-tag");
+
+/// Headers sent to the forward auth service on top of `X-Forwarded-*`.
+const FORWARD_AUTH_PASS: [HeaderName; 2] = [header::AUTHORIZATION, header::COOKIE];
+
+/// Headers of a refusal from the forward auth service passed to the client.
+const FORWARD_AUTH_RETURN: [HeaderName; 4] = [
+    header::CONTENT_TYPE,
+    header::LOCATION,
+    header::SET_COOKIE,
+    header::WWW_AUTHENTICATE,
+];
+
+/// Client addresses with a rate limit bucket; past it, idle ones are dropped.
+const MAX_CLIENTS: usize = 10_000;
+
+/// Compiled `[admin]`, shared by the requests of one config generation.
+#[derive(Clone)]
+pub struct AdminState(Arc<Admin>);
+
+struct Admin {
+    allow: Vec<Cidr>,
+    users: Users,
+    forward_auth: Option<ForwardAuth>,
+    limiter: RateLimiter,
+    max_body_bytes: usize,
+}
+
+struct ForwardAuth {
+    client: reqwest::Client,
+    url: String,
+    copy_headers: Vec<HeaderName>,
+}
+
+impl AdminState {
+    pub fn new(cfg: &Config) -> anyhow::Result<Self> {
+        let admin = cfg
+            .admin
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("missing [admin] config"))?;
+        let forward_auth = match &admin.forward_auth {
+            Some(fa) => Some(ForwardAuth {
+                // The service's redirects (to a login page) go to the client.
+                client: reqwest::Client::builder()
+                    .timeout(fa.timeout)
+                    .redirect(reqwest::redirect::Policy::none())
+                    .build()?,
+                url: fa.url.clone(),
+                copy_headers: fa
+                    .copy_headers
+                    .iter()
+                    .map(|h| HeaderName::try_from(h.as_str()))
+                    .collect::<Result<_, _>>()?,
+            }),
+            None => None,
+        };
+        Ok(Self(Arc::new(Admin {
+            allow: admin.allow.clone(),
+            users: Users::new("admin.users", &admin.users)?,
+            forward_auth,
+            limiter: RateLimiter::new(admin.rate_limit),
+            max_body_bytes: admin.max_body_bytes,
+        })))
+    }
+}
+
+pub async fn admin_mw(State(state): State<AdminState>, req: Request<Body>, next: Next) -> Response {
+    let mut res = guarded(&state.0, req, next).await;
+    let h = res.headers_mut();
+    h.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static("default-src 'self'"),
+    );
+    h.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    h.insert(X_ROBOTS_TAG.clone(), HeaderValue::from_static("noindex"));
+    res
+}
+
+/// Refuse `req` or pass it on, in order: network, rate, size, credentials.
+async fn guarded(admin: &Admin, mut req: Request<Body>, next: Next) -> Response {
+    let Some(client) = req.extensions().get::<ClientInfo>().cloned() else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|r| r.0.clone())
+        .unwrap_or_default();
+    let deny = |reason: &str, user: &str, status: StatusCode| {
+        info!(
+            target: "luciuz::audit",
+            user,
+            reason,
+            method = %method,
+            path = %path,
+            status = status.as_u16(),
+            client_ip = %client.ip,
+            request_id = request_id.as_str(),
+            "admin access denied"
+        );
+    };
+
+    if !any_contains(&admin.allow, client.ip) {
+        deny("network", "", StatusCode::FORBIDDEN);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    if let Err(wait) = admin.limiter.take(client.ip) {
+        deny("rate_limit", "", StatusCode::TOO_MANY_REQUESTS);
+        let secs = wait.as_secs().max(1).to_string();
+        return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, secs)]).into_response();
+    }
+
+    let declared = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    match declared {
+        Some(n) if n > admin.max_body_bytes as u64 => {
+            deny("body_size", "", StatusCode::PAYLOAD_TOO_LARGE);
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        }
+        Some(_) => {}
+        // A body of unknown length fails once past the cap.
+        None => {
+            let cap = admin.max_body_bytes;
+            let (parts, body) = req.into_parts();
+            let mut seen = 0;
+            let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+                let chunk = chunk?;
+                seen += chunk.len();
+                if seen > cap {
+                    return Err(axum::Error::new("request body over admin.max_body_bytes"));
+                }
+                Ok(chunk)
+            }));
+            req = Request::from_parts(parts, body);
+        }
+    }
+
+    if !admin.users.is_empty() {
+        if let Err(tried) = admin.users.authenticate(req.headers()).await {
+            deny(
+                "credentials",
+                tried.as_deref().unwrap_or(""),
+                StatusCode::UNAUTHORIZED,
+            );
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Basic realm=\"luciuz admin\""),
+                )],
+            )
+                .into_response();
+        }
+    }
+
+    if let Some(fa) = &admin.forward_auth {
+        match fa
+            .check(req.method(), req.uri(), req.headers(), &client)
+            .await
+        {
+            Ok(granted) => {
+                let headers = req.headers_mut();
+                for name in &fa.copy_headers {
+                    headers.remove(name);
+                    for value in granted.get_all(name) {
+                        headers.append(name.clone(), value.clone());
+                    }
+                }
+            }
+            Err(res) => {
+                deny("forward_auth", "", res.status());
+                return res;
+            }
+        }
+    }
+
+    next.run(req).await
+}
+
+impl ForwardAuth {
+    /// The service's response headers if it lets the request through, else
+    /// the response to send to the client.
+    async fn check(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        client: &ClientInfo,
+    ) -> Result<HeaderMap, Response> {
+        let uri = uri.path_and_query().map_or(uri.path(), |pq| pq.as_str());
+        let mut out = self
+            .client
+            .get(&self.url)
+            .header("x-forwarded-method", method.as_str())
+            .header("x-forwarded-proto", client.proto.as_str())
+            .header("x-forwarded-uri", uri)
+            .header("x-forwarded-for", client.ip.to_string());
+        if let Some(host) = &client.host {
+            out = out.header("x-forwarded-host", host.as_str());
+        }
+        for name in &FORWARD_AUTH_PASS {
+            for value in headers.get_all(name) {
+                out = out.header(name, value);
+            }
+        }
+
+        let res = match out.send().await {
+            Ok(res) => res,
+            Err(err) => {
+                warn!(error = %err, url = %self.url, "forward auth request failed");
+                return Err(StatusCode::BAD_GATEWAY.into_response());
+            }
+        };
+        if res.status().is_success() {
+            return Ok(res.headers().clone());
+        }
+        let mut refusal = Response::builder().status(res.status());
+        for name in &FORWARD_AUTH_RETURN {
+            for value in res.headers().get_all(name) {
+                refusal = refusal.header(name, value);
+            }
+        }
+        let body = res.bytes().await.unwrap_or_default();
+        Err(refusal
+            .body(Body::from(body))
+            .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response()))
+    }
+}
+
+/// A token bucket per client address, holding `per_minute` requests and
+/// refilled evenly over the minute.
+struct RateLimiter {
+    per_minute: f64,
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl RateLimiter {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute: f64::from(per_minute),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Spend one request of `ip`'s bucket, or tell how long until there is
+    /// one.
+    fn take(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let per_sec = self.per_minute / 60.0;
+        let refilled = |tokens: f64, at: Instant| {
+            (tokens + now.duration_since(at).as_secs_f64() * per_sec).min(self.per_minute)
+        };
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(&ip) {
+            // A full bucket is the same as none.
+            buckets.retain(|_, (tokens, at)| refilled(*tokens, *at) < self.per_minute);
+        }
+        let (tokens, at) = buckets.entry(ip).or_insert((self.per_minute, now));
+        *tokens = refilled(*tokens, *at);
+        *at = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - *tokens) / per_sec))
+        }
+    }
+}
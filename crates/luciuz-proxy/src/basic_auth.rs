@@ -0,0 +1,112 @@
+//! HTTP Basic authentication against configured accounts (name and Argon2id
+//! hash of the password), for uploads and the admin panel.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use argon2::password_hash::{PasswordHasher, SaltString};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::http::{header, HeaderMap};
+use base64::Engine;
+use ring::{hmac, rand};
+
+/// Verified in place of an unknown user's hash, so that a refusal takes as
+/// long whether or not the name exists (`Argon2::default()` parameters).
+const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$rJzE1SO6nIzXDiW5JPbaMw$2iuMUmYFra/m9qtwZkQU+Z5fNpsigTdyUYNCymlzgj8";
+
+#[derive(Debug)]
+pub(crate) struct Users {
+    /// (name, PHC string of the password hash).
+    users: Vec<(String, String)>,
+    /// Passwords already verified, by user, as an HMAC under `key`: Argon2 is
+    /// deliberately slow, and Basic credentials come with every request.
+    verified: Mutex<HashMap<String, Vec<u8>>>,
+    key: hmac::Key,
+}
+
+impl Users {
+    /// `at` names the config key, for errors.
+    pub(crate) fn new(at: &str, users: &[luciuz_config::UploadUser]) -> anyhow::Result<Self> {
+        let users = users
+            .iter()
+            .map(|u| {
+                PasswordHash::new(&u.password_hash)
+                    .map_err(|e| anyhow::anyhow!("{at}: bad hash for {}: {e}", u.name))?;
+                Ok((u.name.clone(), u.password_hash.clone()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let key = hmac::Key::generate(hmac::HMAC_SHA256, &rand::SystemRandom::new())
+            .map_err(|_| anyhow::anyhow!("{at}: failed to generate a key"))?;
+        Ok(Self {
+            users,
+            verified: Mutex::default(),
+            key,
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    /// The user named by valid Basic credentials, or else the name that was
+    /// tried (if any), for the audit log.
+    pub(crate) async fn authenticate(&self, headers: &HeaderMap) -> Result<String, Option<String>> {
+        let credentials = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Basic "))
+            .and_then(|b| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(b.trim())
+                    .ok()
+            })
+            .and_then(|b| String::from_utf8(b).ok())
+            .ok_or(None)?;
+        let (name, password) = credentials.split_once(':').ok_or(None)?;
+        let refused = || Err(Some(name.to_string()));
+        let user = self.users.iter().find(|(n, _)| n == name);
+
+        if user.is_some() {
+            let known = self.verified.lock().unwrap().get(name).cloned();
+            if known.is_some_and(|tag| hmac::verify(&self.key, password.as_bytes(), &tag).is_ok())
+            {
+                return Ok(name.to_string());
+            }
+        }
+        let hash = user.map_or(DUMMY_HASH, |(_, hash)| hash.as_str()).to_string();
+        let attempt = password.to_string();
+        let valid = tokio::task::spawn_blocking(move || {
+            let hash = PasswordHash::new(&hash).ok()?;
+            Argon2::default()
+                .verify_password(attempt.as_bytes(), &hash)
+                .ok()
+        })
+        .await
+        .ok()
+        .flatten()
+        .is_some();
+        if !valid || user.is_none() {
+            return refused();
+        }
+        let tag = hmac::sign(&self.key, password.as_bytes());
+        self.verified
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), tag.as_ref().to_vec());
+        Ok(name.to_string())
+    }
+}
+
+/// Argon2id hash of `password` with a random salt, as a PHC string
+/// (`$argon2id$v=19$m=19456,t=2,p=1$...`), for `users` entries.
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let mut salt = [0u8; 16];
+    rand::SecureRandom::fill(&rand::SystemRandom::new(), &mut salt)
+        .map_err(|_| anyhow::anyhow!("failed to generate a salt"))?;
+    let salt = SaltString::encode_b64(&salt).map_err(|e| anyhow::anyhow!("{e}"))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow::anyhow!("failed to hash the password: {e}"))
+}
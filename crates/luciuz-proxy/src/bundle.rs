@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use luciuz_config::Algorithm;
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use tracing::{info, warn};
+
+use crate::file_cache::{preferred_codings, CachedFile};
+use crate::static_site::{variants, PATH};
+
+/// How often the archive file is checked for a new version.
+const RELOAD_POLL: Duration = Duration::from_secs(1);
+
+/// A static site served from a tar or zip archive held in memory. Replacing
+/// the archive file (e.g. by an atomic rename) switches to the new version.
+#[derive(Debug)]
+pub(crate) struct Bundle {
+    path: PathBuf,
+    /// (coding, file suffix) of enabled precompressed variants, in config order.
+    variants: Vec<(&'static str, &'static str)>,
+    current: RwLock<Arc<Snapshot>>,
+}
+
+/// One loaded version of the archive.
+#[derive(Debug)]
+struct Snapshot {
+    stamp: Stamp,
+    /// Regular files by normalized path (`dir/file.js`, no leading `/`).
+    files: HashMap<String, Arc<CachedFile>>,
+}
+
+/// What identifies a version of the archive file on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    mtime: Option<SystemTime>,
+    inode: u64,
+}
+
+impl Stamp {
+    fn of(meta: &Metadata) -> Self {
+        Self {
+            len: meta.len(),
+            mtime: meta.modified().ok(),
+            inode: inode(meta),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn inode(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_: &Metadata) -> u64 {
+    0
+}
+
+impl Bundle {
+    /// Load `path` and start watching it for new versions.
+    pub(crate) fn open(path: &str, precompressed: &[Algorithm]) -> anyhow::Result<Arc<Self>> {
+        let path = PathBuf::from(path);
+        let snapshot =
+            load(&path).with_context(|| format!("static_site.root: {}", path.display()))?;
+        info!(path = %path.display(), files = snapshot.files.len(), "static bundle loaded");
+        let bundle = Arc::new(Self {
+            path,
+            variants: variants(precompressed),
+            current: RwLock::new(Arc::new(snapshot)),
+        });
+        tokio::spawn(watch(Arc::downgrade(&bundle)));
+        Ok(bundle)
+    }
+
+    fn snapshot(&self) -> Arc<Snapshot> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Whether URL `path` names a file in the archive.
+    pub(crate) fn is_file(&self, path: &str) -> bool {
+        key(path).is_some_and(|k| self.snapshot().files.contains_key(&k))
+    }
+
+    /// Answer a GET/HEAD for URL `path` with `status` (e.g. 404 for the
+    /// `not_found` page), or `None` if the archive has no such file.
+    pub(crate) fn respond(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        status: StatusCode,
+    ) -> Option<Response> {
+        let key = key(path)?;
+        let snapshot = self.snapshot();
+        let file = snapshot.files.get(&key)?;
+        // An error page is not a cached copy of anything: no 304 for it.
+        let mut headers = headers.clone();
+        if status != StatusCode::OK {
+            headers.remove(header::IF_NONE_MATCH);
+            headers.remove(header::IF_MODIFIED_SINCE);
+        }
+        let headers = &headers;
+        for coding in preferred_codings(headers, &self.variants) {
+            let suffix = self
+                .variants
+                .iter()
+                .find(|(c, _)| *c == coding)
+                .map_or("", |(_, s)| *s);
+            if let Some(variant) = snapshot.files.get(&format!("{key}{suffix}")) {
+                return Some(with_status(variant.response(headers, Some(coding)), status));
+            }
+        }
+        Some(with_status(file.response(headers, None), status))
+    }
+}
+
+fn with_status(mut res: Response, status: StatusCode) -> Response {
+    if res.status() == StatusCode::OK {
+        *res.status_mut() = status;
+    }
+    res
+}
+
+/// Normalized archive path for URL `path`, with `index.html` for a
+/// directory; `None` if it could escape the root.
+fn key(path: &str) -> Option<String> {
+    let mut key = normalize(path)?;
+    if path.ends_with('/') || key.is_empty() {
+        if !key.is_empty() {
+            key.push('/');
+        }
+        key.push_str("index.html");
+    }
+    Some(key)
+}
+
+/// `a/./b//c` → `a/b/c`; refuses `..` and backslashes.
+fn normalize(path: &str) -> Option<String> {
+    let mut out: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            s if s.contains('\\') => return None,
+            s => out.push(s),
+        }
+    }
+    Some(out.join("/"))
+}
+
+fn load(path: &Path) -> anyhow::Result<Snapshot> {
+    let file = File::open(path)?;
+    let meta = file.metadata()?;
+    let stamp = Stamp::of(&meta);
+    let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    let name = path.to_string_lossy().to_ascii_lowercase();
+    let files = if name.ends_with(".zip") {
+        load_zip(file, mtime)?
+    } else {
+        let reader = BufReader::new(file);
+        if name.ends_with(".zst") || name.ends_with(".tzst") {
+            load_tar(zstd::Decoder::new(reader)?)?
+        } else if name.ends_with(".gz") || name.ends_with(".tgz") {
+            load_tar(flate2::read::GzDecoder::new(reader))?
+        } else {
+            load_tar(reader)?
+        }
+    };
+    Ok(Snapshot { stamp, files })
+}
+
+/// Zip entries carry a CRC-32, used as the ETag. Their DOS timestamps have
+/// no time zone, so Last-Modified is the archive's own mtime.
+fn load_zip(file: File, mtime: SystemTime) -> anyhow::Result<HashMap<String, Arc<CachedFile>>> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+    let mut files = HashMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.is_file() || entry.is_symlink() {
+            continue;
+        }
+        let Some(key) = normalize(entry.name()) else {
+            continue;
+        };
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)?;
+        let tag = format!("{:08x}-{:x}", entry.crc32(), data.len());
+        let file = CachedFile::with_etag(Bytes::from(data), mtime, Path::new(&key), &tag);
+        files.insert(key, Arc::new(file));
+    }
+    Ok(files)
+}
+
+/// Tar headers only checksum themselves, so the ETag is a CRC-32 of the
+/// content, computed here.
+fn load_tar(reader: impl Read) -> anyhow::Result<HashMap<String, Arc<CachedFile>>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = HashMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(key) = entry.path()?.to_str().and_then(normalize) else {
+            continue;
+        };
+        let mtime = entry.header().mtime().map_or(SystemTime::UNIX_EPOCH, |s| {
+            SystemTime::UNIX_EPOCH + Duration::from_secs(s)
+        });
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)?;
+        let tag = format!("{:08x}-{:x}", crc32fast::hash(&data), data.len());
+        let file = CachedFile::with_etag(Bytes::from(data), mtime, Path::new(&key), &tag);
+        files.insert(key, Arc::new(file));
+    }
+    Ok(files)
+}
+
+/// Reload the archive when the file changes. A version that fails to load
+/// is logged and skipped; the previous one keeps being served.
+async fn watch(bundle: Weak<Bundle>) {
+    let mut seen = match bundle.upgrade() {
+        Some(b) => b.snapshot().stamp,
+        None => return,
+    };
+    loop {
+        tokio::time::sleep(RELOAD_POLL).await;
+        let Some(bundle) = bundle.upgrade() else {
+            return;
+        };
+        let Ok(meta) = tokio::fs::metadata(&bundle.path).await else {
+            continue;
+        };
+        let stamp = Stamp::of(&meta);
+        if stamp == seen {
+            continue;
+        }
+        seen = stamp;
+        let path = bundle.path.clone();
+        match tokio::task::spawn_blocking(move || load(&path)).await {
+            Ok(Ok(snapshot)) => {
+                info!(
+                    path = %bundle.path.display(),
+                    files = snapshot.files.len(),
+                    "static bundle reloaded"
+                );
+                *bundle.current.write().unwrap() = Arc::new(snapshot);
+            }
+            Ok(Err(err)) => {
+                warn!(path = %bundle.path.display(), error = %err, "static bundle reload failed, keeping the previous version");
+            }
+            Err(err) => {
+                warn!(path = %bundle.path.display(), error = %err, "static bundle reload panicked");
+            }
+        }
+    }
+}
+
+/// Serve files from the archive, like the directory file service: GET/HEAD
+/// only, `index.html` for directories, and a redirect to add the trailing
+/// `/` to a directory path.
+pub(crate) async fn bundle_handler(
+    State(bundle): State<Arc<Bundle>>,
+    req: Request<Body>,
+) -> Response {
+    if !(req.method() == Method::GET || req.method() == Method::HEAD) {
+        return (
+            StatusCode::METHOD_NOT_ALLOWED,
+            [(header::ALLOW, HeaderValue::from_static("GET,HEAD"))],
+        )
+            .into_response();
+    }
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    if let Some(res) = bundle.respond(&path, req.headers(), StatusCode::OK) {
+        return res;
+    }
+    if !path.ends_with('/') && bundle.is_file(&format!("{path}/")) {
+        let mut location = utf8_percent_encode(&format!("{path}/"), PATH).to_string();
+        if let Some(q) = req.uri().query() {
+            location.push('?');
+            location.push_str(q);
+        }
+        if let Ok(location) = HeaderValue::from_str(&location) {
+            return (
+                StatusCode::TEMPORARY_REDIRECT,
+                [(header::LOCATION, location)],
+            )
+                .into_response();
+        }
+    }
+    StatusCode::NOT_FOUND.into_response()
+}
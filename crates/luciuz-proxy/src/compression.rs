@@ -0,0 +1,279 @@
+use std::io;
+use std::sync::Arc;
+
+use async_compression::tokio::write::{BrotliEncoder, GzipEncoder, ZstdEncoder};
+use async_compression::Level;
+use axum::{
+    body::{Body, HttpBody},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use luciuz_config::{Algorithm, Config};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Response extension: never compress this response (BREACH-sensitive route).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NoCompression;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coding {
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl Coding {
+    fn token(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Brotli => "br",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Settings {
+    /// Enabled codings with their level, in server preference order.
+    codings: Vec<(Coding, i32)>,
+    mime_types: Vec<String>,
+    min_size: u64,
+}
+
+/// State for [`compression_mw`], built from `[compression]`.
+#[derive(Debug, Clone)]
+pub struct CompressionState {
+    settings: Arc<Settings>,
+}
+
+impl CompressionState {
+    /// `None` when `[compression]` is not configured.
+    pub fn new(cfg: &Config) -> Option<Self> {
+        let c = cfg.compression.as_ref()?;
+        let codings = c
+            .algorithms
+            .iter()
+            .map(|a| match a {
+                Algorithm::Gzip => (Coding::Gzip, c.gzip_level),
+                Algorithm::Brotli => (Coding::Brotli, c.brotli_level),
+                Algorithm::Zstd => (Coding::Zstd, c.zstd_level),
+            })
+            .map(|(coding, level)| (coding, level as i32))
+            .collect();
+        Some(Self {
+            settings: Arc::new(Settings {
+                codings,
+                mime_types: c
+                    .mime_types
+                    .iter()
+                    .map(|m| m.to_ascii_lowercase())
+                    .collect(),
+                min_size: c.min_size,
+            }),
+        })
+    }
+}
+
+/// Compress responses with the best coding the client accepts (gzip, br, zstd).
+///
+/// Bodies are compressed as they stream, one frame at a time. Responses that are already encoded,
+/// partial, too small, of another content type, or marked `no-transform` are
+/// left alone, as are responses to `Range` and `HEAD` requests and those of
+/// BREACH-sensitive proxy routes. Compressible responses get
+/// `Vary: Accept-Encoding` whether or not they were compressed.
+pub async fn compression_mw(
+    State(state): State<CompressionState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let skip_request = req.headers().contains_key(header::RANGE) || req.method() == Method::HEAD;
+
+    let mut res = next.run(req).await;
+    let s = &state.settings;
+    if !compressible(s, &res) {
+        return res;
+    }
+    add_vary(res.headers_mut());
+    if skip_request {
+        return res;
+    }
+    match negotiate(&s.codings, accept.as_deref().unwrap_or("")) {
+        Some((coding, level)) => compress(res, coding, level),
+        None => res,
+    }
+}
+
+fn compressible(s: &Settings, res: &Response) -> bool {
+    let h = res.headers();
+    let status = res.status();
+    if status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || status == StatusCode::PARTIAL_CONTENT
+        || status.is_informational()
+        || res.extensions().get::<NoCompression>().is_some()
+        || h.contains_key(header::CONTENT_ENCODING)
+        || h.contains_key(header::CONTENT_RANGE)
+    {
+        return false;
+    }
+    if h.get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.to_ascii_lowercase().contains("no-transform"))
+    {
+        return false;
+    }
+
+    let size = h
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .or_else(|| res.body().size_hint().exact());
+    if size.is_some_and(|n| n < s.min_size) {
+        return false;
+    }
+
+    let Some(ct) = h.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let essence = ct
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    s.mime_types.iter().any(|m| match m.strip_suffix("/*") {
+        Some(t) => essence.split('/').next() == Some(t),
+        None => *m == essence,
+    })
+}
+
+pub(crate) fn add_vary(h: &mut HeaderMap) {
+    let present = h
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            let v = v.trim();
+            v == "*" || v.eq_ignore_ascii_case("accept-encoding")
+        });
+    if !present {
+        h.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+}
+
+/// q-value `accept` (an `Accept-Encoding` value) gives `token`, falling back
+/// to `*`; 0 if neither is listed.
+pub(crate) fn accept_q(accept: &str, token: &str) -> f32 {
+    let q_of = |token: &str| -> Option<f32> {
+        accept.split(',').find_map(|item| {
+            let mut params = item.split(';');
+            let name = params.next()?.trim();
+            if !name.eq_ignore_ascii_case(token) {
+                return None;
+            }
+            Some(
+                params
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0),
+            )
+        })
+    };
+    q_of(token).or_else(|| q_of("*")).unwrap_or(0.0)
+}
+
+/// Highest-q coding the client accepts; ties go to the server order.
+fn negotiate(codings: &[(Coding, i32)], accept: &str) -> Option<(Coding, i32)> {
+    let mut best: Option<((Coding, i32), f32)> = None;
+    for &(coding, level) in codings {
+        let q = accept_q(accept, coding.token());
+        if q > 0.0 && best.is_none_or(|(_, bq)| q > bq) {
+            best = Some(((coding, level), q));
+        }
+    }
+    best.map(|(c, _)| c)
+}
+
+fn compress(res: Response, coding: Coding, level: i32) -> Response {
+    let (mut parts, body) = res.into_parts();
+    let h = &mut parts.headers;
+    h.remove(header::CONTENT_LENGTH);
+    h.remove(header::ACCEPT_RANGES);
+    h.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(coding.token()),
+    );
+    // The encoded bytes differ from the identity representation: a strong
+    // validator would be wrong for them.
+    if let Some(etag) = h.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+        if etag.starts_with('"') {
+            if let Ok(weak) = HeaderValue::from_str(&format!("W/{etag}")) {
+                h.insert(header::ETAG, weak);
+            }
+        }
+    }
+
+    let level = Level::Precise(level);
+    let body = match coding {
+        Coding::Gzip => encode(body, GzipEncoder::with_quality(Vec::new(), level)),
+        Coding::Brotli => encode(body, BrotliEncoder::with_quality(Vec::new(), level)),
+        Coding::Zstd => encode(body, ZstdEncoder::with_quality(Vec::new(), level)),
+    };
+    Response::from_parts(parts, body)
+}
+
+/// An encoder writing into a buffer we can drain.
+trait Encoder: AsyncWrite + Unpin + Send + 'static {
+    fn buffer(&mut self) -> &mut Vec<u8>;
+}
+
+macro_rules! encoder {
+    ($($t:ident),*) => {$(
+        impl Encoder for $t<Vec<u8>> {
+            fn buffer(&mut self) -> &mut Vec<u8> {
+                self.get_mut()
+            }
+        }
+    )*};
+}
+encoder!(GzipEncoder, BrotliEncoder, ZstdEncoder);
+
+/// Compress `body` frame by frame: the encoder is flushed after each one, so
+/// that streamed responses (server-sent events, long polls) reach the client
+/// as they are produced rather than once a buffer fills.
+fn encode(body: Body, encoder: impl Encoder) -> Body {
+    let frames = body.into_data_stream().map_err(io::Error::other);
+    Body::from_stream(futures_util::stream::try_unfold(
+        (frames, Some(encoder)),
+        |(mut frames, encoder)| async move {
+            let Some(mut encoder) = encoder else {
+                return Ok(None);
+            };
+            while let Some(frame) = frames.try_next().await? {
+                if frame.is_empty() {
+                    continue;
+                }
+                encoder.write_all(&frame).await?;
+                encoder.flush().await?;
+                let out = std::mem::take(encoder.buffer());
+                if !out.is_empty() {
+                    return Ok(Some((Bytes::from(out), (frames, Some(encoder)))));
+                }
+            }
+            encoder.shutdown().await?;
+            let out = Bytes::from(std::mem::take(encoder.buffer()));
+            Ok::<_, io::Error>(Some((out, (frames, None))))
+        },
+    ))
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    body::{Body, HttpBody},
+    extract::State,
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use luciuz_config::{Config, ErrorFormat};
+
+use crate::RequestId;
+
+/// Compiled `[errors]` settings (site-wide or for one proxy route).
+#[derive(Debug)]
+pub(crate) struct ErrorPages {
+    format: Format,
+    /// HTML templates by status code.
+    pages: HashMap<u16, String>,
+    /// `None`: upstream bodies pass through. `Some(empty)`: all 4xx/5xx.
+    intercept: Option<Vec<u16>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Auto,
+    Html,
+    Problem,
+}
+
+/// Response extension: error settings of the proxy route that answered.
+#[derive(Debug, Clone)]
+pub(crate) struct RouteErrorPages(pub(crate) Arc<ErrorPages>);
+
+/// Response extension: the response (and its body) was relayed from an upstream.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FromUpstream;
+
+impl ErrorPages {
+    /// `at` is the config path, for error messages.
+    pub(crate) fn load(cfg: Option<&luciuz_config::ErrorPages>, at: &str) -> anyhow::Result<Self> {
+        let Some(cfg) = cfg else {
+            return Ok(Self {
+                format: Format::Auto,
+                pages: HashMap::new(),
+                intercept: None,
+            });
+        };
+
+        let format = match cfg.format {
+            ErrorFormat::Html => Format::Html,
+            ErrorFormat::Problem => Format::Problem,
+            ErrorFormat::Auto => Format::Auto,
+        };
+        let mut pages = HashMap::new();
+        for (code, path) in &cfg.pages {
+            let code: u16 = code
+                .parse()
+                .with_context(|| format!("{at}.pages: invalid status code {code}"))?;
+            let html = std::fs::read_to_string(path)
+                .with_context(|| format!("{at}.pages.{code}: cannot read {path}"))?;
+            pages.insert(code, html);
+        }
+
+        Ok(Self {
+            format,
+            pages,
+            intercept: cfg.intercept_upstream.then(|| cfg.intercept_status.clone()),
+        })
+    }
+
+    fn intercepts(&self, status: StatusCode) -> bool {
+        self.intercept
+            .as_ref()
+            .is_some_and(|codes| codes.is_empty() || codes.contains(&status.as_u16()))
+    }
+
+    fn render(&self, res: Response, accept: Option<&str>, request_id: Option<&str>) -> Response {
+        let (mut parts, _) = res.into_parts();
+        let status = parts.status;
+        let problem = match self.format {
+            Format::Auto => prefers_json(accept.unwrap_or("")),
+            Format::Html => false,
+            Format::Problem => true,
+        };
+
+        let (content_type, body) = if problem {
+            ("application/problem+json", problem_json(status, request_id))
+        } else {
+            let html = match self.pages.get(&status.as_u16()) {
+                Some(tpl) => tpl
+                    .replace("{{status}}", status.as_str())
+                    .replace("{{request_id}}", request_id.unwrap_or("")),
+                None => default_html(status, request_id),
+            };
+            ("text/html; charset=utf-8", html)
+        };
+
+        for name in [
+            header::CONTENT_LENGTH,
+            header::CONTENT_ENCODING,
+            header::CONTENT_RANGE,
+            header::ETAG,
+            header::LAST_MODIFIED,
+        ] {
+            parts.headers.remove(name);
+        }
+        parts
+            .headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        Response::from_parts(parts, Body::from(body))
+    }
+}
+
+/// Site-wide error settings for [`error_pages_mw`].
+#[derive(Debug, Clone)]
+pub struct ErrorPagesState {
+    site: Arc<ErrorPages>,
+}
+
+impl ErrorPagesState {
+    pub fn new(cfg: &Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            site: Arc::new(ErrorPages::load(cfg.errors.as_ref(), "errors")?),
+        })
+    }
+}
+
+/// Give error responses a body: an HTML page or an RFC 9457 problem details
+/// document, both carrying the request id.
+///
+/// Luciuz's own errors (e.g. 502, 504, 421) are sent with an empty body and
+/// replaced here; upstream error bodies are only replaced when
+/// `intercept_upstream` is set. A proxy route's own `errors` take precedence
+/// over the site-wide settings. Must run inside [`crate::request_id_mw`].
+pub async fn error_pages_mw(
+    State(state): State<ErrorPagesState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let request_id = req.extensions().get::<RequestId>().map(|r| r.0.clone());
+
+    let res = next.run(req).await;
+    let status = res.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return res;
+    }
+
+    let pages = match res.extensions().get::<RouteErrorPages>() {
+        Some(route) => route.0.clone(),
+        None => state.site.clone(),
+    };
+    let replace = if res.extensions().get::<FromUpstream>().is_some() {
+        pages.intercepts(status)
+    } else {
+        res.body().size_hint().exact() == Some(0)
+    };
+    if !replace {
+        return res;
+    }
+    pages.render(res, accept.as_deref(), request_id.as_deref())
+}
+
+/// True if `Accept` ranks a JSON type above HTML. Wildcards count for
+/// neither, so browsers and `*/*` clients get HTML.
+pub(crate) fn prefers_json(accept: &str) -> bool {
+    let (mut html, mut json) = (0.0f32, 0.0f32);
+    for item in accept.split(',') {
+        let mut params = item.split(';');
+        let media = params.next().unwrap_or("").trim().to_ascii_lowercase();
+        let q = params
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media.as_str() {
+            "text/html" | "application/xhtml+xml" => html = html.max(q),
+            "application/json" => json = json.max(q),
+            m if m.starts_with("application/") && m.ends_with("+json") => json = json.max(q),
+            _ => {}
+        }
+    }
+    json > html
+}
+
+fn title(status: StatusCode) -> &'static str {
+    status.canonical_reason().unwrap_or("Error")
+}
+
+fn problem_json(status: StatusCode, request_id: Option<&str>) -> String {
+    // Request ids are restricted to [A-Za-z0-9-_.:], so no escaping is needed.
+    let mut out = format!(
+        r#"{{"type":"about:blank","title":"{}","status":{}"#,
+        title(status),
+        status.as_u16()
+    );
+    if let Some(id) = request_id {
+        out.push_str(&format!(r#","request_id":"{id}""#));
+    }
+    out.push('}');
+    out
+}
+
+fn default_html(status: StatusCode, request_id: Option<&str>) -> String {
+    let heading = format!("{} {}", status.as_u16(), title(status));
+    let id = request_id
+        .map(|id| format!("\n  <p>Request ID: <code>{id}</code></p>"))
+        .unwrap_or_default();
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>{heading}</title>
+</head>
+<body>
+  <h1>{heading}</h1>{id}
+</body>
+</html>
+"#
+    )
+}
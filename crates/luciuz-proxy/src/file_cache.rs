@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use bytes::Bytes;
+use percent_encoding::percent_decode_str;
+use tracing::{debug, info};
+
+use crate::compression::accept_q;
+use crate::static_site::{fs_path, variants};
+
+/// Key of a cached file: its path, and the content coding of a
+/// precompressed variant (`None` for the file itself).
+type Key = (PathBuf, Option<&'static str>);
+
+/// In-memory cache of small static files, in front of the file service.
+#[derive(Debug)]
+pub(crate) struct FileCache {
+    root: PathBuf,
+    max_bytes: u64,
+    max_file_bytes: u64,
+    revalidate: Duration,
+    /// (coding, file suffix) of enabled precompressed variants, in config order.
+    variants: Vec<(&'static str, &'static str)>,
+    inner: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+    evictions: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    /// Sum of cached content lengths.
+    bytes: u64,
+    /// Use counter, for least-recently-used eviction.
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// `None`: the file was absent (only kept for variants).
+    file: Option<Arc<CachedFile>>,
+    checked: Instant,
+    used: u64,
+}
+
+/// A file held in memory, ready to be answered.
+#[derive(Debug)]
+pub(crate) struct CachedFile {
+    bytes: Bytes,
+    len: u64,
+    mtime: SystemTime,
+    etag: HeaderValue,
+    last_modified: HeaderValue,
+    content_type: HeaderValue,
+}
+
+/// What the disk says about a key.
+enum Lookup {
+    /// The file, and whether it was just read from disk.
+    Cached(Arc<CachedFile>, bool),
+    Absent,
+    /// A directory, a file too large to cache, or an I/O error: leave it to
+    /// the file service.
+    Uncacheable,
+}
+
+impl FileCache {
+    pub(crate) fn new(
+        root: &str,
+        cfg: &luciuz_config::FileCache,
+        precompressed: &[luciuz_config::Algorithm],
+    ) -> Arc<Self> {
+        let cache = Arc::new(Self {
+            root: PathBuf::from(root),
+            max_bytes: cfg.max_bytes,
+            max_file_bytes: cfg.max_file_bytes,
+            revalidate: cfg.revalidate,
+            variants: variants(precompressed),
+            inner: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            bypassed: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        });
+        if !cfg.stats_interval.is_zero() {
+            tokio::spawn(log_stats(Arc::downgrade(&cache), cfg.stats_interval));
+        }
+        cache
+    }
+
+    /// The cached file for `key`, revalidated against the disk (size and
+    /// mtime) when older than `revalidate`.
+    async fn get(&self, key: &Key) -> Lookup {
+        let known = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.tick += 1;
+            let tick = inner.tick;
+            match inner.map.get_mut(key) {
+                Some(e) => {
+                    e.used = tick;
+                    if e.checked.elapsed() < self.revalidate {
+                        return match &e.file {
+                            Some(f) => Lookup::Cached(f.clone(), false),
+                            None => Lookup::Absent,
+                        };
+                    }
+                    Some(e.file.clone())
+                }
+                None => None,
+            }
+        };
+
+        let path = variant_path(&key.0, key.1.and_then(|c| self.suffix(c)));
+        let meta = match tokio::fs::metadata(&path).await {
+            Ok(meta) => meta,
+            Err(_) if key.1.is_some() => {
+                self.insert(key.clone(), None);
+                return Lookup::Absent;
+            }
+            Err(_) => {
+                self.remove(key);
+                return Lookup::Absent;
+            }
+        };
+        if !meta.is_file() || meta.len() > self.max_file_bytes {
+            self.remove(key);
+            return Lookup::Uncacheable;
+        }
+        let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        if let Some(Some(f)) = known {
+            if f.len == meta.len() && f.mtime == mtime {
+                if let Some(e) = self.inner.lock().unwrap().map.get_mut(key) {
+                    e.checked = Instant::now();
+                }
+                return Lookup::Cached(f, false);
+            }
+        }
+
+        let Ok(data) = tokio::fs::read(&path).await else {
+            return Lookup::Uncacheable;
+        };
+        let file = Arc::new(CachedFile::new(data, mtime, &key.0));
+        self.insert(key.clone(), Some(file.clone()));
+        Lookup::Cached(file, true)
+    }
+
+    fn count(&self, loaded: bool) {
+        let counter = if loaded { &self.misses } else { &self.hits };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn suffix(&self, coding: &str) -> Option<&'static str> {
+        self.variants
+            .iter()
+            .find(|(c, _)| *c == coding)
+            .map(|(_, s)| *s)
+    }
+
+    fn insert(&self, key: Key, file: Option<Arc<CachedFile>>) {
+        let len = file.as_ref().map_or(0, |f| f.len);
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(old) = inner.map.remove(&key) {
+            inner.bytes -= old.file.map_or(0, |f| f.len);
+        }
+        while inner.bytes + len > self.max_bytes {
+            let Some(lru) = inner
+                .map
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(Some(old)) = inner.map.remove(&lru).map(|e| e.file) {
+                inner.bytes -= old.len;
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        inner.tick += 1;
+        let used = inner.tick;
+        inner.bytes += len;
+        inner.map.insert(
+            key,
+            Entry {
+                file,
+                checked: Instant::now(),
+                used,
+            },
+        );
+    }
+
+    fn remove(&self, key: &Key) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(old) = inner.map.remove(key) {
+            inner.bytes -= old.file.map_or(0, |f| f.len);
+        }
+    }
+}
+
+impl CachedFile {
+    /// `data` read from `path`, with an ETag hashed from the content.
+    fn new(data: Vec<u8>, mtime: SystemTime, path: &Path) -> Self {
+        let digest = ring::digest::digest(&ring::digest::SHA256, &data);
+        let hex: String = digest.as_ref()[..16]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Self::with_etag(Bytes::from(data), mtime, path, &hex)
+    }
+
+    /// `tag` is the opaque part of the ETag (no quotes), e.g. a checksum.
+    pub(crate) fn with_etag(bytes: Bytes, mtime: SystemTime, path: &Path, tag: &str) -> Self {
+        let content_type = mime_guess::from_path(path)
+            .first_raw()
+            .unwrap_or("application/octet-stream");
+        Self {
+            len: bytes.len() as u64,
+            bytes,
+            mtime,
+            etag: HeaderValue::from_str(&format!("\"{tag}\"")).expect("etag"),
+            last_modified: HeaderValue::from_str(&httpdate::fmt_http_date(mtime))
+                .expect("http date"),
+            content_type: HeaderValue::from_static(content_type),
+        }
+    }
+
+    fn not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(inm) = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+        {
+            let ours = self.etag.to_str().unwrap_or("");
+            return inm.split(',').any(|t| {
+                let t = t.trim();
+                t == "*" || t.trim_start_matches("W/") == ours
+            });
+        }
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok())
+            .is_some_and(|since| {
+                // HTTP dates have a one-second resolution.
+                self.mtime
+                    .duration_since(since)
+                    .map_or(true, |d| d < Duration::from_secs(1))
+            })
+    }
+
+    pub(crate) fn response(
+        &self,
+        req_headers: &HeaderMap,
+        coding: Option<&'static str>,
+    ) -> Response {
+        let not_modified = self.not_modified(req_headers);
+        let mut res = if not_modified {
+            Response::new(Body::empty())
+        } else {
+            // Cloning `Bytes` shares the buffer: no copy per response.
+            Response::new(Body::from(self.bytes.clone()))
+        };
+        let h = res.headers_mut();
+        h.insert(header::CONTENT_TYPE, self.content_type.clone());
+        h.insert(header::ETAG, self.etag.clone());
+        h.insert(header::LAST_MODIFIED, self.last_modified.clone());
+        h.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        if let Some(coding) = coding {
+            h.insert(header::CONTENT_ENCODING, HeaderValue::from_static(coding));
+        }
+        if not_modified {
+            *res.status_mut() = StatusCode::NOT_MODIFIED;
+        } else {
+            res.headers_mut()
+                .insert(header::CONTENT_LENGTH, HeaderValue::from(self.len));
+        }
+        res
+    }
+}
+
+fn variant_path(file: &Path, suffix: Option<&str>) -> PathBuf {
+    match suffix {
+        Some(suffix) => {
+            let mut p = file.as_os_str().to_owned();
+            p.push(suffix);
+            PathBuf::from(p)
+        }
+        None => file.to_path_buf(),
+    }
+}
+
+/// Codings of `variants` the client accepts, highest q-value first.
+pub(crate) fn preferred_codings(
+    headers: &HeaderMap,
+    variants: &[(&'static str, &'static str)],
+) -> Vec<&'static str> {
+    let accept = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let mut codings: Vec<(&'static str, f32)> = variants
+        .iter()
+        .map(|(c, _)| (*c, accept_q(accept, c)))
+        .filter(|(_, q)| *q > 0.0)
+        .collect();
+    codings.sort_by(|a, b| b.1.total_cmp(&a.1));
+    codings.into_iter().map(|(c, _)| c).collect()
+}
+
+/// Serve GET/HEAD requests for small files from memory, with an ETag and
+/// conditional (304) responses. Range requests, directories and large files
+/// go to the file service. Precompressed variants are picked by the client's
+/// `Accept-Encoding`, highest q-value first.
+pub(crate) async fn file_cache_mw(
+    State(cache): State<Arc<FileCache>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let cacheable = (req.method() == Method::GET || req.method() == Method::HEAD)
+        && !req.headers().contains_key(header::RANGE);
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    let file = fs_path(&cache.root, &path).filter(|_| cacheable);
+    let Some(mut file) = file else {
+        cache.bypassed.fetch_add(1, Ordering::Relaxed);
+        return next.run(req).await;
+    };
+    if path.ends_with('/') {
+        file.push("index.html");
+    }
+
+    let identity = match cache.get(&(file.clone(), None)).await {
+        Lookup::Cached(f, loaded) => (f, loaded),
+        Lookup::Absent | Lookup::Uncacheable => {
+            cache.bypassed.fetch_add(1, Ordering::Relaxed);
+            return next.run(req).await;
+        }
+    };
+
+    for coding in preferred_codings(req.headers(), &cache.variants) {
+        if let Lookup::Cached(f, loaded) = cache.get(&(file.clone(), Some(coding))).await {
+            cache.count(loaded);
+            return f.response(req.headers(), Some(coding));
+        }
+    }
+    let (identity, loaded) = identity;
+    cache.count(loaded);
+    identity.response(req.headers(), None)
+}
+
+/// Log cache statistics every `interval` while traffic changes them; stops
+/// when the cache is dropped (e.g. the router was rebuilt).
+async fn log_stats(cache: Weak<FileCache>, interval: Duration) {
+    let mut last = (0, 0);
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(cache) = cache.upgrade() else {
+            debug!("static file cache dropped, stopping stats");
+            return;
+        };
+        let hits = cache.hits.load(Ordering::Relaxed);
+        let misses = cache.misses.load(Ordering::Relaxed);
+        if (hits, misses) == last {
+            continue;
+        }
+        last = (hits, misses);
+        let (entries, bytes) = {
+            let inner = cache.inner.lock().unwrap();
+            (inner.map.len(), inner.bytes)
+        };
+        info!(
+            hits,
+            misses,
+            bypassed = cache.bypassed.load(Ordering::Relaxed),
+            evictions = cache.evictions.load(Ordering::Relaxed),
+            entries,
+            bytes,
+            "static file cache stats"
+        );
+    }
+}
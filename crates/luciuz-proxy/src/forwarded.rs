@@ -0,0 +1,275 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{connect_info::ConnectInfo, State},
+    http::{header, HeaderMap, HeaderName, Request},
+    middleware::Next,
+    response::Response,
+};
+use luciuz_config::{any_contains, Cidr, Config};
+
+use crate::ProxyHeader;
+
+/// Forwarding headers a client could use to spoof its address, scheme or host.
+const FORWARDING_HEADERS: [&str; 7] = [
+    "forwarded",
+    "x-forwarded-for",
+    "x-forwarded-proto",
+    "x-forwarded-host",
+    "x-forwarded-prefix",
+    "x-forwarded-uri",
+    "x-real-ip",
+];
+
+/// Client identity for a request, stored in request extensions by
+/// [`client_ip_mw`]. Logs and policies should use `ip`, not the TCP peer.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    /// Resolved client IP.
+    pub ip: IpAddr,
+    /// TCP peer (the last hop).
+    pub peer: SocketAddr,
+    /// Whether `peer` is a trusted proxy, i.e. its forwarding headers were honoured.
+    pub trusted_peer: bool,
+    /// Original scheme ("https" / "http").
+    pub proto: String,
+    /// Original Host.
+    pub host: Option<String>,
+}
+
+impl ClientInfo {
+    /// A client talking to us directly: forwarding headers are ignored.
+    pub(crate) fn direct(peer: SocketAddr, headers: &HeaderMap, proto: &str) -> Self {
+        Self {
+            ip: peer.ip(),
+            peer,
+            trusted_peer: false,
+            proto: proto.to_string(),
+            host: header_str(headers, header::HOST.as_str()).map(str::to_string),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClientIpState {
+    trusted: Arc<[Cidr]>,
+    proto: &'static str,
+}
+
+impl ClientIpState {
+    /// `proto` is the scheme of the listener this state is attached to.
+    pub fn new(cfg: &Config, proto: &'static str) -> Self {
+        Self {
+            trusted: cfg.server.trusted_proxies.clone().into(),
+            proto,
+        }
+    }
+
+    fn resolve(&self, peer: SocketAddr, proto: &str, headers: &HeaderMap) -> ClientInfo {
+        let mut info = ClientInfo::direct(peer, headers, proto);
+        if !any_contains(&self.trusted, peer.ip()) {
+            return info;
+        }
+        info.trusted_peer = true;
+
+        // Scheme and host are only taken from what a trusted hop wrote: the
+        // `Forwarded` element the walk stops at, or the last value of the
+        // `X-Forwarded-*` headers. Anything left of that is the client's.
+        let (proto, host) = match joined(headers, "forwarded") {
+            Some(fwd) => {
+                let elems = parse_forwarded(&fwd);
+                let chain: Vec<Option<IpAddr>> = elems
+                    .iter()
+                    .map(|e| e.for_node.as_deref().and_then(parse_node))
+                    .collect();
+                match self.walk(&chain, &mut info.ip).and_then(|i| elems.get(i)) {
+                    Some(e) => (e.proto.clone(), e.host.clone()),
+                    None => (None, None),
+                }
+            }
+            None => {
+                let chain: Vec<Option<IpAddr>> = joined(headers, "x-forwarded-for")
+                    .map(|v| v.split(',').map(parse_node).collect())
+                    .unwrap_or_default();
+                self.walk(&chain, &mut info.ip);
+                let last = |name: &str| {
+                    joined(headers, name)
+                        .and_then(|v| v.rsplit(',').next().map(|v| v.trim().to_string()))
+                };
+                (last("x-forwarded-proto"), last("x-forwarded-host"))
+            }
+        };
+
+        if let Some(p) = proto.filter(|p| p == "http" || p == "https") {
+            info.proto = p;
+        }
+        if let Some(h) = host.filter(|h| !h.is_empty()) {
+            info.host = Some(h);
+        }
+        info
+    }
+
+    /// Walk `chain` right to left: skip trusted hops, stop at the first
+    /// untrusted one, whose address goes to `ip`. An unparseable hop (e.g.
+    /// "unknown" or "_hidden") ends the walk. The index it stopped at, or
+    /// `None` for an empty chain.
+    fn walk(&self, chain: &[Option<IpAddr>], ip: &mut IpAddr) -> Option<usize> {
+        for (i, hop) in chain.iter().enumerate().rev() {
+            match hop {
+                Some(hop) => {
+                    *ip = *hop;
+                    if !any_contains(&self.trusted, *hop) {
+                        return Some(i);
+                    }
+                }
+                None => return Some(i),
+            }
+        }
+        (!chain.is_empty()).then_some(0)
+    }
+}
+
+/// Resolve the client address and attach a [`ClientInfo`] to the request.
+///
+/// On PROXY protocol connections, the decoded source address replaces the
+/// `ConnectInfo<SocketAddr>` peer for everything downstream.
+pub async fn client_ip_mw(
+    State(state): State<ClientIpState>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut proto = state.proto;
+    if let Some(h) = req.extensions().get::<ProxyHeader>() {
+        if h.ssl.as_ref().is_some_and(|ssl| ssl.client_ssl) {
+            proto = "https";
+        }
+        if let Some(src) = h.source {
+            req.extensions_mut().insert(ConnectInfo(src));
+        }
+    }
+
+    if let Some(peer) = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ci| ci.0)
+    {
+        let info = state.resolve(peer, proto, req.headers());
+        req.extensions_mut().insert(info);
+    }
+    next.run(req).await
+}
+
+/// Remove forwarding headers (used when the peer is not a trusted proxy).
+pub(crate) fn strip_forwarding_headers(headers: &mut HeaderMap) {
+    for name in FORWARDING_HEADERS {
+        headers.remove(HeaderName::from_static(name));
+    }
+}
+
+/// One RFC 7239 `Forwarded` element, e.g. `for=192.0.2.43;proto=https;by=_luciuz`.
+pub(crate) fn forwarded_element(
+    for_ip: IpAddr,
+    proto: &str,
+    host: Option<&str>,
+    by: &str,
+) -> String {
+    let mut out = format!(
+        "for={};proto={proto}",
+        node(&for_ip.to_string(), for_ip.is_ipv6())
+    );
+    if let Some(h) = host {
+        out.push_str(&format!(";host={}", quote(h)));
+    }
+    let by_is_v6 = by.parse::<std::net::Ipv6Addr>().is_ok();
+    out.push_str(&format!(";by={}", node(by, by_is_v6)));
+    out
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    for_node: Option<String>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+fn parse_forwarded(v: &str) -> Vec<Element> {
+    split_unquoted(v, ',')
+        .into_iter()
+        .map(|elem| {
+            let mut e = Element::default();
+            for pair in split_unquoted(elem, ';') {
+                let Some((k, v)) = pair.split_once('=') else {
+                    continue;
+                };
+                let v = v.trim().trim_matches('"').to_string();
+                match k.trim().to_ascii_lowercase().as_str() {
+                    "for" => e.for_node = Some(v),
+                    "proto" => e.proto = Some(v.to_ascii_lowercase()),
+                    "host" => e.host = Some(v),
+                    _ => {}
+                }
+            }
+            e
+        })
+        .collect()
+}
+
+/// Split on `sep`, ignoring separators inside double quotes.
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c == sep && !in_quotes => {
+                out.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&s[start..]);
+    out
+}
+
+/// Parse a node ("192.0.2.43", "192.0.2.43:80", "[2001:db8::1]:80", "2001:db8::1").
+fn parse_node(s: &str) -> Option<IpAddr> {
+    let s = s.trim().trim_matches('"');
+    if let Some(rest) = s.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    s.parse::<IpAddr>()
+        .ok()
+        .or_else(|| s.parse::<SocketAddr>().ok().map(|sa| sa.ip()))
+}
+
+fn node(value: &str, ipv6: bool) -> String {
+    if ipv6 {
+        format!("\"[{value}]\"")
+    } else if value.contains(':') {
+        quote(value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn quote(v: &str) -> String {
+    format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// All values of a (list-valued) header, joined with ", ".
+pub(crate) fn joined(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<&str> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
@@ -6,15 +6,84 @@ use axum::{
 };
 use luciuz_config::Config;
 use reqwest::Client;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Instant;
-use std::{collections::HashSet, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 use tracing::{info, warn};
 
+mod admin;
+mod basic_auth;
+mod bundle;
+mod compression;
+mod error_pages;
+mod file_cache;
+mod forwarded;
+mod listing;
+mod outbound;
+mod pool;
+mod proxy_protocol;
+mod request_id;
+mod split;
+mod static_guard;
+mod static_site;
+mod upload;
+
+pub use admin::{admin_mw, AdminState};
+pub use basic_auth::hash_password;
+pub use compression::{compression_mw, CompressionState};
+pub use error_pages::{error_pages_mw, ErrorPagesState};
+pub use forwarded::{client_ip_mw, ClientInfo, ClientIpState};
+pub use proxy_protocol::{ProxyHeader, ProxyProtocolAcceptor, ProxySsl, WithProxyHeader};
+pub use request_id::{request_id_mw, RequestId};
+pub use static_site::static_router;
+
+use compression::NoCompression;
+use error_pages::{ErrorPages, FromUpstream, RouteErrorPages};
+use forwarded::{forwarded_element, joined, strip_forwarding_headers};
+use outbound::UpstreamError;
+use pool::Pool;
+use split::Split;
+
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where a route sends its traffic.
+enum Upstream {
+    /// A named pool, or the implicit one-target pool behind `upstream`.
+    Pool(Arc<Pool>),
+    Split(Split),
+}
+
+/// Per-route settings shared by the `/prefix` and `/prefix/{*path}` handlers.
+///
+/// Handlers hold an `Arc` to it, so a request keeps the route state it started
+/// with even if the router is rebuilt (e.g. on config reload).
+struct RouteCtx {
+    client: Client,
+    upstream: Upstream,
+    prefix: String,
+    strip_prefix: bool,
+    preserve_host: bool,
+    pass_x_forwarded: bool,
+    forwarded: bool,
+    forwarded_by: String,
+    /// Scheme of the listener serving the route.
+    proto: &'static str,
+    max_body_bytes: usize,
+    errors: Option<Arc<ErrorPages>>,
+    breach_sensitive: bool,
+}
+
 /// Build the proxy router from config.
 /// It creates explicit routes for:
 /// - /api
 /// - /api/{*path}
-pub fn router(cfg: &Config) -> anyhow::Result<Router<()>> {
+///
+/// `proto` is the scheme of the listener the router is served on.
+pub fn router(cfg: &Config, proto: &'static str) -> anyhow::Result<Router<()>> {
     let proxy_cfg = cfg
         .proxy
         .as_ref()
@@ -26,107 +95,96 @@ pub fn router(cfg: &Config) -> anyhow::Result<Router<()>> {
     let max_body: usize = if proxy_cfg.max_body_bytes == 0 {
         10 * 1024 * 1024 // 10 MB
     } else {
-        proxy_cfg.max_body_bytes as usize
+        proxy_cfg.max_body_bytes
     };
 
     // A simple reqwest client for upstream calls
-    let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let client = Client::builder().timeout(UPSTREAM_TIMEOUT).build()?;
+
+    let pools = proxy_cfg
+        .pools
+        .iter()
+        .map(|(name, p)| Ok((name.clone(), Arc::new(Pool::new(name, p)?))))
+        .collect::<anyhow::Result<HashMap<String, Arc<Pool>>>>()?;
+    let lookup = |name: &str| {
+        pools
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("unknown upstream pool: {name}"))
+    };
 
     let mut rtr = Router::new();
 
     for route in routes {
         let prefix = route.prefix.trim_end_matches('/').to_string(); // "/api"
-        let upstream = route.upstream.trim_end_matches('/').to_string();
-        let strip_prefix = route.strip_prefix;
-        let preserve_host = route.preserve_host;
-        let pass_x_forwarded = route.pass_x_forwarded;
 
         if prefix.is_empty() {
             continue;
         }
 
+        let upstream = if let Some(split) = &route.split {
+            Upstream::Split(Split::new(split, lookup)?)
+        } else if let Some(pool) = &route.pool {
+            Upstream::Pool(lookup(pool)?)
+        } else {
+            Upstream::Pool(Arc::new(Pool::single(
+                &route.upstream,
+                route.send_proxy_protocol,
+            )))
+        };
+
         let pattern = format!("{}/{{*path}}", prefix.trim_end_matches('/')); // "/api/{*path}"
 
-        // /api
-        {
-            let client = client.clone();
-            let upstream = upstream.clone();
-            let prefix_for_strip = prefix.clone();
-            let preserve_host = preserve_host;
-            let pass_x_forwarded = pass_x_forwarded;
-            let max_body = max_body;
-
-            rtr = rtr.route(
-                &prefix,
-                any(move |req: Request<Body>| {
-                    let client = client.clone();
-                    let upstream = upstream.clone();
-                    let prefix_for_strip = prefix_for_strip.clone();
-                    let strip_prefix = strip_prefix;
-                    async move {
-                        proxy_one(
-                            req,
-                            client,
-                            upstream,
-                            prefix_for_strip,
-                            strip_prefix,
-                            preserve_host,
-                            pass_x_forwarded,
-                            max_body,
-                        )
-                        .await
-                    }
-                }),
-            );
-        }
+        let errors = match &route.errors {
+            Some(e) => Some(Arc::new(ErrorPages::load(
+                Some(e),
+                &format!("proxy.routes[{prefix}].errors"),
+            )?)),
+            None => None,
+        };
+
+        let ctx = Arc::new(RouteCtx {
+            client: client.clone(),
+            upstream,
+            prefix: prefix.clone(),
+            strip_prefix: route.strip_prefix,
+            preserve_host: route.preserve_host,
+            pass_x_forwarded: route.pass_x_forwarded,
+            forwarded: route.forwarded,
+            forwarded_by: cfg.server.forwarded_by.clone(),
+            proto,
+            max_body_bytes: max_body,
+            errors,
+            breach_sensitive: route.breach_sensitive,
+        });
+
+        let handler = any(move |req: Request<Body>| {
+            let ctx = ctx.clone();
+            async move {
+                let mut res = proxy_one(req, &ctx).await;
+                if let Some(errors) = &ctx.errors {
+                    res.extensions_mut().insert(RouteErrorPages(errors.clone()));
+                }
+                if ctx.breach_sensitive {
+                    res.extensions_mut().insert(NoCompression);
+                }
+                res
+            }
+        });
 
-        // /api/{*path}
-        {
-            let client = client.clone();
-            let upstream = upstream.clone();
-            let prefix_for_strip = prefix.clone();
-            let preserve_host = preserve_host;
-            let pass_x_forwarded = pass_x_forwarded;
-            let max_body = max_body;
-
-            rtr = rtr.route(
-                &pattern,
-                any(move |req: Request<Body>| {
-                    let client = client.clone();
-                    let upstream = upstream.clone();
-                    let prefix_for_strip = prefix_for_strip.clone();
-                    let strip_prefix = strip_prefix;
-                    async move {
-                        proxy_one(
-                            req,
-                            client,
-                            upstream,
-                            prefix_for_strip,
-                            strip_prefix,
-                            preserve_host,
-                            pass_x_forwarded,
-                            max_body,
-                        )
-                        .await
-                    }
-                }),
-            );
-        }
+        // /api and /api/{*path}
+        rtr = rtr.route(&prefix, handler.clone()).route(&pattern, handler);
     }
 
     Ok(rtr)
 }
 
-async fn proxy_one(
-    req: Request<Body>,
-    client: Client,
-    upstream: String,
-    prefix: String,
-    strip_prefix: bool,
-    preserve_host: bool,
-    pass_x_forwarded: bool,
-    max_body_bytes: usize,
-) -> Response<Body> {
+async fn proxy_one(req: Request<Body>, ctx: &RouteCtx) -> Response<Body> {
+    let client = &ctx.client;
+    let prefix = &ctx.prefix;
+    let (strip_prefix, preserve_host, pass_x_forwarded) =
+        (ctx.strip_prefix, ctx.preserve_host, ctx.pass_x_forwarded);
+
     let (parts, body) = req.into_parts();
 
     // Preserve some incoming metadata before we move headers around.
@@ -135,21 +193,17 @@ async fn proxy_one(
         .get(header::HOST)
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
-    let incoming_xf_proto = parts
-        .headers
-        .get("x-forwarded-proto")
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.to_string());
-    let orig_host = parts
-        .headers
-        .get(header::HOST)
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.to_string());
 
-    let client_ip = parts
-        .extensions
-        .get::<axum::extract::connect_info::ConnectInfo<std::net::SocketAddr>>()
-        .map(|ci| ci.0.ip().to_string());
+    // Resolved by `client_ip_mw`; without it, the TCP peer is the client and
+    // no forwarding header is trusted.
+    let client_info = parts.extensions.get::<ClientInfo>().cloned().or_else(|| {
+        parts
+            .extensions
+            .get::<axum::extract::connect_info::ConnectInfo<SocketAddr>>()
+            .map(|ci| ClientInfo::direct(ci.0, &parts.headers, ctx.proto))
+    });
+    let client_ip = client_info.as_ref().map(|c| c.ip);
+    let request_id = parts.extensions.get::<RequestId>().map(|r| r.0.clone());
 
     // IMPORTANT: we want /api => / and /api/ => /
     let orig_path = parts.uri.path();
@@ -170,52 +224,79 @@ async fn proxy_one(
     }
 
     let rest = if rest.is_empty() { "/" } else { rest };
-    let base = upstream.trim_end_matches('/');
 
-    let mut target = format!("{base}{rest}");
+    let (pool, mut set_cookies) = match &ctx.upstream {
+        Upstream::Pool(pool) => (pool.as_ref(), Vec::new()),
+        Upstream::Split(split) => {
+            let secure = client_info.as_ref().map_or(ctx.proto, |c| c.proto.as_str()) == "https";
+            let choice = split.choose(&parts.headers, parts.uri.query(), prefix, secure);
+            (
+                choice.pool.as_ref(),
+                choice.set_cookie.into_iter().collect(),
+            )
+        }
+    };
+    let pool_name = (!pool.name.is_empty()).then_some(pool.name.as_str());
+    let mut pick = pool.pick(&parts.headers);
+
+    let mut rest = rest.to_string();
     if let Some(q) = parts.uri.query() {
-        target.push('?');
-        target.push_str(q);
+        rest.push('?');
+        rest.push_str(q);
     }
+    let mut target = format!("{}{rest}", pool.url(pick.index));
 
     // We'll build a reqwest request, and then attach these headers.
     // (We add them only if they are missing.)
 
     // Body (with limit)
-    let bytes = match to_bytes(body, max_body_bytes).await {
+    let bytes = match to_bytes(body, ctx.max_body_bytes).await {
         Ok(b) => b,
         Err(_) => {
-            return Response::builder()
-                .status(StatusCode::PAYLOAD_TOO_LARGE)
-                .body(Body::from("payload too large"))
-                .unwrap();
+            return error_response(StatusCode::PAYLOAD_TOO_LARGE);
         }
     };
 
-    // Build upstream request
-    let mut rb = client.request(parts.method.clone(), target.clone());
-
+    // Upstream request headers
     let mut out_headers = filter_hop_by_hop(parts.headers);
     if !preserve_host {
         out_headers.remove(header::HOST);
     }
-    if pass_x_forwarded {
-        // x-forwarded-host (set only if missing)
-        let xf_host = HeaderName::from_static("x-forwarded-host");
-        if !out_headers.contains_key(&xf_host) {
-            if let Some(host) = orig_host.as_deref() {
-                if let Ok(v) = HeaderValue::from_str(host) {
-                    out_headers.insert(xf_host, v);
-                }
-            }
+
+    // Forwarding headers are only passed on from trusted proxies.
+    if !client_info.as_ref().is_some_and(|c| c.trusted_peer) {
+        strip_forwarding_headers(&mut out_headers);
+    }
+
+    if let (true, Some(info)) = (ctx.forwarded, client_info.as_ref()) {
+        let elem = forwarded_element(
+            info.peer.ip(),
+            &info.proto,
+            info.host.as_deref(),
+            &ctx.forwarded_by,
+        );
+        let value = match joined(&out_headers, "forwarded") {
+            Some(prev) => format!("{prev}, {elem}"),
+            None => elem,
+        };
+        if let Ok(v) = HeaderValue::from_str(&value) {
+            out_headers.insert(header::FORWARDED, v);
         }
+    }
 
-        // x-forwarded-proto (set only if missing)
-        let xf_proto = HeaderName::from_static("x-forwarded-proto");
-        if !out_headers.contains_key(&xf_proto) {
-            let proto = incoming_xf_proto.as_deref().unwrap_or("https");
-            if let Ok(v) = HeaderValue::from_str(proto) {
-                out_headers.insert(xf_proto, v);
+    if pass_x_forwarded {
+        if let Some(info) = client_info.as_ref() {
+            // x-forwarded-host / x-forwarded-proto: original values, as resolved
+            // from trusted proxies.
+            if let Some(v) = info
+                .host
+                .as_deref()
+                .and_then(|h| HeaderValue::from_str(h).ok())
+            {
+                out_headers.insert(HeaderName::from_static("x-forwarded-host"), v);
+            }
+            if let Ok(v) = HeaderValue::from_str(&info.proto) {
+                out_headers.insert(HeaderName::from_static("x-forwarded-proto"), v);
             }
         }
 
@@ -235,11 +316,12 @@ async fn proxy_one(
             out_headers.insert(HeaderName::from_static("x-forwarded-uri"), v);
         }
 
-        // x-forwarded-for = IP client (si on l'a via ConnectInfo)
-        if let Some(ip) = client_ip.as_deref() {
+        // x-forwarded-for: append the TCP peer to the (trusted) incoming chain
+        if let Some(ip) = client_info.as_ref().map(|c| c.peer.ip().to_string()) {
+            let ip = ip.as_str();
             let name = HeaderName::from_static("x-forwarded-for");
 
-            match out_headers.get(&name).and_then(|v| v.to_str().ok()) {
+            match joined(&out_headers, "x-forwarded-for").as_deref() {
                 Some(prev) => {
                     let combined = format!("{prev}, {ip}");
                     if let Ok(v) = HeaderValue::from_str(&combined) {
@@ -255,33 +337,69 @@ async fn proxy_one(
         }
     }
 
-    rb = rb.headers(out_headers);
-
-    // Force Host header according to route policy.
-    // - preserve_host=true  -> forward the original Host (e.g. luciuz.com)
-    // - preserve_host=false -> use the upstream host:port (e.g. 127.0.0.1:8080)
-    let host_value = if preserve_host {
-        incoming_host
-    } else {
-        hostport_from_url(&target)
-    };
-    if let Some(h) = host_value.as_deref() {
-        rb = rb.header(header::HOST, h);
-    }
+    // PROXY protocol addresses: the resolved client, and what it connected to
+    // when we received the connection through PROXY protocol ourselves.
+    let source = client_info.as_ref().map(|c| {
+        let port = if c.ip == c.peer.ip() {
+            c.peer.port()
+        } else {
+            0
+        };
+        SocketAddr::new(c.ip, port)
+    });
+    let destination = parts
+        .extensions
+        .get::<ProxyHeader>()
+        .and_then(|h| h.destination);
 
-    // Send
+    // Send (connection failures are retried once on another pool target)
     let start = Instant::now();
+    let mut retried = false;
+
+    let upstream_resp = loop {
+        let mut rb = client
+            .request(parts.method.clone(), target.clone())
+            .headers(out_headers.clone());
+
+        // Force Host header according to route policy.
+        // - preserve_host=true  -> forward the original Host (e.g. luciuz.com)
+        // - preserve_host=false -> use the upstream host:port (e.g. 127.0.0.1:8080)
+        let host_value = if preserve_host {
+            incoming_host.clone()
+        } else {
+            hostport_from_url(&target)
+        };
+        if let Some(h) = host_value.as_deref() {
+            rb = rb.header(header::HOST, h);
+        }
 
-    let upstream_resp = match rb.body(bytes).send().await {
-        Ok(r) => r,
-        Err(err) => {
-            warn!(?err, target = %target, "upstream request failed");
-            return Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(Body::from("bad gateway"))
-                .unwrap();
+        let sent = match (rb.body(bytes.clone()).build(), pool.proxy_protocol()) {
+            (Err(err), _) => Err(err.into()),
+            (Ok(req), None) => client.execute(req).await.map_err(UpstreamError::from),
+            (Ok(req), Some(pp)) => {
+                tokio::time::timeout(UPSTREAM_TIMEOUT, pp.execute(req, source, destination))
+                    .await
+                    .unwrap_or(Err(UpstreamError::Timeout))
+            }
+        };
+
+        match sent {
+            Ok(r) => break r,
+            Err(err) => {
+                warn!(%err, target = %target, "upstream request failed");
+                if err.is_connect() && !retried {
+                    if let Some(next) = pool.failover(pick.index) {
+                        retried = true;
+                        pick = next;
+                        target = format!("{}{rest}", pool.url(pick.index));
+                        continue;
+                    }
+                }
+                return error_response(StatusCode::BAD_GATEWAY);
+            }
         }
     };
+    set_cookies.extend(pick.set_cookie);
 
     tracing::info!(
         method = %parts.method,
@@ -290,13 +408,15 @@ async fn proxy_one(
         status = %upstream_resp.status(),
         dur_ms = start.elapsed().as_millis() as u64,
         client_ip = ?client_ip,
+        pool = ?pool_name,
+        request_id = ?request_id,
         "upstream response"
     );
 
     let status =
         StatusCode::from_u16(upstream_resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
 
-    let mut out = Response::builder().status(status);
+    let mut out = Response::builder().status(status).extension(FromUpstream);
 
     // Copy upstream response headers (filter hop-by-hop)
     if let Some(headers) = out.headers_mut() {
@@ -312,19 +432,23 @@ async fn proxy_one(
         headers.remove(header::SERVER);
         headers.remove(HeaderName::from_static("x-powered-by"));
         headers.remove(HeaderName::from_static("via"));
-    }
 
-    let body_bytes = match upstream_resp.bytes().await {
-        Ok(b) => b,
-        Err(_) => {
-            return Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(Body::from("bad gateway"))
-                .unwrap();
+        for cookie in set_cookies {
+            headers.append(header::SET_COOKIE, cookie);
         }
-    };
+    }
+
+    // Stream the upstream body (a failure mid-body aborts the response).
+    out.body(Body::from_stream(upstream_resp.bytes_stream()))
+        .unwrap()
+}
 
-    out.body(Body::from(body_bytes)).unwrap()
+/// Luciuz's own error: the body is left empty for `error_pages_mw` to fill.
+fn error_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
 }
 
 fn filter_hop_by_hop(mut in_headers: HeaderMap) -> HeaderMap {
@@ -0,0 +1,367 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use luciuz_config::Glob;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::error_pages::prefers_json;
+use crate::static_guard::StaticGuard;
+use crate::static_site::fs_path;
+
+/// Characters escaped in a file name used as a relative link.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'\'')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Compiled `[static_site.listing]`.
+#[derive(Debug)]
+pub(crate) struct Listing {
+    root: PathBuf,
+    /// Listed path prefixes, each ending in `/`.
+    prefixes: Vec<String>,
+    hide: Vec<Glob>,
+    guard: Arc<StaticGuard>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    dir: bool,
+    size: u64,
+    /// Seconds since the Unix epoch.
+    mtime: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Name,
+    Size,
+    Mtime,
+}
+
+impl SortKey {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::Mtime => "mtime",
+        }
+    }
+}
+
+impl Listing {
+    pub(crate) fn new(
+        root: &str,
+        cfg: &luciuz_config::Listing,
+        guard: Arc<StaticGuard>,
+    ) -> anyhow::Result<Self> {
+        let hide = cfg
+            .hide
+            .iter()
+            .map(|h| h.parse::<Glob>().map_err(anyhow::Error::msg))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let prefixes = cfg
+            .prefixes
+            .iter()
+            .map(|p| format!("{}/", p.trim_end_matches('/')))
+            .collect();
+        Ok(Self {
+            root: PathBuf::from(root),
+            prefixes,
+            hide,
+            guard,
+        })
+    }
+
+    /// The longest listed prefix `path` is under.
+    fn prefix_of(&self, path: &str) -> Option<&str> {
+        self.prefixes
+            .iter()
+            .filter(|p| path.starts_with(p.as_str()))
+            .max_by_key(|p| p.len())
+            .map(String::as_str)
+    }
+
+    fn hidden(&self, name: &str) -> bool {
+        name.starts_with('.') || self.hide.iter().any(|g| g.matches(name))
+    }
+
+    /// Entries of `dir` (at URL `path`), or `None` if it is not a directory
+    /// to list (it has an index.html, or does not exist). Entries the static
+    /// guard would refuse are left out.
+    async fn read(&self, path: &str, dir: &Path) -> Option<Vec<Entry>> {
+        if tokio::fs::metadata(dir.join("index.html"))
+            .await
+            .is_ok_and(|m| m.is_file())
+        {
+            return None;
+        }
+        let mut rd = tokio::fs::read_dir(dir).await.ok()?;
+        let mut entries = Vec::new();
+        while let Ok(Some(e)) = rd.next_entry().await {
+            // Links are built from the name, so it must be valid UTF-8 to be
+            // served back.
+            let Ok(name) = e.file_name().into_string() else {
+                continue;
+            };
+            if self.hidden(&name)
+                || self.guard.check_path(&format!("{path}{name}")).is_err()
+                || self.guard.check_fs(&e.path()).await.is_err()
+            {
+                continue;
+            }
+            let Ok(meta) = tokio::fs::metadata(e.path()).await else {
+                continue;
+            };
+            entries.push(Entry {
+                name,
+                dir: meta.is_dir(),
+                size: if meta.is_dir() { 0 } else { meta.len() },
+                mtime: meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+            });
+        }
+        Some(entries)
+    }
+}
+
+/// Serve a generated index for directories under a listed prefix that have
+/// no index.html: sortable HTML, or JSON when `Accept` prefers it. Dotfiles
+/// and `hide` patterns are left out.
+pub(crate) async fn listing_mw(
+    State(listing): State<Arc<Listing>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !(req.method() == Method::GET || req.method() == Method::HEAD) {
+        return next.run(req).await;
+    }
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    let Some(prefix) = listing.prefix_of(&path) else {
+        return next.run(req).await;
+    };
+    if !path.ends_with('/') {
+        return next.run(req).await;
+    }
+    let Some(dir) = fs_path(&listing.root, &path) else {
+        return next.run(req).await;
+    };
+    // A refused directory is not listed; the file service then refuses it.
+    if listing.guard.check_path(&path).is_err() || listing.guard.check_fs(&dir).await.is_err() {
+        return next.run(req).await;
+    }
+    let Some(mut entries) = listing.read(&path, &dir).await else {
+        return next.run(req).await;
+    };
+
+    let (key, desc) = sort_params(req.uri().query().unwrap_or(""));
+    entries.sort_by(|a, b| {
+        let ord = match key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Mtime => a.mtime.cmp(&b.mtime),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        // Directories first, whatever the order.
+        b.dir
+            .cmp(&a.dir)
+            .then(if desc { ord.reverse() } else { ord })
+    });
+
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let (content_type, body) = if prefers_json(accept) {
+        ("application/json", render_json(&path, &entries))
+    } else {
+        let parent = path.len() > prefix.len();
+        (
+            "text/html; charset=utf-8",
+            render_html(&path, &entries, parent, key, desc),
+        )
+    };
+    (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (header::VARY, HeaderValue::from_static("accept")),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// `?sort=name|size|mtime&order=asc|desc`; defaults to name, ascending.
+fn sort_params(query: &str) -> (SortKey, bool) {
+    let (mut key, mut desc) = (SortKey::Name, false);
+    for pair in query.split('&') {
+        match pair.split_once('=') {
+            Some(("sort", "size")) => key = SortKey::Size,
+            Some(("sort", "mtime")) => key = SortKey::Mtime,
+            Some(("sort", "name")) => key = SortKey::Name,
+            Some(("order", "desc")) => desc = true,
+            Some(("order", "asc")) => desc = false,
+            _ => {}
+        }
+    }
+    (key, desc)
+}
+
+fn render_json(path: &str, entries: &[Entry]) -> String {
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|e| {
+            serde_json::json!({
+                "name": e.name,
+                "type": if e.dir { "dir" } else { "file" },
+                "size": e.size,
+                "mtime": e.mtime.map(rfc3339),
+            })
+        })
+        .collect();
+    serde_json::json!({ "path": path, "entries": entries }).to_string()
+}
+
+fn render_html(path: &str, entries: &[Entry], parent: bool, key: SortKey, desc: bool) -> String {
+    let title = format!("Index of {}", html_escape(path));
+    // Clicking the current sort column flips the order.
+    let th = |k: SortKey, label: &str| {
+        let order = if k == key && !desc { "desc" } else { "asc" };
+        let mark = match (k == key, desc) {
+            (true, false) => " ▲",
+            (true, true) => " ▼",
+            (false, _) => "",
+        };
+        format!(
+            r#"<th><a href="?sort={}&amp;order={order}">{label}</a>{mark}</th>"#,
+            k.as_str()
+        )
+    };
+
+    let mut rows = String::new();
+    if parent {
+        rows.push_str("    <tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for e in entries {
+        let slash = if e.dir { "/" } else { "" };
+        let href = utf8_percent_encode(&e.name, SEGMENT);
+        let size = if e.dir {
+            "-".to_string()
+        } else {
+            human_size(e.size)
+        };
+        let mtime = e
+            .mtime
+            .map(|t| rfc3339(t).replace('T', " ").replace('Z', ""))
+            .unwrap_or_default();
+        rows.push_str(&format!(
+            "    <tr><td><a href=\"{href}{slash}\">{}{slash}</a></td><td title=\"{}\">{size}</td><td>{mtime}</td></tr>\n",
+            html_escape(&e.name),
+            e.size,
+        ));
+    }
+
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>{title}</title>
+  <style>
+    body{{font-family:system-ui,sans-serif;margin:32px}}
+    table{{border-collapse:collapse}}
+    th,td{{text-align:left;padding:2px 16px 2px 0}}
+    td:nth-child(2){{text-align:right}}
+  </style>
+</head>
+<body>
+  <h1>{title}</h1>
+  <table>
+    <tr>{}{}{}</tr>
+{rows}  </table>
+</body>
+</html>
+"#,
+        th(SortKey::Name, "Name"),
+        th(SortKey::Size, "Size"),
+        th(SortKey::Mtime, "Modified (UTC)"),
+    )
+}
+
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn human_size(n: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = n as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{n} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// `secs` since the Unix epoch as "YYYY-MM-DDTHH:MM:SSZ".
+fn rfc3339(secs: u64) -> String {
+    // Days to civil date, from Howard Hinnant's `civil_from_days`.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let rem = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, BodyDataStream, HttpBody},
+    http::{header, uri::PathAndQuery, HeaderValue, Request, Response, Uri},
+    BoxError,
+};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use hyper::client::conn::http1::{self, SendRequest};
+use hyper_util::rt::TokioIo;
+use luciuz_config::ProxyProtocolVersion;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::proxy_protocol;
+
+/// Idle connections kept per (target, client).
+const MAX_IDLE_PER_CLIENT: usize = 4;
+/// Distinct (target, client) keys with idle connections.
+const MAX_IDLE_CLIENTS: usize = 1024;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error of one upstream attempt, whichever client sent it.
+#[derive(Debug)]
+pub(crate) enum UpstreamError {
+    Client(reqwest::Error),
+    Connect(io::Error),
+    Http(BoxError),
+    Timeout,
+}
+
+impl UpstreamError {
+    /// The target could not be reached (the request was not sent).
+    pub(crate) fn is_connect(&self) -> bool {
+        match self {
+            Self::Client(err) => err.is_connect(),
+            Self::Connect(_) => true,
+            Self::Http(_) | Self::Timeout => false,
+        }
+    }
+}
+
+impl std::fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Client(err) => write!(f, "{err}"),
+            Self::Connect(err) => write!(f, "connect: {err}"),
+            Self::Http(err) => write!(f, "http: {err}"),
+            Self::Timeout => write!(f, "timed out"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for UpstreamError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Client(err)
+    }
+}
+
+/// HTTP/1.1 client for targets expecting a PROXY protocol header.
+///
+/// The header describes a single client, so connections cannot be shared the
+/// way reqwest pools them: idle connections are keyed by target and client
+/// address and only reused for that same client.
+#[derive(Debug)]
+pub(crate) struct ProxyProtocolClient {
+    version: u8,
+    idle: Arc<Mutex<IdleConns>>,
+}
+
+#[derive(Debug)]
+struct IdleConns {
+    conns: HashMap<Key, Vec<Idle>>,
+    last_sweep: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    authority: String,
+    source: Option<SocketAddr>,
+    destination: Option<SocketAddr>,
+}
+
+#[derive(Debug)]
+struct Idle {
+    sender: SendRequest<reqwest::Body>,
+    since: Instant,
+}
+
+impl Idle {
+    fn usable(&self) -> bool {
+        !self.sender.is_closed() && self.since.elapsed() < IDLE_TIMEOUT
+    }
+}
+
+impl ProxyProtocolClient {
+    pub(crate) fn new(version: ProxyProtocolVersion) -> Self {
+        Self {
+            version: match version {
+                ProxyProtocolVersion::V1 => 1,
+                ProxyProtocolVersion::V2 => 2,
+            },
+            idle: Arc::new(Mutex::new(IdleConns {
+                conns: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+
+    /// Send `req` for the client at `source`. `destination` is the address the
+    /// client connected to, when known (e.g. from an inbound PROXY header);
+    /// otherwise our local address on the upstream connection is announced.
+    pub(crate) async fn execute(
+        &self,
+        req: reqwest::Request,
+        source: Option<SocketAddr>,
+        destination: Option<SocketAddr>,
+    ) -> Result<reqwest::Response, UpstreamError> {
+        let retry = req.try_clone();
+        let (req, authority) = prepare(req)?;
+        let key = Key {
+            authority,
+            source,
+            destination,
+        };
+
+        let (mut sender, reused) = match self.checkout(&key).await {
+            Some(sender) => (sender, true),
+            None => (self.connect(&key).await?, false),
+        };
+
+        let resp = match sender.send_request(req).await {
+            Ok(resp) => resp,
+            // The upstream may close an idle connection just as we reuse it:
+            // retry once on a fresh one.
+            Err(err) if reused => {
+                debug!(?err, "idle upstream connection failed, reconnecting");
+                let (req, _) = prepare(retry.ok_or(UpstreamError::Http(err.into()))?)?;
+                sender = self.connect(&key).await?;
+                sender
+                    .send_request(req)
+                    .await
+                    .map_err(|e| UpstreamError::Http(e.into()))?
+            }
+            Err(err) => return Err(UpstreamError::Http(err.into())),
+        };
+
+        // The body is streamed; the connection goes back to the idle pool
+        // once it has been read to the end.
+        let (parts, body) = resp.into_parts();
+        let body = Returning {
+            body: Body::new(body).into_data_stream(),
+            conn: Some((self.idle.clone(), key, sender)),
+        };
+        Ok(Response::from_parts(parts, reqwest::Body::wrap_stream(body)).into())
+    }
+
+    async fn connect(&self, key: &Key) -> Result<SendRequest<reqwest::Body>, UpstreamError> {
+        let mut stream = TcpStream::connect(&key.authority)
+            .await
+            .map_err(UpstreamError::Connect)?;
+        let destination = key.destination.or_else(|| stream.local_addr().ok());
+        let header = proxy_protocol::encode(self.version, key.source.zip(destination));
+        stream
+            .write_all(&header)
+            .await
+            .map_err(UpstreamError::Connect)?;
+
+        let (sender, conn) = http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| UpstreamError::Http(e.into()))?;
+        tokio::spawn(async move {
+            if let Err(err) = conn.await {
+                debug!(?err, "upstream connection closed");
+            }
+        });
+        Ok(sender)
+    }
+
+    async fn checkout(&self, key: &Key) -> Option<SendRequest<reqwest::Body>> {
+        loop {
+            let idle = {
+                let mut idle = self.idle.lock().unwrap();
+                let list = idle.conns.get_mut(key)?;
+                let found = list.pop();
+                if list.is_empty() {
+                    idle.conns.remove(key);
+                }
+                found?
+            };
+            if !idle.usable() {
+                continue;
+            }
+            let mut sender = idle.sender;
+            if sender.ready().await.is_ok() {
+                return Some(sender);
+            }
+        }
+    }
+}
+
+/// An upstream response body that hands its connection back to the idle pool
+/// once read to the end, which may be before its last poll: a server writing
+/// a `Content-Length` body stops once it has the announced length. Dropped
+/// before that, the connection, with unread
+/// data on it, is closed along with it.
+struct Returning {
+    body: BodyDataStream,
+    conn: Option<(Arc<Mutex<IdleConns>>, Key, SendRequest<reqwest::Body>)>,
+}
+
+impl Stream for Returning {
+    type Item = Result<Bytes, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = ready!(self.body.poll_next_unpin(cx));
+        if item.is_none() || self.body.is_end_stream() {
+            if let Some((idle, key, sender)) = self.conn.take() {
+                checkin(&idle, key, sender);
+            }
+        }
+        Poll::Ready(item)
+    }
+}
+
+fn checkin(idle: &Mutex<IdleConns>, key: Key, sender: SendRequest<reqwest::Body>) {
+    if sender.is_closed() {
+        return;
+    }
+    let mut idle = idle.lock().unwrap();
+    let full =
+        |idle: &IdleConns| idle.conns.len() >= MAX_IDLE_CLIENTS && !idle.conns.contains_key(&key);
+    if full(&idle) || idle.last_sweep.elapsed() >= IDLE_TIMEOUT {
+        idle.conns.retain(|_, list| {
+            list.retain(Idle::usable);
+            !list.is_empty()
+        });
+        idle.last_sweep = Instant::now();
+        if full(&idle) {
+            return;
+        }
+    }
+    let list = idle.conns.entry(key).or_default();
+    if list.len() < MAX_IDLE_PER_CLIENT {
+        list.push(Idle {
+            sender,
+            since: Instant::now(),
+        });
+    }
+}
+
+/// Convert to an origin-form HTTP/1.1 request, returning it with the
+/// "host:port" to connect to.
+fn prepare(req: reqwest::Request) -> Result<(Request<reqwest::Body>, String), UpstreamError> {
+    let mut req: Request<reqwest::Body> = req.try_into()?;
+    let Some(authority) = req.uri().authority().cloned() else {
+        return Err(UpstreamError::Http("upstream URL has no host".into()));
+    };
+    if !req.headers().contains_key(header::HOST) {
+        if let Ok(v) = HeaderValue::from_str(authority.as_str()) {
+            req.headers_mut().insert(header::HOST, v);
+        }
+    }
+    let path = req
+        .uri()
+        .path_and_query()
+        .cloned()
+        .unwrap_or_else(|| PathAndQuery::from_static("/"));
+    *req.uri_mut() = Uri::from(path);
+
+    let addr = match authority.port_u16() {
+        Some(_) => authority.to_string(),
+        None => format!("{}:80", authority.host()),
+    };
+    Ok((req, addr))
+}
@@ -0,0 +1,298 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use luciuz_config::{Affinity, ProxyProtocolVersion, UpstreamPool};
+use ring::{hmac, rand};
+use tracing::warn;
+
+use crate::outbound::ProxyProtocolClient;
+use crate::split::cookie_value;
+
+/// A named set of upstream targets, used round-robin.
+///
+/// Targets that fail to connect are skipped for `unhealthy_for` (passive health
+/// checking). With affinity enabled, a client sticks to one target until that
+/// target becomes unhealthy.
+#[derive(Debug)]
+pub(crate) struct Pool {
+    /// Empty for the implicit pool behind a route's single `upstream`.
+    pub(crate) name: String,
+    targets: Vec<Target>,
+    next: AtomicUsize,
+    affinity: Option<AffinityMode>,
+    epoch: Instant,
+    unhealthy_for: Duration,
+    proxy_protocol: Option<ProxyProtocolClient>,
+}
+
+#[derive(Debug)]
+struct Target {
+    url: String,
+    /// Milliseconds since `Pool::epoch` until which the target is skipped.
+    down_until_ms: AtomicU64,
+}
+
+enum AffinityMode {
+    /// Luciuz-issued cookie: `<index>.<hex hmac(pool, target url)>`.
+    Cookie {
+        name: String,
+        attrs: String,
+        key: hmac::Key,
+    },
+    HashCookie(String),
+    HashHeader(HeaderName),
+}
+
+impl std::fmt::Debug for AffinityMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cookie { name, .. } => write!(f, "Cookie({name})"),
+            Self::HashCookie(name) => write!(f, "HashCookie({name})"),
+            Self::HashHeader(name) => write!(f, "HashHeader({name})"),
+        }
+    }
+}
+
+/// A target chosen for one upstream attempt.
+pub(crate) struct Pick {
+    pub(crate) index: usize,
+    /// Affinity cookie to send back when the client is (re)pinned.
+    pub(crate) set_cookie: Option<HeaderValue>,
+}
+
+impl Pool {
+    pub(crate) fn new(name: &str, cfg: &UpstreamPool) -> anyhow::Result<Self> {
+        let affinity = cfg
+            .affinity
+            .as_ref()
+            .map(|a| affinity_mode(name, a))
+            .transpose()?;
+
+        Ok(Self {
+            name: name.to_string(),
+            targets: cfg.targets.iter().map(|t| Target::new(t)).collect(),
+            next: AtomicUsize::new(0),
+            affinity,
+            epoch: Instant::now(),
+            unhealthy_for: cfg.unhealthy_for,
+            proxy_protocol: cfg.send_proxy_protocol.map(ProxyProtocolClient::new),
+        })
+    }
+
+    /// Implicit pool for a route with a single `upstream`.
+    pub(crate) fn single(url: &str, send_proxy_protocol: Option<ProxyProtocolVersion>) -> Self {
+        Self {
+            name: String::new(),
+            targets: vec![Target::new(url)],
+            next: AtomicUsize::new(0),
+            affinity: None,
+            epoch: Instant::now(),
+            unhealthy_for: Duration::ZERO,
+            proxy_protocol: send_proxy_protocol.map(ProxyProtocolClient::new),
+        }
+    }
+
+    pub(crate) fn url(&self, index: usize) -> &str {
+        &self.targets[index].url
+    }
+
+    /// Set when the targets expect a PROXY protocol header.
+    pub(crate) fn proxy_protocol(&self) -> Option<&ProxyProtocolClient> {
+        self.proxy_protocol.as_ref()
+    }
+
+    pub(crate) fn pick(&self, headers: &HeaderMap) -> Pick {
+        let pinned = match &self.affinity {
+            None => None,
+            Some(AffinityMode::Cookie { name, key, .. }) => cookie_value(headers, name)
+                .and_then(|v| self.verify_cookie(key, v))
+                .filter(|i| self.is_up(*i)),
+            Some(AffinityMode::HashCookie(name)) => {
+                cookie_value(headers, name).and_then(|v| self.hashed(v))
+            }
+            Some(AffinityMode::HashHeader(name)) => headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| self.hashed(v)),
+        };
+
+        match pinned {
+            Some(index) => Pick {
+                index,
+                set_cookie: None,
+            },
+            None => self.pin(self.round_robin(None)),
+        }
+    }
+
+    /// Mark `failed` unhealthy and pick another target, if the pool has one.
+    pub(crate) fn failover(&self, failed: usize) -> Option<Pick> {
+        self.mark_down(failed);
+        if self.targets.len() < 2 {
+            return None;
+        }
+        Some(self.pin(self.round_robin(Some(failed))))
+    }
+
+    fn pin(&self, index: usize) -> Pick {
+        let set_cookie = match &self.affinity {
+            Some(AffinityMode::Cookie { name, attrs, key }) => {
+                let tag = hmac::sign(key, self.mac_input(index).as_bytes());
+                HeaderValue::from_str(&format!("{name}={index}.{}{attrs}", to_hex(tag.as_ref())))
+                    .ok()
+            }
+            _ => None,
+        };
+        Pick { index, set_cookie }
+    }
+
+    /// Next healthy target in round-robin order. Falls back to any target
+    /// (other than `exclude`) when all of them are marked down.
+    fn round_robin(&self, exclude: Option<usize>) -> usize {
+        let n = self.targets.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let candidates = (0..n)
+            .map(|k| (start + k) % n)
+            .filter(|i| Some(*i) != exclude);
+        let mut fallback = None;
+        for i in candidates {
+            if self.is_up(i) {
+                return i;
+            }
+            fallback.get_or_insert(i);
+        }
+        fallback.unwrap_or(start % n)
+    }
+
+    /// Consistent choice for a hash key, skipping targets that are down.
+    fn hashed(&self, key: &str) -> Option<usize> {
+        let n = self.targets.len();
+        let mut h = DefaultHasher::new();
+        key.hash(&mut h);
+        let start = (h.finish() % n as u64) as usize;
+        (0..n).map(|k| (start + k) % n).find(|i| self.is_up(*i))
+    }
+
+    fn verify_cookie(&self, key: &hmac::Key, value: &str) -> Option<usize> {
+        let (index, tag) = value.split_once('.')?;
+        let index: usize = index.parse().ok()?;
+        if index >= self.targets.len() {
+            return None;
+        }
+        let tag = from_hex(tag)?;
+        hmac::verify(key, self.mac_input(index).as_bytes(), &tag).ok()?;
+        Some(index)
+    }
+
+    /// The cookie is bound to the pool and the target URL, so it stops
+    /// verifying when the target list is reordered or changed.
+    fn mac_input(&self, index: usize) -> String {
+        format!("{}\n{}", self.name, self.targets[index].url)
+    }
+
+    fn is_up(&self, index: usize) -> bool {
+        let until = self.targets[index].down_until_ms.load(Ordering::Relaxed);
+        until == 0 || self.now_ms() >= until
+    }
+
+    fn mark_down(&self, index: usize) {
+        if self.unhealthy_for.is_zero() {
+            return;
+        }
+        let until = self.now_ms() + self.unhealthy_for.as_millis() as u64;
+        self.targets[index]
+            .down_until_ms
+            .store(until, Ordering::Relaxed);
+        warn!(
+            pool = %self.name,
+            target = %self.targets[index].url,
+            secs = self.unhealthy_for.as_secs(),
+            "upstream target marked unhealthy"
+        );
+    }
+
+    fn now_ms(&self) -> u64 {
+        // +1 keeps 0 free to mean "never marked down".
+        self.epoch.elapsed().as_millis() as u64 + 1
+    }
+}
+
+impl Target {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            down_until_ms: AtomicU64::new(0),
+        }
+    }
+}
+
+fn affinity_mode(pool: &str, a: &Affinity) -> anyhow::Result<AffinityMode> {
+    Ok(match a.mode {
+        luciuz_config::AffinityMode::Cookie => {
+            let secret = match &a.secret {
+                Some(s) => s.as_bytes().to_vec(),
+                None => {
+                    warn!(
+                        pool = %pool,
+                        "affinity.secret not set; using a random key kept across reloads \
+                         (pins reset on restart)"
+                    );
+                    process_key()?.to_vec()
+                }
+            };
+
+            let mut attrs = String::from("; Path=/");
+            if !a.ttl.is_zero() {
+                attrs.push_str(&format!("; Max-Age={}", a.ttl.as_secs()));
+            }
+            if a.http_only {
+                attrs.push_str("; HttpOnly");
+            }
+            if a.secure {
+                attrs.push_str("; Secure");
+            }
+            attrs.push_str(&format!("; SameSite={}", a.same_site));
+
+            AffinityMode::Cookie {
+                name: a.name.clone(),
+                attrs,
+                key: hmac::Key::new(hmac::HMAC_SHA256, &secret),
+            }
+        }
+        luciuz_config::AffinityMode::HashCookie => AffinityMode::HashCookie(a.name.clone()),
+        luciuz_config::AffinityMode::HashHeader => {
+            AffinityMode::HashHeader(HeaderName::try_from(a.name.as_str())?)
+        }
+    })
+}
+
+/// Random affinity key for pools without `affinity.secret`, generated once
+/// per process so that reloads, which rebuild the pools, keep existing pins.
+fn process_key() -> anyhow::Result<&'static [u8; 32]> {
+    static KEY: OnceLock<[u8; 32]> = OnceLock::new();
+    if let Some(key) = KEY.get() {
+        return Ok(key);
+    }
+    let mut buf = [0u8; 32];
+    rand::SecureRandom::fill(&rand::SystemRandom::new(), &mut buf)
+        .map_err(|_| anyhow::anyhow!("failed to generate affinity key"))?;
+    Ok(KEY.get_or_init(|| buf))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
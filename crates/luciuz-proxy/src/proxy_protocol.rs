@@ -0,0 +1,543 @@
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::http::Request;
+use axum_server::accept::Accept;
+use luciuz_config::{any_contains, Cidr, Config};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::net::TcpStream;
+use tower::Service;
+use tracing::warn;
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// Longest valid v1 header, CRLF included.
+const V1_MAX_LEN: usize = 107;
+
+const PP2_TYPE_ALPN: u8 = 0x01;
+const PP2_TYPE_AUTHORITY: u8 = 0x02;
+const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+const PP2_TYPE_SSL: u8 = 0x20;
+const PP2_SUBTYPE_SSL_VERSION: u8 = 0x21;
+const PP2_SUBTYPE_SSL_CN: u8 = 0x22;
+const PP2_SUBTYPE_SSL_CIPHER: u8 = 0x23;
+const PP2_CLIENT_SSL: u8 = 0x01;
+
+/// Decoded PROXY protocol header, stored in request extensions for every
+/// request of a connection that carried one.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyHeader {
+    /// 1 or 2.
+    pub version: u8,
+    /// Original client address (None for `LOCAL` / `UNKNOWN` connections,
+    /// e.g. load balancer health checks).
+    pub source: Option<SocketAddr>,
+    /// Address the client connected to on the load balancer.
+    pub destination: Option<SocketAddr>,
+    /// ALPN negotiated by the load balancer (v2 only).
+    pub alpn: Option<String>,
+    /// Original TLS SNI / host name (v2 only).
+    pub authority: Option<String>,
+    /// Connection id assigned by the load balancer (v2 only).
+    pub unique_id: Option<Vec<u8>>,
+    /// TLS information when the load balancer terminated TLS (v2 only).
+    pub ssl: Option<ProxySsl>,
+    /// All v2 TLVs as (type, value), including vendor-specific ones.
+    pub tlvs: Vec<(u8, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProxySsl {
+    /// The client connected over TLS.
+    pub client_ssl: bool,
+    /// The client presented a certificate that was verified.
+    pub verified: bool,
+    pub version: Option<String>,
+    pub cipher: Option<String>,
+    /// Common name of the client certificate.
+    pub cn: Option<String>,
+}
+
+#[derive(Debug)]
+struct Settings {
+    allowed_sources: Vec<Cidr>,
+    header_timeout: Duration,
+}
+
+/// axum-server acceptor reading a PROXY protocol header before handing the
+/// connection to `inner` (e.g. the TLS acceptor).
+///
+/// The header is only read from `allowed_sources`; other peers are served as
+/// direct connections. Disabled listeners pass connections through untouched.
+#[derive(Debug, Clone)]
+pub struct ProxyProtocolAcceptor<A> {
+    inner: A,
+    settings: Option<Arc<Settings>>,
+}
+
+impl<A> ProxyProtocolAcceptor<A> {
+    /// `listener` is "http" or "https" (see `server.proxy_protocol.listeners`).
+    pub fn new(inner: A, cfg: &Config, listener: &str) -> anyhow::Result<Self> {
+        let settings = match &cfg.server.proxy_protocol {
+            Some(pp) if pp.listeners.iter().any(|l| l.as_str() == listener) => {
+                Some(Arc::new(Settings {
+                    allowed_sources: pp.allowed_sources.clone(),
+                    header_timeout: pp.header_timeout,
+                }))
+            }
+            _ => None,
+        };
+        Ok(Self { inner, settings })
+    }
+}
+
+impl<A, S> Accept<TcpStream, S> for ProxyProtocolAcceptor<A>
+where
+    A: Accept<TcpStream, WithProxyHeader<S>> + Clone + Send + Sync + 'static,
+    A::Future: Send,
+    S: Send + 'static,
+{
+    type Stream = A::Stream;
+    type Service = A::Service;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(A::Stream, A::Service)>> + Send>>;
+
+    fn accept(&self, mut stream: TcpStream, service: S) -> Self::Future {
+        let inner = self.inner.clone();
+        let settings = self.settings.clone();
+
+        Box::pin(async move {
+            let header = match settings {
+                Some(s) => {
+                    let peer = stream.peer_addr()?;
+                    if any_contains(&s.allowed_sources, peer.ip()) {
+                        let read = tokio::time::timeout(s.header_timeout, read_header(&mut stream));
+                        match read.await {
+                            Ok(Ok(h)) => Some(h),
+                            Ok(Err(err)) => {
+                                warn!(%peer, %err, "rejecting connection: invalid PROXY header");
+                                return Err(err);
+                            }
+                            Err(_) => {
+                                warn!(%peer, "rejecting connection: PROXY header timeout");
+                                return Err(io::ErrorKind::TimedOut.into());
+                            }
+                        }
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            };
+
+            let service = WithProxyHeader {
+                inner: service,
+                header,
+            };
+            inner.accept(stream, service).await
+        })
+    }
+}
+
+/// Per-connection service adding the connection's [`ProxyHeader`] (if any)
+/// to each request.
+#[derive(Debug, Clone)]
+pub struct WithProxyHeader<S> {
+    inner: S,
+    header: Option<ProxyHeader>,
+}
+
+impl<S, B> Service<Request<B>> for WithProxyHeader<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Some(h) = &self.header {
+            req.extensions_mut().insert(h.clone());
+        }
+        self.inner.call(req)
+    }
+}
+
+/// Read a v1 or v2 header, consuming exactly its bytes from `r`.
+pub(crate) async fn read_header<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<ProxyHeader> {
+    let mut start = [0u8; 5];
+    r.read_exact(&mut start).await?;
+
+    if &start == b"PROXY" {
+        // v1: read byte by byte so nothing past the header is consumed.
+        let mut line = start.to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= V1_MAX_LEN {
+                return Err(invalid("v1 header too long"));
+            }
+            line.push(r.read_u8().await?);
+        }
+        let line = std::str::from_utf8(&line).map_err(|_| invalid("v1 header is not ASCII"))?;
+        return parse_v1(line.trim_end());
+    }
+
+    if start == V2_SIGNATURE[..5] {
+        let mut rest = [0u8; 11];
+        r.read_exact(&mut rest).await?;
+        if rest[..7] != V2_SIGNATURE[5..] {
+            return Err(invalid("bad v2 signature"));
+        }
+        let (ver_cmd, fam) = (rest[7], rest[8]);
+        let len = u16::from_be_bytes([rest[9], rest[10]]) as usize;
+        let mut payload = vec![0u8; len];
+        r.read_exact(&mut payload).await?;
+        return parse_v2(ver_cmd, fam, &payload);
+    }
+
+    Err(invalid("missing PROXY header"))
+}
+
+fn parse_v1(line: &str) -> io::Result<ProxyHeader> {
+    let parts: Vec<&str> = line.split(' ').collect();
+    let mut h = ProxyHeader {
+        version: 1,
+        ..Default::default()
+    };
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => {}
+        ["PROXY", fam @ ("TCP4" | "TCP6"), src, dst, sport, dport] => {
+            let ip = |s: &str| -> io::Result<IpAddr> {
+                let ip: IpAddr = s.parse().map_err(|_| invalid("bad v1 address"))?;
+                if ip.is_ipv4() != (*fam == "TCP4") {
+                    return Err(invalid("v1 address family mismatch"));
+                }
+                Ok(ip)
+            };
+            let port = |s: &str| s.parse::<u16>().map_err(|_| invalid("bad v1 port"));
+            h.source = Some(SocketAddr::new(ip(src)?, port(sport)?));
+            h.destination = Some(SocketAddr::new(ip(dst)?, port(dport)?));
+        }
+        _ => return Err(invalid("malformed v1 header")),
+    }
+    Ok(h)
+}
+
+fn parse_v2(ver_cmd: u8, fam: u8, payload: &[u8]) -> io::Result<ProxyHeader> {
+    if ver_cmd >> 4 != 2 {
+        return Err(invalid("unsupported v2 version"));
+    }
+    // LOCAL (0x0) or PROXY (0x1).
+    if ver_cmd & 0x0F > 0x1 {
+        return Err(invalid("unsupported v2 command"));
+    }
+    // UNSPEC, STREAM or DGRAM.
+    if fam & 0x0F > 0x2 {
+        return Err(invalid("unsupported v2 transport"));
+    }
+    let mut h = ProxyHeader {
+        version: 2,
+        ..Default::default()
+    };
+
+    let addr_len = match fam >> 4 {
+        0x0 => 0,
+        0x1 => 12,
+        0x2 => 36,
+        0x3 => 216,
+        _ => return Err(invalid("unsupported v2 address family")),
+    };
+    if payload.len() < addr_len {
+        return Err(invalid("truncated v2 addresses"));
+    }
+    let (addrs, mut tlvs) = payload.split_at(addr_len);
+
+    // Only PROXY (0x1) carries the client address; LOCAL (0x0) is used for the
+    // load balancer's own health checks.
+    if ver_cmd & 0x0F == 0x1 {
+        match fam >> 4 {
+            0x1 => {
+                let ip = |o: usize| {
+                    IpAddr::V4(Ipv4Addr::new(
+                        addrs[o],
+                        addrs[o + 1],
+                        addrs[o + 2],
+                        addrs[o + 3],
+                    ))
+                };
+                let port = |o: usize| u16::from_be_bytes([addrs[o], addrs[o + 1]]);
+                h.source = Some(SocketAddr::new(ip(0), port(8)));
+                h.destination = Some(SocketAddr::new(ip(4), port(10)));
+            }
+            0x2 => {
+                let ip = |o: usize| {
+                    let b: [u8; 16] = addrs[o..o + 16].try_into().expect("16 bytes");
+                    IpAddr::V6(Ipv6Addr::from(b))
+                };
+                let port = |o: usize| u16::from_be_bytes([addrs[o], addrs[o + 1]]);
+                h.source = Some(SocketAddr::new(ip(0), port(32)));
+                h.destination = Some(SocketAddr::new(ip(16), port(34)));
+            }
+            _ => {}
+        }
+    }
+
+    while !tlvs.is_empty() {
+        let (kind, value, rest) = split_tlv(tlvs)?;
+        tlvs = rest;
+        match kind {
+            PP2_TYPE_ALPN => h.alpn = Some(String::from_utf8_lossy(value).into_owned()),
+            PP2_TYPE_AUTHORITY => h.authority = Some(String::from_utf8_lossy(value).into_owned()),
+            PP2_TYPE_UNIQUE_ID => h.unique_id = Some(value.to_vec()),
+            PP2_TYPE_SSL => h.ssl = Some(parse_ssl(value)?),
+            _ => {}
+        }
+        h.tlvs.push((kind, value.to_vec()));
+    }
+
+    Ok(h)
+}
+
+fn parse_ssl(value: &[u8]) -> io::Result<ProxySsl> {
+    if value.len() < 5 {
+        return Err(invalid("truncated v2 SSL TLV"));
+    }
+    let mut ssl = ProxySsl {
+        client_ssl: value[0] & PP2_CLIENT_SSL != 0,
+        verified: u32::from_be_bytes([value[1], value[2], value[3], value[4]]) == 0,
+        ..Default::default()
+    };
+    let mut sub = &value[5..];
+    while !sub.is_empty() {
+        let (kind, v, rest) = split_tlv(sub)?;
+        sub = rest;
+        let v = Some(String::from_utf8_lossy(v).into_owned());
+        match kind {
+            PP2_SUBTYPE_SSL_VERSION => ssl.version = v,
+            PP2_SUBTYPE_SSL_CN => ssl.cn = v,
+            PP2_SUBTYPE_SSL_CIPHER => ssl.cipher = v,
+            _ => {}
+        }
+    }
+    Ok(ssl)
+}
+
+fn split_tlv(buf: &[u8]) -> io::Result<(u8, &[u8], &[u8])> {
+    if buf.len() < 3 {
+        return Err(invalid("truncated v2 TLV"));
+    }
+    let len = u16::from_be_bytes([buf[1], buf[2]]) as usize;
+    let body = &buf[3..];
+    if body.len() < len {
+        return Err(invalid("truncated v2 TLV"));
+    }
+    Ok((buf[0], &body[..len], &body[len..]))
+}
+
+/// Encode a header for an outbound connection: `PROXY` with the client
+/// `(source, destination)`, or `UNKNOWN` (v1) / `LOCAL` (v2) without one.
+pub(crate) fn encode(version: u8, addrs: Option<(SocketAddr, SocketAddr)>) -> Vec<u8> {
+    // Both addresses must share a family; mix v4 and v6 as v4-mapped v6.
+    let addrs = addrs.map(|(src, dst)| match (src, dst) {
+        (SocketAddr::V4(_), SocketAddr::V6(_)) => (to_v6(src), dst),
+        (SocketAddr::V6(_), SocketAddr::V4(_)) => (src, to_v6(dst)),
+        _ => (src, dst),
+    });
+
+    if version == 1 {
+        return match addrs {
+            Some((src, dst)) => {
+                let fam = if src.is_ipv4() { "TCP4" } else { "TCP6" };
+                format!(
+                    "PROXY {fam} {} {} {} {}\r\n",
+                    src.ip(),
+                    dst.ip(),
+                    src.port(),
+                    dst.port()
+                )
+                .into_bytes()
+            }
+            None => b"PROXY UNKNOWN\r\n".to_vec(),
+        };
+    }
+
+    let mut out = V2_SIGNATURE.to_vec();
+    let mut payload = Vec::new();
+    let (ver_cmd, fam) = match addrs {
+        Some((SocketAddr::V4(src), SocketAddr::V4(dst))) => {
+            payload.extend_from_slice(&src.ip().octets());
+            payload.extend_from_slice(&dst.ip().octets());
+            payload.extend_from_slice(&src.port().to_be_bytes());
+            payload.extend_from_slice(&dst.port().to_be_bytes());
+            (0x21, 0x11)
+        }
+        Some((SocketAddr::V6(src), SocketAddr::V6(dst))) => {
+            payload.extend_from_slice(&src.ip().octets());
+            payload.extend_from_slice(&dst.ip().octets());
+            payload.extend_from_slice(&src.port().to_be_bytes());
+            payload.extend_from_slice(&dst.port().to_be_bytes());
+            (0x21, 0x21)
+        }
+        _ => (0x20, 0x00),
+    };
+    out.push(ver_cmd);
+    out.push(fam);
+    out.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
+
+fn to_v6(a: SocketAddr) -> SocketAddr {
+    match a.ip() {
+        IpAddr::V4(ip) => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), a.port()),
+        IpAddr::V6(_) => a,
+    }
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(mut bytes: &[u8]) -> io::Result<ProxyHeader> {
+        read_header(&mut bytes).await
+    }
+
+    /// A v2 header with the given command, family and payload.
+    fn v2(ver_cmd: u8, fam: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = V2_SIGNATURE.to_vec();
+        out.extend_from_slice(&[ver_cmd, fam]);
+        out.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn error(res: io::Result<ProxyHeader>) -> String {
+        res.expect_err("header accepted").to_string()
+    }
+
+    const V4_ADDRS: [u8; 12] = [192, 0, 2, 1, 10, 0, 0, 1, 0x1F, 0x90, 0x01, 0xBB];
+
+    #[tokio::test]
+    async fn encoded_headers_read_back() {
+        let v4: (SocketAddr, SocketAddr) = (
+            "192.0.2.1:8080".parse().unwrap(),
+            "10.0.0.1:443".parse().unwrap(),
+        );
+        let v6: (SocketAddr, SocketAddr) = (
+            "[2001:db8::1]:8080".parse().unwrap(),
+            "[2001:db8::2]:443".parse().unwrap(),
+        );
+        for version in [1, 2] {
+            for addrs in [v4, v6] {
+                let h = read(&encode(version, Some(addrs))).await.unwrap();
+                assert_eq!(h.version, version);
+                assert_eq!((h.source, h.destination), (Some(addrs.0), Some(addrs.1)));
+            }
+            let h = read(&encode(version, None)).await.unwrap();
+            assert_eq!((h.source, h.destination), (None, None));
+        }
+    }
+
+    #[tokio::test]
+    async fn nothing_past_the_header_is_consumed() {
+        let mut bytes = encode(2, None);
+        bytes.extend_from_slice(b"GET / HTTP/1.1\r\n");
+        let mut r = &bytes[..];
+        read_header(&mut r).await.unwrap();
+        assert_eq!(r, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn a_v1_line_is_bounded() {
+        let long = format!("PROXY TCP4 {} 10.0.0.1 8080 443\r\n", "1".repeat(100));
+        assert_eq!(error(read(long.as_bytes()).await), "v1 header too long");
+        let mixed = b"PROXY TCP4 2001:db8::1 10.0.0.1 8080 443\r\n";
+        assert_eq!(error(read(mixed).await), "v1 address family mismatch");
+    }
+
+    #[tokio::test]
+    async fn unknown_commands_and_families_are_refused() {
+        assert_eq!(
+            error(read(&v2(0x22, 0x11, &V4_ADDRS)).await),
+            "unsupported v2 command"
+        );
+        assert_eq!(
+            error(read(&v2(0x21, 0x41, &V4_ADDRS)).await),
+            "unsupported v2 address family"
+        );
+        assert_eq!(
+            error(read(&v2(0x31, 0x11, &V4_ADDRS)).await),
+            "unsupported v2 version"
+        );
+    }
+
+    #[tokio::test]
+    async fn truncated_addresses_are_refused() {
+        assert_eq!(
+            error(read(&v2(0x21, 0x11, &V4_ADDRS[..8])).await),
+            "truncated v2 addresses"
+        );
+        // INET6 announced, INET addresses sent.
+        assert_eq!(
+            error(read(&v2(0x21, 0x21, &V4_ADDRS)).await),
+            "truncated v2 addresses"
+        );
+        // The length announces more than the connection sends.
+        let mut short = v2(0x21, 0x11, &V4_ADDRS);
+        short.truncate(short.len() - 2);
+        assert_eq!(
+            read(&short).await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[tokio::test]
+    async fn tlvs_are_read_and_bounded() {
+        let mut payload = V4_ADDRS.to_vec();
+        payload.extend_from_slice(&[PP2_TYPE_AUTHORITY, 0, 11]);
+        payload.extend_from_slice(b"example.com");
+        payload.extend_from_slice(&[PP2_TYPE_SSL, 0, 9, PP2_CLIENT_SSL, 0, 0, 0, 0]);
+        payload.extend_from_slice(&[PP2_SUBTYPE_SSL_CN, 0, 1, b'x']);
+        let h = read(&v2(0x21, 0x11, &payload)).await.unwrap();
+        assert_eq!(h.authority.as_deref(), Some("example.com"));
+        let ssl = h.ssl.unwrap();
+        assert!(ssl.client_ssl && ssl.verified);
+        assert_eq!(ssl.cn.as_deref(), Some("x"));
+        assert_eq!(h.tlvs.len(), 2);
+
+        let mut overrun = V4_ADDRS.to_vec();
+        overrun.extend_from_slice(&[PP2_TYPE_AUTHORITY, 0, 12, b'a']);
+        assert_eq!(
+            error(read(&v2(0x21, 0x11, &overrun)).await),
+            "truncated v2 TLV"
+        );
+        let mut stub = V4_ADDRS.to_vec();
+        stub.extend_from_slice(&[PP2_TYPE_ALPN, 0]);
+        assert_eq!(
+            error(read(&v2(0x21, 0x11, &stub)).await),
+            "truncated v2 TLV"
+        );
+        let mut ssl = V4_ADDRS.to_vec();
+        ssl.extend_from_slice(&[PP2_TYPE_SSL, 0, 2, PP2_CLIENT_SSL, 0]);
+        assert_eq!(
+            error(read(&v2(0x21, 0x11, &ssl)).await),
+            "truncated v2 SSL TLV"
+        );
+    }
+
+    #[tokio::test]
+    async fn local_ignores_addresses() {
+        let h = read(&v2(0x20, 0x11, &V4_ADDRS)).await.unwrap();
+        assert_eq!((h.source, h.destination), (None, None));
+    }
+}
@@ -0,0 +1,58 @@
+use axum::{
+    body::Body,
+    http::{HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::ClientInfo;
+
+pub(crate) const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Request identifier, stored in request extensions by [`request_id_mw`].
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Assign a request id, forwarded upstream and returned as `x-request-id`.
+///
+/// An incoming `x-request-id` is kept only from trusted proxies, so ids in
+/// logs can be correlated across hops but not forged by clients. Must run
+/// inside [`crate::client_ip_mw`].
+pub async fn request_id_mw(mut req: Request<Body>, next: Next) -> Response {
+    let trusted = req
+        .extensions()
+        .get::<ClientInfo>()
+        .is_some_and(|c| c.trusted_peer);
+    let incoming = req
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| trusted && is_valid(v))
+        .map(str::to_string);
+    let id = incoming.unwrap_or_else(generate);
+
+    // Valid by construction (checked above, or hex).
+    let value = HeaderValue::from_str(&id).expect("request id is a valid header value");
+    req.headers_mut().insert(X_REQUEST_ID, value.clone());
+    req.extensions_mut().insert(RequestId(id));
+
+    let mut res = next.run(req).await;
+    res.headers_mut().insert(X_REQUEST_ID, value);
+    res
+}
+
+fn is_valid(v: &str) -> bool {
+    !v.is_empty()
+        && v.len() <= 128
+        && v.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+pub(crate) fn generate() -> String {
+    let mut buf = [0u8; 16];
+    // SystemRandom only fails if the OS RNG is unavailable; an all-zero id
+    // is still a usable (if not unique) value then.
+    let _ = SystemRandom::new().fill(&mut buf);
+    buf.iter().map(|b| format!("{b:02x}")).collect()
+}
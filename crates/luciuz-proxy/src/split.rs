@@ -0,0 +1,198 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use luciuz_config::{SplitOverride, TrafficSplit};
+
+use crate::pool::Pool;
+
+#[derive(Debug)]
+enum Matcher {
+    Header(HeaderName),
+    Cookie(String),
+    Query(String),
+}
+
+#[derive(Debug)]
+struct Override {
+    matcher: Matcher,
+    value: Option<String>,
+    /// Index into `Split::targets`.
+    target: usize,
+}
+
+#[derive(Debug)]
+struct Sticky {
+    cookie: String,
+    ttl_secs: u64,
+}
+
+/// Weighted traffic split across pools, with header/cookie/query overrides
+/// and optional cookie stickiness.
+#[derive(Debug)]
+pub(crate) struct Split {
+    targets: Vec<(Arc<Pool>, u32)>,
+    total_weight: u64,
+    overrides: Vec<Override>,
+    sticky: Option<Sticky>,
+}
+
+/// Outcome of a split decision: the chosen pool and, when stickiness is on and
+/// the client is not pinned yet, the cookie that pins it.
+pub(crate) struct Choice<'a> {
+    pub(crate) pool: &'a Arc<Pool>,
+    pub(crate) set_cookie: Option<HeaderValue>,
+}
+
+impl Split {
+    pub(crate) fn new(
+        cfg: &TrafficSplit,
+        lookup: impl Fn(&str) -> anyhow::Result<Arc<Pool>>,
+    ) -> anyhow::Result<Self> {
+        let mut targets = Vec::with_capacity(cfg.targets.len());
+        for t in &cfg.targets {
+            targets.push((lookup(&t.pool)?, t.weight));
+        }
+
+        let overrides = cfg
+            .overrides
+            .iter()
+            .map(|o| build_override(o, &targets))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            total_weight: targets.iter().map(|(_, w)| u64::from(*w)).sum(),
+            targets,
+            overrides,
+            sticky: cfg.sticky_cookie.as_ref().map(|c| Sticky {
+                cookie: c.clone(),
+                ttl_secs: cfg.sticky_ttl.as_secs(),
+            }),
+        })
+    }
+
+    /// `secure`: the client connected over TLS, so the pin cookie can be
+    /// marked `Secure` (browsers would not send it back over plain HTTP).
+    pub(crate) fn choose(
+        &self,
+        headers: &HeaderMap,
+        query: Option<&str>,
+        path: &str,
+        secure: bool,
+    ) -> Choice<'_> {
+        for o in &self.overrides {
+            let found = match &o.matcher {
+                Matcher::Header(name) => headers.get(name).and_then(|v| v.to_str().ok()),
+                Matcher::Cookie(name) => cookie_value(headers, name),
+                Matcher::Query(name) => query_value(query, name),
+            };
+            let hit = match (found, o.value.as_deref()) {
+                (Some(v), Some(want)) => v == want,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if hit {
+                return Choice {
+                    pool: &self.targets[o.target].0,
+                    set_cookie: None,
+                };
+            }
+        }
+
+        if let Some(sticky) = &self.sticky {
+            // Only honour the pin while the pool still receives traffic, so that
+            // setting a weight to 0 (rollback) drains pinned clients too.
+            let pinned = cookie_value(headers, &sticky.cookie).and_then(|v| {
+                self.targets
+                    .iter()
+                    .find(|(p, w)| *w > 0 && p.name == v)
+                    .map(|(p, _)| p)
+            });
+            if let Some(pool) = pinned {
+                return Choice {
+                    pool,
+                    set_cookie: None,
+                };
+            }
+        }
+
+        let pool = self.weighted();
+        let set_cookie = self.sticky.as_ref().and_then(|s| {
+            let path = if path.is_empty() { "/" } else { path };
+            let secure = if secure { "; Secure" } else { "" };
+            HeaderValue::from_str(&format!(
+                "{}={}; Path={path}; Max-Age={}; HttpOnly{secure}; SameSite=Lax",
+                s.cookie, pool.name, s.ttl_secs
+            ))
+            .ok()
+        });
+
+        Choice { pool, set_cookie }
+    }
+
+    fn weighted(&self) -> &Arc<Pool> {
+        let mut r = random_u64() % self.total_weight;
+        for (pool, w) in &self.targets {
+            let w = u64::from(*w);
+            if r < w {
+                return pool;
+            }
+            r -= w;
+        }
+        // Unreachable while total_weight > 0 (enforced by config validation).
+        &self.targets[0].0
+    }
+}
+
+fn build_override(o: &SplitOverride, targets: &[(Arc<Pool>, u32)]) -> anyhow::Result<Override> {
+    let matcher = match (&o.header, &o.cookie, &o.query) {
+        (Some(h), None, None) => Matcher::Header(HeaderName::try_from(h.as_str())?),
+        (None, Some(c), None) => Matcher::Cookie(c.clone()),
+        (None, None, Some(q)) => Matcher::Query(q.clone()),
+        _ => anyhow::bail!("split override needs exactly one of header, cookie or query"),
+    };
+    let target = targets
+        .iter()
+        .position(|(p, _)| p.name == o.pool)
+        .ok_or_else(|| anyhow::anyhow!("split override pool {} is not a split target", o.pool))?;
+
+    Ok(Override {
+        matcher,
+        value: o.value.clone(),
+        target,
+    })
+}
+
+/// Value of the first cookie named `name` across all `Cookie` headers.
+pub(crate) fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|kv| kv.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+/// Raw (not percent-decoded) value of the first query parameter named `name`.
+fn query_value<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .map(|kv| kv.split_once('=').unwrap_or((kv, "")))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+/// Cheap non-cryptographic randomness, good enough for traffic weighting.
+///
+/// We avoid pulling a dependency (like `rand`) and rely on the per-instance
+/// random keys of the std hasher.
+fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut h = RandomState::new().build_hasher();
+    h.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    h.finish()
+}
@@ -0,0 +1,208 @@
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use luciuz_config::{StaticSite, Symlinks};
+use percent_encoding::percent_decode_str;
+use tracing::warn;
+
+use crate::static_site::{fs_path, variants};
+use crate::{ClientInfo, RequestId};
+
+/// Extensions of compressed copies, looked through by `deny_extensions`.
+const COMPRESSED: [&str; 3] = ["gz", "br", "zst"];
+
+/// Why a static path was refused (logged as the `reason` field).
+pub(crate) type Denied = &'static str;
+
+/// Compiled `[static_site]` hardening settings.
+#[derive(Debug)]
+pub(crate) struct StaticGuard {
+    root: PathBuf,
+    /// Canonical `root`, for `within_root_only`.
+    canonical_root: PathBuf,
+    deny_hidden: bool,
+    hidden_allow: Vec<String>,
+    /// Lowercase, without the leading dot.
+    deny_extensions: Vec<String>,
+    symlinks: Symlinks,
+    /// Suffixes of the precompressed variants ServeDir may send instead.
+    variants: Vec<&'static str>,
+}
+
+impl StaticGuard {
+    pub(crate) fn new(s: &StaticSite) -> anyhow::Result<Self> {
+        let symlinks = s.symlinks;
+        let canonical_root = if symlinks == Symlinks::WithinRootOnly {
+            std::fs::canonicalize(&s.root)
+                .with_context(|| format!("static_site.root: cannot resolve {}", s.root))?
+        } else {
+            PathBuf::from(&s.root)
+        };
+        Ok(Self {
+            root: PathBuf::from(&s.root),
+            canonical_root,
+            deny_hidden: s.deny_hidden,
+            hidden_allow: s.hidden_allow.clone(),
+            deny_extensions: s
+                .deny_extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+            symlinks,
+            variants: variants(&s.precompressed)
+                .into_iter()
+                .map(|(_, suffix)| suffix)
+                .collect(),
+        })
+    }
+
+    /// Checks on the (decoded) URL path alone.
+    pub(crate) fn check_path(&self, path: &str) -> Result<(), Denied> {
+        let mut segments = path.split('/').filter(|s| !s.is_empty()).peekable();
+        while let Some(segment) = segments.next() {
+            if self.deny_hidden
+                && segment.starts_with('.')
+                && !self.hidden_allow.iter().any(|a| a == segment)
+            {
+                return Err("hidden");
+            }
+            if segments.peek().is_none() && self.denied_extension(segment) {
+                return Err("extension");
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the extension under any compression suffix too, so that
+    /// `secret.bak.gz` is refused like `secret.bak`.
+    fn denied_extension(&self, mut name: &str) -> bool {
+        while let Some((stem, ext)) = name.rsplit_once('.') {
+            if self
+                .deny_extensions
+                .iter()
+                .any(|d| d.eq_ignore_ascii_case(ext))
+            {
+                return true;
+            }
+            if !COMPRESSED.iter().any(|c| c.eq_ignore_ascii_case(ext)) {
+                return false;
+            }
+            name = stem;
+        }
+        false
+    }
+
+    /// Symlink policy for `file` (under `root`). Missing files pass: they
+    /// are answered 404 anyway.
+    pub(crate) async fn check_fs(&self, file: &Path) -> Result<(), Denied> {
+        match self.symlinks {
+            Symlinks::Follow => Ok(()),
+            Symlinks::WithinRootOnly => match tokio::fs::canonicalize(file).await {
+                Ok(real) if real.starts_with(&self.canonical_root) => Ok(()),
+                Ok(_) => Err("symlink_outside_root"),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(_) => Err("unresolvable"),
+            },
+            Symlinks::Deny | Symlinks::OwnerMatch => {
+                let Ok(rel) = file.strip_prefix(&self.root) else {
+                    return Err("outside_root");
+                };
+                let mut cur = self.root.clone();
+                for component in rel.components() {
+                    cur.push(component);
+                    let Ok(link) = tokio::fs::symlink_metadata(&cur).await else {
+                        return Ok(());
+                    };
+                    if !link.file_type().is_symlink() {
+                        continue;
+                    }
+                    if self.symlinks == Symlinks::Deny {
+                        return Err("symlink");
+                    }
+                    match tokio::fs::metadata(&cur).await {
+                        Ok(target) if same_owner(&link, &target) => {}
+                        Ok(_) => return Err("symlink_owner_mismatch"),
+                        Err(_) => return Ok(()),
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// All checks for a request about to reach the file service: the path,
+    /// the file, and the index.html or precompressed variant that may be
+    /// sent in its place.
+    async fn check_request(&self, path: &str) -> Result<(), Denied> {
+        self.check_path(path)?;
+        if self.symlinks == Symlinks::Follow {
+            return Ok(());
+        }
+        let Some(file) = fs_path(&self.root, path) else {
+            return Ok(());
+        };
+        self.check_fs(&file).await?;
+        if path.ends_with('/') {
+            return self.check_fs(&file.join("index.html")).await;
+        }
+        for suffix in &self.variants {
+            let mut variant = file.clone().into_os_string();
+            variant.push(suffix);
+            self.check_fs(Path::new(&variant)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn same_owner(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.uid() == b.uid()
+}
+
+#[cfg(not(unix))]
+fn same_owner(_: &Metadata, _: &Metadata) -> bool {
+    false
+}
+
+/// Log a refused static request as a security event.
+pub(crate) fn log_denied<B>(req: &Request<B>, reason: Denied) {
+    let client_ip = req.extensions().get::<ClientInfo>().map(|c| c.ip);
+    let request_id = req.extensions().get::<RequestId>().map(|r| r.0.as_str());
+    warn!(
+        target: "luciuz::security",
+        reason,
+        path = %req.uri().path(),
+        client_ip = ?client_ip,
+        request_id = request_id.unwrap_or(""),
+        "denied static file request"
+    );
+}
+
+/// Refuse hidden paths, denied extensions and symlinks the policy forbids,
+/// with a plain 404. Runs right in front of the file service, so it sees the
+/// path `try_files` picked.
+pub(crate) async fn static_guard_mw(
+    State(guard): State<Arc<StaticGuard>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    if let Err(reason) = guard.check_request(&path).await {
+        log_denied(&req, reason);
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    next.run(req).await
+}
@@ -0,0 +1,337 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode, Uri},
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Response},
+    routing::get_service,
+    Router,
+};
+use luciuz_config::{Algorithm, Config, Glob};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::bundle::{bundle_handler, Bundle};
+use crate::compression::add_vary;
+use crate::file_cache::{file_cache_mw, FileCache};
+use crate::listing::{listing_mw, Listing};
+use crate::static_guard::{static_guard_mw, StaticGuard};
+use crate::upload::{upload_router, Upload};
+
+/// Characters escaped when a `try_files` candidate becomes a request path.
+pub(crate) const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Compiled `[static_site]` response header settings.
+#[derive(Debug)]
+struct StaticHeaders {
+    index: String,
+    /// (pattern, Cache-Control) in config order.
+    rules: Vec<(Glob, HeaderValue)>,
+    fallback: Option<HeaderValue>,
+    /// Responses depend on Accept-Encoding (precompressed variants enabled).
+    vary: bool,
+}
+
+impl StaticHeaders {
+    fn cache_control(&self, path: &str) -> Option<&HeaderValue> {
+        self.rules
+            .iter()
+            .find(|(glob, _)| glob.matches(path))
+            .map(|(_, value)| value)
+            .or(self.fallback.as_ref())
+    }
+}
+
+/// Compiled `try_files` candidates and 404 page.
+#[derive(Debug)]
+struct TryFiles {
+    root: PathBuf,
+    /// Set when `root` is an archive: files are looked up there instead.
+    bundle: Option<Arc<Bundle>>,
+    /// (template, excluded request path prefixes) in config order.
+    candidates: Vec<(String, Vec<String>)>,
+    /// URL path of the 404 page.
+    not_found: Option<String>,
+}
+
+impl TryFiles {
+    /// The first candidate naming an existing file (or, for a candidate
+    /// ending in `/`, a directory with an index.html) for request `path`.
+    async fn resolve(&self, path: &str) -> Option<String> {
+        for (template, exclude) in &self.candidates {
+            if exclude.iter().any(|p| path.starts_with(p.as_str())) {
+                continue;
+            }
+            let candidate = template.replace("$uri", path);
+            if let Some(bundle) = &self.bundle {
+                if bundle.is_file(&candidate) {
+                    return Some(candidate);
+                }
+                continue;
+            }
+            let Some(mut file) = fs_path(&self.root, &candidate) else {
+                continue;
+            };
+            if candidate.ends_with('/') {
+                file.push("index.html");
+            }
+            if tokio::fs::metadata(&file).await.is_ok_and(|m| m.is_file()) {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    async fn not_found(&self, method: Method, headers: &HeaderMap) -> Response {
+        let Some(page) = &self.not_found else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        if let Some(bundle) = &self.bundle {
+            return bundle
+                .respond(page, headers, StatusCode::NOT_FOUND)
+                .unwrap_or_else(|| StatusCode::NOT_FOUND.into_response());
+        }
+        let Some(file) = fs_path(&self.root, page) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let req = Request::builder()
+            .method(method)
+            .body(Body::empty())
+            .expect("valid request");
+        match ServeFile::new(&file).oneshot(req).await {
+            Ok(res) if res.status().is_success() => {
+                let (mut parts, body) = res.into_parts();
+                parts.status = StatusCode::NOT_FOUND;
+                Response::from_parts(parts, Body::new(body))
+            }
+            Ok(_) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => {
+                tracing::error!(?err, file = %file.display(), "static not_found file error");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Map a URL path onto `root`, refusing anything that could escape it.
+pub(crate) fn fs_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut out = root.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(c) if !c.to_string_lossy().contains('\\') => out.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+/// (coding, file suffix) of the enabled precompressed variants, in config
+/// order.
+pub(crate) fn variants(precompressed: &[Algorithm]) -> Vec<(&'static str, &'static str)> {
+    precompressed
+        .iter()
+        .map(|p| match p {
+            Algorithm::Brotli => ("br", ".br"),
+            Algorithm::Zstd => ("zstd", ".zst"),
+            Algorithm::Gzip => ("gzip", ".gz"),
+        })
+        .collect()
+}
+
+/// Build the `static_site` profile router: files under `root` (a directory
+/// or an archive), with
+/// precompressed variants, Cache-Control rules, `try_files`, a 404 page,
+/// directory listings, hidden file / extension / symlink restrictions, and
+/// an in-memory cache of hot files, and an authenticated upload prefix.
+pub fn static_router(cfg: &Config) -> anyhow::Result<Router<()>> {
+    let s = cfg
+        .static_site
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("missing [static_site] config"))?;
+
+    let bundle = if s.is_archive() {
+        Some(Bundle::open(&s.root, &s.precompressed)?)
+    } else {
+        None
+    };
+
+    let header = |at: &str, value: &str| {
+        HeaderValue::from_str(value.trim()).with_context(|| format!("{at}: invalid header value"))
+    };
+    let rules = s
+        .cache_rules
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let at = format!("static_site.cache_rules[{i}]");
+            let glob = r
+                .pattern
+                .parse::<Glob>()
+                .map_err(|e| anyhow::anyhow!("{at}.pattern: {e}"))?;
+            Ok((
+                glob,
+                header(&format!("{at}.cache_control"), &r.cache_control)?,
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let fallback = match &s.cache_control {
+        Some(cc) => Some(header("static_site.cache_control", cc)?),
+        None => None,
+    };
+    let headers = Arc::new(StaticHeaders {
+        index: s.index.clone(),
+        rules,
+        fallback,
+        vary: !s.precompressed.is_empty(),
+    });
+
+    if let Some(f) = &s.not_found {
+        let path = fs_path(Path::new(&s.root), f)
+            .ok_or_else(|| anyhow::anyhow!("static_site.not_found must stay inside root: {f}"))?;
+        let exists = match &bundle {
+            Some(b) => b.is_file(f),
+            None => path.is_file(),
+        };
+        if !exists {
+            anyhow::bail!("static_site.not_found: no such file {}", path.display());
+        }
+    }
+    let try_files = Arc::new(TryFiles {
+        root: PathBuf::from(&s.root),
+        bundle: bundle.clone(),
+        candidates: s
+            .try_files
+            .iter()
+            .map(|t| (t.path().to_string(), t.exclude().to_vec()))
+            .collect(),
+        not_found: s.not_found.clone(),
+    });
+
+    let guard = Arc::new(StaticGuard::new(s)?);
+
+    let router = match bundle {
+        Some(b) => Router::new().fallback(bundle_handler).with_state(b),
+        None => {
+            let mut dir = ServeDir::new(&s.root).append_index_html_on_directories(true);
+            for p in &s.precompressed {
+                dir = match p {
+                    Algorithm::Brotli => dir.precompressed_br(),
+                    Algorithm::Zstd => dir.precompressed_zstd(),
+                    Algorithm::Gzip => dir.precompressed_gzip(),
+                };
+            }
+            Router::new().fallback_service(get_service(dir).handle_error(|err| async move {
+                tracing::error!(?err, "static file error");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }))
+        }
+    };
+    let router = match &s.cache {
+        Some(c) => router.layer(from_fn_with_state(
+            FileCache::new(&s.root, c, &s.precompressed),
+            file_cache_mw,
+        )),
+        None => router,
+    };
+    let router = router
+        .layer(from_fn_with_state(guard.clone(), static_guard_mw))
+        .layer(from_fn_with_state(headers, static_headers_mw))
+        .layer(from_fn_with_state(try_files, try_files_mw));
+    let router = match &s.listing {
+        Some(l) => router.layer(from_fn_with_state(
+            Arc::new(Listing::new(&s.root, l, guard.clone())?),
+            listing_mw,
+        )),
+        None => router,
+    };
+    // Outside the layers above: writes go straight to the upload handler.
+    Ok(match &s.upload {
+        Some(u) => router.merge(upload_router(Arc::new(Upload::new(&s.root, u, guard)?))),
+        None => router,
+    })
+}
+
+/// Rewrite GET/HEAD requests to their first existing `try_files` candidate,
+/// and answer 404s with the `not_found` page.
+async fn try_files_mw(
+    State(tf): State<Arc<TryFiles>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let method = req.method().clone();
+    if !(method == Method::GET || method == Method::HEAD) {
+        return next.run(req).await;
+    }
+    let headers = req.headers().clone();
+
+    if !tf.candidates.is_empty() {
+        let path = percent_decode_str(req.uri().path())
+            .decode_utf8_lossy()
+            .into_owned();
+        let Some(target) = tf.resolve(&path).await else {
+            return tf.not_found(method, &headers).await;
+        };
+        let encoded = utf8_percent_encode(&target, PATH).to_string();
+        let uri = match req.uri().query() {
+            Some(q) => format!("{encoded}?{q}"),
+            None => encoded,
+        };
+        match uri.parse::<Uri>() {
+            Ok(uri) => *req.uri_mut() = uri,
+            Err(_) => return tf.not_found(method, &headers).await,
+        }
+    }
+
+    let res = next.run(req).await;
+    if res.status() == StatusCode::NOT_FOUND {
+        return tf.not_found(method, &headers).await;
+    }
+    res
+}
+
+/// Add Cache-Control (first matching rule, else the default) and, when
+/// precompressed variants are enabled, `Vary: Accept-Encoding` to successful
+/// and 304 responses.
+async fn static_headers_mw(
+    State(headers): State<Arc<StaticHeaders>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    if path.ends_with('/') {
+        path.push_str(&headers.index);
+    }
+
+    let mut res = next.run(req).await;
+    let status = res.status();
+    if !(status.is_success() || status == StatusCode::NOT_MODIFIED) {
+        return res;
+    }
+    if headers.vary {
+        add_vary(res.headers_mut());
+    }
+    if !res.headers().contains_key(header::CACHE_CONTROL) {
+        if let Some(cc) = headers.cache_control(&path) {
+            res.headers_mut().insert(header::CACHE_CONTROL, cc.clone());
+        }
+    }
+    res
+}
@@ -0,0 +1,290 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::any,
+    Router,
+};
+use futures_util::StreamExt;
+use percent_encoding::percent_decode_str;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+use crate::basic_auth::Users;
+use crate::request_id::generate;
+use crate::static_guard::{log_denied, StaticGuard};
+use crate::static_site::fs_path;
+use crate::{ClientInfo, RequestId};
+
+const ALLOW: &str = "OPTIONS, PUT, DELETE, MKCOL";
+
+/// Compiled `[static_site.upload]`.
+#[derive(Debug)]
+pub(crate) struct Upload {
+    /// Without a trailing `/`.
+    prefix: String,
+    root: PathBuf,
+    /// Canonical `root`: writes must land under it, whatever `symlinks` says.
+    canonical_root: PathBuf,
+    max_body_bytes: usize,
+    users: Users,
+    guard: Arc<StaticGuard>,
+}
+
+impl Upload {
+    pub(crate) fn new(
+        root: &str,
+        cfg: &luciuz_config::Upload,
+        guard: Arc<StaticGuard>,
+    ) -> anyhow::Result<Self> {
+        let canonical_root = std::fs::canonicalize(root)
+            .with_context(|| format!("static_site.root: cannot resolve {root}"))?;
+        let users = Users::new("static_site.upload", &cfg.users)?;
+        Ok(Self {
+            prefix: cfg.prefix.trim_end_matches('/').to_string(),
+            root: PathBuf::from(root),
+            canonical_root,
+            max_body_bytes: cfg.max_body_bytes,
+            users,
+            guard,
+        })
+    }
+
+    /// File system path for `rel` (the request path after the prefix), if
+    /// the static guard allows it.
+    fn target(&self, rel: &str) -> Result<PathBuf, &'static str> {
+        self.guard.check_path(rel)?;
+        fs_path(&self.root, rel).ok_or("traversal")
+    }
+
+    /// `dir` must exist and resolve under the canonical root.
+    async fn confined(&self, dir: &Path) -> Result<(), StatusCode> {
+        match tokio::fs::canonicalize(dir).await {
+            Ok(real) if real.starts_with(&self.canonical_root) => Ok(()),
+            Ok(_) => Err(StatusCode::FORBIDDEN),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(StatusCode::CONFLICT),
+            Err(err) => Err(io_status(&err, dir)),
+        }
+    }
+
+    /// Stream `body` to a temporary file next to `file`, then rename it into
+    /// place. Returns the status and the number of bytes written.
+    async fn put(&self, file: &Path, headers: &HeaderMap, body: Body) -> (StatusCode, u64) {
+        let declared = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if declared.is_some_and(|n| n > self.max_body_bytes as u64) {
+            return (StatusCode::PAYLOAD_TOO_LARGE, 0);
+        }
+        let Some(parent) = file.parent() else {
+            return (StatusCode::METHOD_NOT_ALLOWED, 0);
+        };
+        if let Err(status) = self.confined(parent).await {
+            return (status, 0);
+        }
+        let existed = match tokio::fs::symlink_metadata(file).await {
+            Ok(meta) if meta.is_dir() => return (StatusCode::METHOD_NOT_ALLOWED, 0),
+            Ok(_) => true,
+            Err(_) => false,
+        };
+
+        // Hidden, so never served even if left behind by a crash.
+        let tmp = parent.join(format!(".luciuz-upload-{}.tmp", generate()));
+        let written = match self.write(&tmp, body).await {
+            Ok(n) => n,
+            Err(status) => {
+                let _ = tokio::fs::remove_file(&tmp).await;
+                return (status, 0);
+            }
+        };
+        if let Err(err) = tokio::fs::rename(&tmp, file).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return (io_status(&err, file), 0);
+        }
+        let status = if existed {
+            StatusCode::NO_CONTENT
+        } else {
+            StatusCode::CREATED
+        };
+        (status, written)
+    }
+
+    async fn write(&self, tmp: &Path, body: Body) -> Result<u64, StatusCode> {
+        let mut out = tokio::fs::File::create(tmp)
+            .await
+            .map_err(|err| io_status(&err, tmp))?;
+        let mut stream = body.into_data_stream();
+        let mut written = 0u64;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+            written += chunk.len() as u64;
+            if written > self.max_body_bytes as u64 {
+                return Err(StatusCode::PAYLOAD_TOO_LARGE);
+            }
+            out.write_all(&chunk)
+                .await
+                .map_err(|err| io_status(&err, tmp))?;
+        }
+        out.sync_all().await.map_err(|err| io_status(&err, tmp))?;
+        Ok(written)
+    }
+
+    /// Remove a file, a symlink (not its target) or a whole directory.
+    async fn delete(&self, path: &Path) -> StatusCode {
+        if path == self.root {
+            return StatusCode::FORBIDDEN;
+        }
+        let Some(parent) = path.parent() else {
+            return StatusCode::FORBIDDEN;
+        };
+        if let Err(status) = self.confined(parent).await {
+            return if status == StatusCode::CONFLICT {
+                StatusCode::NOT_FOUND
+            } else {
+                status
+            };
+        }
+        let result = match tokio::fs::symlink_metadata(path).await {
+            Ok(meta) if meta.is_dir() => tokio::fs::remove_dir_all(path).await,
+            Ok(_) => tokio::fs::remove_file(path).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(err) => io_status(&err, path),
+        }
+    }
+
+    async fn mkcol(&self, dir: &Path, has_body: bool) -> StatusCode {
+        if has_body {
+            return StatusCode::UNSUPPORTED_MEDIA_TYPE;
+        }
+        let Some(parent) = dir.parent().filter(|_| dir != self.root) else {
+            return StatusCode::METHOD_NOT_ALLOWED;
+        };
+        if let Err(status) = self.confined(parent).await {
+            return status;
+        }
+        match tokio::fs::create_dir(dir).await {
+            Ok(()) => StatusCode::CREATED,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                StatusCode::METHOD_NOT_ALLOWED
+            }
+            Err(err) => io_status(&err, dir),
+        }
+    }
+}
+
+fn io_status(err: &io::Error, path: &Path) -> StatusCode {
+    match err.kind() {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => {
+            error!(error = %err, path = %path.display(), "static upload I/O error");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Routes for `prefix` and everything under it.
+pub(crate) fn upload_router(upload: Arc<Upload>) -> Router<()> {
+    let prefix = upload.prefix.clone();
+    Router::new()
+        .route(&prefix, any(upload_handler))
+        .route(&format!("{prefix}/"), any(upload_handler))
+        .route(&format!("{prefix}/{{*path}}"), any(upload_handler))
+        .with_state(upload)
+}
+
+/// Authenticated writes under the upload prefix: PUT a file, DELETE a file
+/// or directory, MKCOL a directory. Every attempt is logged to the
+/// `luciuz::audit` target.
+async fn upload_handler(State(up): State<Arc<Upload>>, req: Request<Body>) -> Response {
+    let method = req.method().clone();
+    if method == Method::OPTIONS {
+        return (
+            StatusCode::NO_CONTENT,
+            [(header::ALLOW, HeaderValue::from_static(ALLOW))],
+        )
+            .into_response();
+    }
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    let rel = path
+        .strip_prefix(up.prefix.as_str())
+        .unwrap_or("")
+        .to_string();
+    let client_ip = req.extensions().get::<ClientInfo>().map(|c| c.ip);
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|r| r.0.clone())
+        .unwrap_or_default();
+    let audit = |user: &str, status: StatusCode, bytes: u64| {
+        info!(
+            target: "luciuz::audit",
+            user,
+            method = %method,
+            path = %rel,
+            status = status.as_u16(),
+            bytes,
+            client_ip = ?client_ip,
+            request_id = request_id.as_str(),
+            "static write"
+        );
+    };
+
+    let user = match up.users.authenticate(req.headers()).await {
+        Ok(user) => user,
+        Err(tried) => {
+            audit(tried.as_deref().unwrap_or(""), StatusCode::UNAUTHORIZED, 0);
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Basic realm=\"luciuz upload\""),
+                )],
+            )
+                .into_response();
+        }
+    };
+    let target = match up.target(&rel) {
+        Ok(target) => target,
+        Err(reason) => {
+            log_denied(&req, reason);
+            audit(&user, StatusCode::FORBIDDEN, 0);
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    };
+
+    let (status, bytes) = match method.as_str() {
+        "PUT" if rel.ends_with('/') || target == up.root => (StatusCode::METHOD_NOT_ALLOWED, 0),
+        "PUT" => {
+            let (parts, body) = req.into_parts();
+            up.put(&target, &parts.headers, body).await
+        }
+        "DELETE" => (up.delete(&target).await, 0),
+        "MKCOL" => {
+            let has_body = req
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .is_some_and(|v| v != "0")
+                || req.headers().contains_key(header::TRANSFER_ENCODING);
+            (up.mkcol(&target, has_body).await, 0)
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, 0),
+    };
+    audit(&user, status, bytes);
+    if status == StatusCode::METHOD_NOT_ALLOWED {
+        return (status, [(header::ALLOW, HeaderValue::from_static(ALLOW))]).into_response();
+    }
+    status.into_response()
+}
@@ -0,0 +1,102 @@
+//! Pools pinning clients with a signed affinity cookie.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request},
+    routing::get,
+    Router,
+};
+use tower::ServiceExt;
+
+/// An upstream answering every request with `name`.
+async fn upstream(name: &'static str) -> String {
+    let app = Router::new().fallback(get(move || async move { name }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    url
+}
+
+/// A `/api` route to a pool of `targets`, pinning with `secret`.
+fn pool(targets: &[String], secret: &str) -> Router {
+    let toml = format!(
+        "[server]\nprofile = \"public_api\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [proxy.pools.app]\ntargets = {targets:?}\n\n\
+         [proxy.pools.app.affinity]\nmode = \"cookie\"\nsecret = \"{secret}\"\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\npool = \"app\"\n"
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    luciuz_proxy::router(&cfg, "http").unwrap()
+}
+
+/// The target that answered, and the `name=value` of the cookie set (if any).
+async fn get_api(app: &Router, cookie: Option<&str>) -> (String, Option<String>) {
+    let mut req = Request::get("/api/x");
+    if let Some(cookie) = cookie {
+        req = req.header(header::COOKIE, cookie);
+    }
+    let res = app
+        .clone()
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let set_cookie = res.headers().get(header::SET_COOKIE).map(|v| {
+        let v = v.to_str().unwrap();
+        v.split(';').next().unwrap().to_string()
+    });
+    let body = to_bytes(res.into_body(), 1024).await.unwrap();
+    (String::from_utf8(body.to_vec()).unwrap(), set_cookie)
+}
+
+const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+#[tokio::test]
+async fn a_signed_cookie_keeps_its_target() {
+    let app = pool(&[upstream("a").await, upstream("b").await], SECRET);
+    let (target, cookie) = get_api(&app, None).await;
+    let cookie = cookie.expect("affinity cookie");
+    assert!(cookie.starts_with("luciuz_affinity="), "{cookie}");
+    for _ in 0..10 {
+        assert_eq!(get_api(&app, Some(&cookie)).await, (target.clone(), None));
+    }
+}
+
+#[tokio::test]
+async fn a_tampered_cookie_is_not_trusted() {
+    let app = pool(&[upstream("a").await, upstream("b").await], SECRET);
+    let (_, cookie) = get_api(&app, None).await;
+    let cookie = cookie.unwrap();
+    let (index, tag) = cookie["luciuz_affinity=".len()..].split_once('.').unwrap();
+
+    // The other target's index with this target's tag, a flipped tag, a
+    // truncated tag, no tag, an index out of range.
+    let other = if index == "0" { "1" } else { "0" };
+    let flipped = format!(
+        "{}{}",
+        if tag.starts_with('0') { '1' } else { '0' },
+        &tag[1..]
+    );
+    for forged in [
+        format!("{other}.{tag}"),
+        format!("{index}.{flipped}"),
+        format!("{index}.{}", &tag[..tag.len() - 2]),
+        index.to_string(),
+        format!("2.{tag}"),
+    ] {
+        let (_, set_cookie) = get_api(&app, Some(&format!("luciuz_affinity={forged}"))).await;
+        // Re-pinned by the balancer, with a cookie Luciuz signed.
+        let set_cookie = set_cookie.unwrap_or_else(|| panic!("{forged} was trusted"));
+        let (_, fresh) = get_api(&app, Some(&set_cookie)).await;
+        assert_eq!(fresh, None, "{forged}");
+    }
+}
+
+#[tokio::test]
+async fn a_cookie_from_another_key_is_not_trusted() {
+    let targets = [upstream("a").await, upstream("b").await];
+    let (_, cookie) = get_api(&pool(&targets, SECRET), None).await;
+    let other = pool(&targets, "fedcba9876543210fedcba9876543210");
+    let (_, set_cookie) = get_api(&other, Some(&cookie.unwrap())).await;
+    assert!(set_cookie.is_some());
+}
@@ -0,0 +1,165 @@
+//! A site served from a zip or tar archive, swapped when the file is replaced.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("luciuz-bundle-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write a gzipped tar of `files` to `path`.
+fn tar_gz(path: &Path, files: &[(&str, &str)]) {
+    let gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    let mut tar = tar::Builder::new(gz);
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(1_700_000_000);
+        tar.append_data(&mut header, name, content.as_bytes())
+            .unwrap();
+    }
+    std::fs::write(path, tar.into_inner().unwrap().finish().unwrap()).unwrap();
+}
+
+fn zip(path: &Path, files: &[(&str, &str)]) {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, content) in files {
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+fn site(root: &Path) -> Router {
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\n",
+        root.to_str().unwrap()
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    luciuz_proxy::static_router(&cfg).unwrap()
+}
+
+async fn get_with(
+    app: &Router,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> (StatusCode, HeaderMap, String) {
+    let mut req = Request::get(path);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let res = app
+        .clone()
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let (status, headers) = (res.status(), res.headers().clone());
+    let body = to_bytes(res.into_body(), 1 << 20).await.unwrap();
+    (status, headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn body(app: &Router, path: &str) -> String {
+    get_with(app, path, &[]).await.2
+}
+
+#[tokio::test]
+async fn a_tar_serves_like_a_directory() {
+    let dir = dir("tar");
+    let root = dir.join("site.tar.gz");
+    tar_gz(
+        &root,
+        &[
+            ("index.html", "home"),
+            ("docs/index.html", "docs"),
+            ("app.js", "js"),
+        ],
+    );
+    let app = site(&root);
+    assert_eq!(body(&app, "/").await, "home");
+    assert_eq!(body(&app, "/docs/").await, "docs");
+    assert_eq!(body(&app, "/app.js").await, "js");
+
+    let (status, headers, _) = get_with(&app, "/docs?v=1", &[]).await;
+    assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(headers[header::LOCATION], "/docs/?v=1");
+
+    for path in [
+        "/missing.js",
+        "/../site.tar.gz",
+        "/docs/..%2f..%2fsite.tar.gz",
+    ] {
+        assert_eq!(
+            get_with(&app, path, &[]).await.0,
+            StatusCode::NOT_FOUND,
+            "{path}"
+        );
+    }
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn zip_entries_revalidate_by_checksum() {
+    let dir = dir("zip");
+    let root = dir.join("site.zip");
+    zip(&root, &[("index.html", "home"), ("app.js", "js")]);
+    let app = site(&root);
+
+    let (status, headers, body) = get_with(&app, "/app.js", &[]).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "js"));
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+    let (status, _, body) = get_with(&app, "/app.js", &[("if-none-match", &etag)]).await;
+    assert_eq!((status, body.as_str()), (StatusCode::NOT_MODIFIED, ""));
+
+    let (_, other, _) = get_with(&app, "/index.html", &[]).await;
+    assert_ne!(other[header::ETAG], etag);
+    std::fs::remove_dir_all(dir).ok();
+}
+
+/// Poll `path` until it answers `expected`, for up to 5s.
+async fn eventually(app: &Router, path: &str, expected: &str) {
+    for _ in 0..50 {
+        if body(app, path).await == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{path} never answered {expected:?}");
+}
+
+#[tokio::test]
+async fn a_replaced_archive_is_swapped_in() {
+    let dir = dir("swap");
+    let root = dir.join("site.tar.gz");
+    tar_gz(&root, &[("index.html", "v1")]);
+    let app = site(&root);
+    assert_eq!(body(&app, "/").await, "v1");
+
+    let next = dir.join("site.tar.gz.new");
+    tar_gz(&next, &[("index.html", "v2"), ("new.txt", "new")]);
+    std::fs::rename(&next, &root).unwrap();
+    eventually(&app, "/", "v2").await;
+    assert_eq!(body(&app, "/new.txt").await, "new");
+
+    // A broken version is skipped: v2 keeps being served.
+    std::fs::write(&next, "not a tar").unwrap();
+    std::fs::rename(&next, &root).unwrap();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(body(&app, "/").await, "v2");
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,80 @@
+//! Streamed responses are compressed as they go.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::Body,
+    http::{header, Request},
+    middleware::from_fn_with_state,
+    response::Response,
+    routing::get,
+    Router,
+};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use luciuz_proxy::{compression_mw, CompressionState};
+use tokio::sync::mpsc;
+use tower::ServiceExt;
+
+/// Decompressed so far from a gzip stream (that need not be finished).
+fn gunzip(decoder: &mut flate2::write::GzDecoder<Vec<u8>>, chunk: &[u8]) -> String {
+    decoder.write_all(chunk).unwrap();
+    decoder.flush().unwrap();
+    String::from_utf8(std::mem::take(decoder.get_mut())).unwrap()
+}
+
+#[tokio::test]
+async fn event_streams_are_flushed_per_frame() {
+    let (cfg, report) = luciuz_config::check_str(
+        "luciuz.toml",
+        "[server]\nprofile = \"public_api\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [compression]\nalgorithms = [\"gzip\"]\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\nupstream = \"http://127.0.0.1:3000\"\n",
+    );
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+
+    let (tx, rx) = mpsc::channel::<Bytes>(4);
+    let events = Body::from_stream(futures_util::stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        Some((Ok::<_, std::io::Error>(event), rx))
+    }));
+    let events = Arc::new(Mutex::new(Some(events)));
+    let app = Router::new()
+        .route(
+            "/events",
+            get(move || async move {
+                Response::builder()
+                    .header(header::CONTENT_TYPE, "text/event-stream")
+                    .body(events.lock().unwrap().take().unwrap())
+                    .unwrap()
+            }),
+        )
+        .layer(from_fn_with_state(
+            CompressionState::new(&cfg).unwrap(),
+            compression_mw,
+        ));
+
+    let req = Request::get("/events")
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.oneshot(req).await.unwrap();
+    assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+    let mut body = res.into_body().into_data_stream();
+    let mut decoder = flate2::write::GzDecoder::new(Vec::new());
+
+    // The upstream is still open: each event must come through on its own.
+    for event in ["data: one\n\n", "data: two\n\n"] {
+        tx.send(Bytes::from(event)).await.unwrap();
+        let chunk = body.next().await.unwrap().unwrap();
+        assert_eq!(gunzip(&mut decoder, &chunk), event);
+    }
+    drop(tx);
+    let mut rest = Vec::new();
+    while let Some(chunk) = body.next().await {
+        rest.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(gunzip(&mut decoder, &rest), "");
+    decoder.finish().unwrap();
+}
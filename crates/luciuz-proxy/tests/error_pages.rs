@@ -0,0 +1,110 @@
+//! Bodies of error responses: HTML or problem details, with the request id.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{from_fn, from_fn_with_state},
+    routing::get,
+    Router,
+};
+use luciuz_proxy::{error_pages_mw, request_id_mw, ErrorPagesState};
+use tower::ServiceExt;
+
+/// An upstream failing every request with a 500 and a stack trace.
+async fn failing_upstream() -> String {
+    let app = Router::new().fallback(get(|| async {
+        (StatusCode::INTERNAL_SERVER_ERROR, "panic at src/db.rs:42")
+    }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    url
+}
+
+/// An address nothing listens on.
+async fn closed_port() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+/// Routes `/down` (502 from Luciuz), `/app` (upstream 500, passed through)
+/// and `/api` (upstream 500, replaced by problem details), under the
+/// site-wide `errors` settings.
+async fn app(errors: &str) -> Router {
+    let upstream = failing_upstream().await;
+    let toml = format!(
+        "[server]\nprofile = \"public_api\"\nhttp_listen = \"127.0.0.1:8080\"\n\n{errors}\n\
+         [[proxy.routes]]\nprefix = \"/down\"\nupstream = \"{}\"\n\n\
+         [[proxy.routes]]\nprefix = \"/app\"\nupstream = \"{upstream}\"\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\nupstream = \"{upstream}\"\n\n\
+         [proxy.routes.errors]\nformat = \"problem\"\nintercept_upstream = true\n",
+        closed_port().await
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    luciuz_proxy::router(&cfg, "http")
+        .unwrap()
+        .layer(from_fn_with_state(
+            ErrorPagesState::new(&cfg).unwrap(),
+            error_pages_mw,
+        ))
+        .layer(from_fn(request_id_mw))
+}
+
+async fn get_with(app: &Router, path: &str, accept: &str) -> (StatusCode, HeaderMap, String) {
+    let req = Request::get(path)
+        .header(header::ACCEPT, accept)
+        .body(Body::empty())
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let (status, headers) = (res.status(), res.headers().clone());
+    let body = to_bytes(res.into_body(), 1 << 16).await.unwrap();
+    (status, headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn json_clients_get_problem_details() {
+    let app = app("").await;
+    let (status, headers, body) = get_with(&app, "/down", "application/json").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/problem+json");
+    let problem: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(problem["status"], 502);
+    assert_eq!(problem["title"], "Bad Gateway");
+    assert_eq!(
+        problem["request_id"],
+        headers["x-request-id"].to_str().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn browsers_get_a_page() {
+    let app = app("").await;
+    for accept in ["text/html,application/xhtml+xml,*/*;q=0.8", "*/*"] {
+        let (status, headers, body) = get_with(&app, "/down", accept).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(headers[header::CONTENT_TYPE], "text/html; charset=utf-8");
+        assert!(body.contains("<h1>502 Bad Gateway</h1>"), "{body}");
+        assert!(body.contains(headers["x-request-id"].to_str().unwrap()));
+    }
+}
+
+#[tokio::test]
+async fn the_problem_format_ignores_accept() {
+    let app = app("[errors]\nformat = \"problem\"\n").await;
+    let (_, headers, _) = get_with(&app, "/down", "text/html").await;
+    assert_eq!(headers[header::CONTENT_TYPE], "application/problem+json");
+}
+
+#[tokio::test]
+async fn upstream_errors_are_replaced_only_when_intercepted() {
+    let app = app("").await;
+    let (status, _, body) = get_with(&app, "/app", "application/json").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body, "panic at src/db.rs:42");
+
+    let (status, headers, body) = get_with(&app, "/api", "text/html").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/problem+json");
+    assert!(!body.contains("panic"), "{body}");
+}
@@ -0,0 +1,55 @@
+//! The hot file cache trusts its entries for `revalidate`.
+
+use std::path::PathBuf;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+/// A cached site serving `/page.txt`, and its root.
+fn site(name: &str, cache: &str) -> (Router, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("luciuz-cache-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("page.txt"), "v1").unwrap();
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\n\n[static_site.cache]\nstats_interval = \"0s\"\n{cache}",
+        dir.to_str().unwrap()
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    (luciuz_proxy::static_router(&cfg).unwrap(), dir)
+}
+
+async fn get(app: &Router) -> (StatusCode, String) {
+    let req = Request::get("/page.txt").body(Body::empty()).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), 1024).await.unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+#[tokio::test]
+async fn hits_within_the_window_skip_the_disk() {
+    // Default window (2s).
+    let (app, dir) = site("window", "");
+    assert_eq!(get(&app).await, (StatusCode::OK, "v1".into()));
+    // Gone from disk, yet still served: the hit did not even stat the file.
+    std::fs::remove_file(dir.join("page.txt")).unwrap();
+    assert_eq!(get(&app).await, (StatusCode::OK, "v1".into()));
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn without_a_window_every_hit_is_checked() {
+    let (app, dir) = site("no-window", "revalidate = \"0s\"\n");
+    assert_eq!(get(&app).await, (StatusCode::OK, "v1".into()));
+    std::fs::write(dir.join("page.txt"), "v2, longer").unwrap();
+    assert_eq!(get(&app).await, (StatusCode::OK, "v2, longer".into()));
+    std::fs::remove_file(dir.join("page.txt")).unwrap();
+    assert_eq!(get(&app).await.0, StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,103 @@
+//! Client identity from forwarding headers: only trusted hops are believed.
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{connect_info::ConnectInfo, Extension},
+    http::Request,
+    middleware::from_fn_with_state,
+    routing::get,
+    Router,
+};
+use luciuz_proxy::{client_ip_mw, ClientInfo, ClientIpState};
+use tower::ServiceExt;
+
+/// A load balancer at 10.0.0.1 in front of us.
+const CONFIG: &str = r#"
+[server]
+http_listen = "127.0.0.1:8080"
+trusted_proxies = ["10.0.0.0/8"]
+
+[[proxy.routes]]
+prefix = "/api"
+upstream = "http://127.0.0.1:3000"
+"#;
+
+/// `ip proto host` as resolved for a request from `peer` with `headers`.
+async fn resolve(peer: &str, headers: &[(&str, &str)]) -> String {
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", CONFIG);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    let app = Router::new()
+        .route(
+            "/",
+            get(|Extension(ci): Extension<ClientInfo>| async move {
+                format!("{} {} {}", ci.ip, ci.proto, ci.host.unwrap_or_default())
+            }),
+        )
+        .layer(from_fn_with_state(
+            ClientIpState::new(&cfg, "http"),
+            client_ip_mw,
+        ));
+
+    let mut req = Request::get("/").header("host", "origin.internal");
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let mut req = req.body(Body::empty()).unwrap();
+    let peer: SocketAddr = peer.parse().unwrap();
+    req.extensions_mut().insert(ConnectInfo(peer));
+    let res = app.oneshot(req).await.unwrap();
+    let body = to_bytes(res.into_body(), 1024).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn an_untrusted_peer_is_the_client() {
+    let resolved = resolve(
+        "203.0.113.9:5000",
+        &[("forwarded", "for=192.0.2.1;proto=https;host=evil")],
+    )
+    .await;
+    assert_eq!(resolved, "203.0.113.9 http origin.internal");
+}
+
+#[tokio::test]
+async fn proto_and_host_come_from_the_trusted_hop() {
+    let resolved = resolve(
+        "10.0.0.1:5000",
+        &[(
+            "forwarded",
+            "for=192.0.2.1;proto=https;host=evil, for=198.51.100.7;proto=http;host=shop.example",
+        )],
+    )
+    .await;
+    assert_eq!(resolved, "198.51.100.7 http shop.example");
+}
+
+#[tokio::test]
+async fn injected_proto_and_host_are_ignored() {
+    let resolved = resolve(
+        "10.0.0.1:5000",
+        &[(
+            "forwarded",
+            "for=192.0.2.1;proto=https;host=evil, for=198.51.100.7",
+        )],
+    )
+    .await;
+    assert_eq!(resolved, "198.51.100.7 http origin.internal");
+}
+
+#[tokio::test]
+async fn x_forwarded_values_are_read_from_the_right() {
+    let resolved = resolve(
+        "10.0.0.1:5000",
+        &[
+            ("x-forwarded-for", "192.0.2.1, 198.51.100.7"),
+            ("x-forwarded-proto", "http, https"),
+            ("x-forwarded-host", "evil, shop.example"),
+        ],
+    )
+    .await;
+    assert_eq!(resolved, "198.51.100.7 https shop.example");
+}
@@ -0,0 +1,104 @@
+//! Generated directory listings under `[static_site.listing]` prefixes.
+
+use std::path::PathBuf;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+/// A site listing `/mirror/`, and its root.
+fn site(name: &str) -> (Router, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("luciuz-listing-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (file, size) in [
+        ("mirror/b.tar.gz", 30),
+        ("mirror/a.iso", 200),
+        ("mirror/c <&>.txt", 1),
+        ("mirror/upload.part", 5),
+        ("mirror/.secret", 5),
+        ("mirror/old.bak", 5),
+        ("mirror/pool/x.deb", 5),
+        ("mirror/site/index.html", 5),
+        ("other/y.txt", 5),
+    ] {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, vec![b'x'; size]).unwrap();
+    }
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\ndeny_extensions = [\"bak\"]\n\n\
+         [static_site.listing]\nprefixes = [\"/mirror/\"]\nhide = [\"*.part\"]\n",
+        dir.to_str().unwrap()
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    (luciuz_proxy::static_router(&cfg).unwrap(), dir)
+}
+
+async fn get_with(app: &Router, path: &str, accept: &str) -> (StatusCode, String) {
+    let req = Request::get(path)
+        .header(header::ACCEPT, accept)
+        .body(Body::empty())
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), 1 << 20).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Entry names of a JSON listing, in order.
+async fn names(app: &Router, path: &str) -> Vec<String> {
+    let (status, body) = get_with(app, path, "application/json").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let listing: serde_json::Value = serde_json::from_str(&body).unwrap();
+    listing["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn listings_sort_and_hide() {
+    let (app, dir) = site("sort");
+    // Directories first; dotfiles, `hide` globs and denied extensions left out.
+    assert_eq!(
+        names(&app, "/mirror/").await,
+        ["pool", "site", "a.iso", "b.tar.gz", "c <&>.txt"]
+    );
+    assert_eq!(
+        names(&app, "/mirror/?sort=size&order=desc").await,
+        ["site", "pool", "a.iso", "b.tar.gz", "c <&>.txt"]
+    );
+    assert_eq!(
+        names(&app, "/mirror/?sort=size").await,
+        ["pool", "site", "c <&>.txt", "b.tar.gz", "a.iso"]
+    );
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn html_listings_escape_names() {
+    let (app, dir) = site("html");
+    let (status, body) = get_with(&app, "/mirror/", "text/html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("c &lt;&amp;&gt;.txt"), "{body}");
+    assert!(!body.contains("c <&>"), "{body}");
+    assert!(body.contains("href=\"c%20%3C%26%3E.txt\""), "{body}");
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn only_listed_prefixes_without_an_index_are_listed() {
+    let (app, dir) = site("scope");
+    assert_eq!(names(&app, "/mirror/pool/").await, ["x.deb"]);
+    // An index.html is served instead.
+    assert_eq!(get_with(&app, "/mirror/site/", "*/*").await.1, "xxxxx");
+    assert_ne!(get_with(&app, "/other/", "*/*").await.0, StatusCode::OK);
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,107 @@
+//! Precompressed siblings (`app.js.br`, `.gz`) and Cache-Control rules.
+
+use std::path::PathBuf;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+/// A site with br/gzip variants and Cache-Control rules, and its root.
+fn site(name: &str) -> (Router, PathBuf) {
+    let dir = std::env::temp_dir().join(format!(
+        "luciuz-precompressed-{}-{name}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (file, content) in [
+        ("app.0123abcd.js", "plain"),
+        ("app.0123abcd.js.br", "brotli"),
+        ("app.0123abcd.js.gz", "gzip"),
+        ("style.css", "css"),
+        ("index.html", "home"),
+        ("docs/index.html", "docs"),
+        ("downloads/v1/tool.tar", "tar"),
+    ] {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+    }
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\ncache_control = \"public, max-age=60\"\n\
+         precompressed = [\"br\", \"gzip\"]\n\n\
+         [[static_site.cache_rules]]\npattern = \"*.[0-9a-f]{{8}}.{{js,css}}\"\n\
+         cache_control = \"public, max-age=31536000, immutable\"\n\n\
+         [[static_site.cache_rules]]\npattern = \"*.html\"\ncache_control = \"no-cache\"\n\n\
+         [[static_site.cache_rules]]\npattern = \"/downloads/**\"\n\
+         cache_control = \"public, max-age=86400\"\n",
+        dir.to_str().unwrap()
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    (luciuz_proxy::static_router(&cfg).unwrap(), dir)
+}
+
+async fn get_with(app: &Router, path: &str, accept_encoding: &str) -> (HeaderMap, String) {
+    let req = Request::get(path)
+        .header(header::ACCEPT_ENCODING, accept_encoding)
+        .body(Body::empty())
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK, "{path}");
+    let headers = res.headers().clone();
+    let body = to_bytes(res.into_body(), 1 << 20).await.unwrap();
+    (headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn variants_follow_accept_encoding() {
+    let (app, dir) = site("variants");
+    for (accept, coding, body) in [
+        ("br, gzip", Some("br"), "brotli"),
+        ("gzip", Some("gzip"), "gzip"),
+        ("identity", None, "plain"),
+    ] {
+        let (headers, got) = get_with(&app, "/app.0123abcd.js", accept).await;
+        assert_eq!(got, body, "{accept}");
+        assert_eq!(
+            headers
+                .get(header::CONTENT_ENCODING)
+                .map(|v| v.to_str().unwrap()),
+            coding,
+            "{accept}"
+        );
+        // The variant keeps the type of the file it stands for.
+        assert!(headers[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/javascript"));
+        assert_eq!(headers[header::VARY], "accept-encoding");
+    }
+    // Without a variant, the file itself is sent.
+    let (headers, got) = get_with(&app, "/style.css", "br").await;
+    assert_eq!(got, "css");
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn the_first_matching_cache_rule_wins() {
+    let (app, dir) = site("rules");
+    for (path, expected) in [
+        ("/app.0123abcd.js", "public, max-age=31536000, immutable"),
+        ("/index.html", "no-cache"),
+        // A directory matches as its index file.
+        ("/docs/", "no-cache"),
+        ("/downloads/v1/tool.tar", "public, max-age=86400"),
+        // Not hashed: falls back to `cache_control`.
+        ("/style.css", "public, max-age=60"),
+    ] {
+        let (headers, _) = get_with(&app, path, "identity").await;
+        assert_eq!(headers[header::CACHE_CONTROL], expected, "{path}");
+    }
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,119 @@
+//! PROXY protocol headers sent to upstreams (`send_proxy_protocol`).
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::connect_info::ConnectInfo,
+    http::{Request, StatusCode},
+    Router,
+};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tower::ServiceExt;
+
+/// An HTTP/1.1 upstream answering each request with the PROXY header of its
+/// connection (the v1 line, or the v2 bytes in hex) and the connection number.
+async fn upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        for conn in 1.. {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                let mut first = [0u8; 12];
+                stream.read_exact(&mut first).await.unwrap();
+                let header = if first.starts_with(b"PROXY ") {
+                    let mut line = String::from_utf8(first.to_vec()).unwrap();
+                    stream.read_line(&mut line).await.unwrap();
+                    line.trim_end().to_string()
+                } else {
+                    let mut rest = [0u8; 4];
+                    stream.read_exact(&mut rest).await.unwrap();
+                    let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+                    let mut addrs = vec![0u8; len];
+                    stream.read_exact(&mut addrs).await.unwrap();
+                    [&first[..], &rest, &addrs]
+                        .concat()
+                        .iter()
+                        .map(|b| format!("{b:02x}"))
+                        .collect()
+                };
+                // Requests have no body: answer each one after its blank line.
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if stream.read_line(&mut line).await.unwrap() == 0 {
+                        return;
+                    }
+                    if line == "\r\n" {
+                        let body = format!("{conn} {header}");
+                        let res = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{body}",
+                            body.len()
+                        );
+                        stream.get_mut().write_all(res.as_bytes()).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+    url
+}
+
+fn proxy(upstream: &str, version: &str) -> Router {
+    let toml = format!(
+        "[server]\nprofile = \"public_api\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\nupstream = \"{upstream}\"\n\
+         send_proxy_protocol = \"{version}\"\n"
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    luciuz_proxy::router(&cfg, "http").unwrap()
+}
+
+/// The upstream's answer to a request from `peer`.
+async fn get_from(app: &Router, peer: &str) -> String {
+    let mut req = Request::get("/api/x").body(Body::empty()).unwrap();
+    req.extensions_mut()
+        .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), 1024).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn v1_announces_the_client() {
+    let app = proxy(&upstream().await, "v1");
+    let answer = get_from(&app, "203.0.113.7:40000").await;
+    // The destination is our side of the upstream connection.
+    let (start, port) = answer.rsplit_once(' ').unwrap();
+    assert_eq!(start, "1 PROXY TCP4 203.0.113.7 127.0.0.1 40000");
+    assert!(port.parse::<u16>().is_ok(), "{answer}");
+}
+
+#[tokio::test]
+async fn connections_are_reused_for_the_same_client_only() {
+    let app = proxy(&upstream().await, "v1");
+    let first = get_from(&app, "203.0.113.7:40000").await;
+    assert_eq!(get_from(&app, "203.0.113.7:40000").await, first);
+
+    let other = get_from(&app, "198.51.100.9:50000").await;
+    assert!(other.starts_with("2 PROXY TCP4 198.51.100.9 "), "{other}");
+}
+
+#[tokio::test]
+async fn v2_sends_the_binary_header() {
+    let app = proxy(&upstream().await, "v2");
+    let answer = get_from(&app, "203.0.113.7:40000").await;
+    let (_, header) = answer.split_once(' ').unwrap();
+    // Signature, PROXY over TCP/IPv4, 12 bytes of addresses.
+    assert!(
+        header.starts_with("0d0a0d0a000d0a515549540a2111000c"),
+        "{header}"
+    );
+    // Source 203.0.113.7, then (after the destination) port 40000.
+    assert_eq!(&header[32..40], "cb007107");
+    assert_eq!(&header[48..52], "9c40");
+}
@@ -0,0 +1,110 @@
+//! Weighted traffic splits and their sticky cookie.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request},
+    routing::get,
+    Router,
+};
+use tower::ServiceExt;
+
+/// An upstream answering every request with `name`.
+async fn upstream(name: &'static str) -> String {
+    let app = Router::new().fallback(get(move || async move { name }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    url
+}
+
+/// A `/api` route split `stable`:`canary` by `weights`, on a `proto` listener.
+async fn split(weights: (u32, u32), proto: &'static str) -> Router {
+    let toml = format!(
+        "[server]\nprofile = \"public_api\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [proxy.pools.stable]\ntargets = [\"{}\"]\n\n\
+         [proxy.pools.canary]\ntargets = [\"{}\"]\n\n\
+         [[proxy.routes]]\nprefix = \"/api\"\n\n\
+         [proxy.routes.split]\n\
+         targets = [{{ pool = \"stable\", weight = {} }}, {{ pool = \"canary\", weight = {} }}]\n\
+         sticky_cookie = \"luciuz_split\"\nsticky_ttl = \"1d\"\n",
+        upstream("stable").await,
+        upstream("canary").await,
+        weights.0,
+        weights.1,
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    luciuz_proxy::router(&cfg, proto).unwrap()
+}
+
+/// The pool that answered, and the cookie set (if any).
+async fn get_api(app: &Router, cookie: Option<&str>) -> (String, Option<String>) {
+    let mut req = Request::get("/api/x");
+    if let Some(cookie) = cookie {
+        req = req.header(header::COOKIE, cookie);
+    }
+    let res = app
+        .clone()
+        .oneshot(req.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let set_cookie = res
+        .headers()
+        .get(header::SET_COOKIE)
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = to_bytes(res.into_body(), 1024).await.unwrap();
+    (String::from_utf8(body.to_vec()).unwrap(), set_cookie)
+}
+
+#[tokio::test]
+async fn traffic_follows_the_weights() {
+    let app = split((3, 1), "http").await;
+    let mut canary = 0;
+    for _ in 0..400 {
+        if get_api(&app, None).await.0 == "canary" {
+            canary += 1;
+        }
+    }
+    // 100 expected; far outside the binomial spread (sd ~8.7) otherwise.
+    assert!(
+        (60..=140).contains(&canary),
+        "{canary} of 400 went to canary"
+    );
+
+    let app = split((1, 0), "http").await;
+    for _ in 0..50 {
+        assert_eq!(get_api(&app, None).await.0, "stable");
+    }
+}
+
+#[tokio::test]
+async fn a_sticky_client_keeps_its_pool() {
+    let app = split((1, 1), "http").await;
+    let (pool, set_cookie) = get_api(&app, None).await;
+    let set_cookie = set_cookie.expect("pin cookie");
+    assert!(set_cookie.starts_with(&format!("luciuz_split={pool}; Path=/api;")));
+
+    let cookie = format!("luciuz_split={pool}");
+    for _ in 0..20 {
+        assert_eq!(get_api(&app, Some(&cookie)).await, (pool.clone(), None));
+    }
+}
+
+#[tokio::test]
+async fn a_pin_to_a_drained_pool_is_replaced() {
+    let app = split((1, 0), "http").await;
+    let (pool, set_cookie) = get_api(&app, Some("luciuz_split=canary")).await;
+    assert_eq!(pool, "stable");
+    assert!(set_cookie.unwrap().starts_with("luciuz_split=stable;"));
+}
+
+#[tokio::test]
+async fn the_cookie_is_secure_over_https_only() {
+    let plain = get_api(&split((1, 1), "http").await, None).await.1.unwrap();
+    assert!(!plain.contains("Secure"), "{plain}");
+    let tls = get_api(&split((1, 1), "https").await, None)
+        .await
+        .1
+        .unwrap();
+    assert!(tls.contains("; Secure;"), "{tls}");
+}
@@ -0,0 +1,149 @@
+//! Files `[static_site]` refuses to serve, and paths that must not leave its root.
+
+use std::path::{Path, PathBuf};
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+/// A site root of its own under the temp dir, holding `files`.
+fn root(name: &str, files: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("luciuz-guard-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for file in files {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, file.as_bytes()).unwrap();
+    }
+    dir
+}
+
+fn site(root: &Path, settings: &str) -> Router {
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\n{settings}",
+        root.to_str().unwrap()
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    luciuz_proxy::static_router(&cfg).unwrap()
+}
+
+/// Status and body of `GET path`.
+async fn get(app: &Router, path: &str) -> (StatusCode, String) {
+    let req = Request::get(path).body(Body::empty()).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), 1 << 20).await.unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+#[tokio::test]
+async fn denied_extensions_cover_compressed_copies() {
+    let dir = root(
+        "compressed",
+        &[
+            "db.bak",
+            "db.bak.gz",
+            "db.BAK.br",
+            "logs.tar.zst",
+            "app.js.gz",
+        ],
+    );
+    let app = site(&dir, "deny_extensions = [\"bak\", \".tar\"]\n");
+    for path in ["/db.bak", "/db.bak.gz", "/db.BAK.br", "/logs.tar.zst"] {
+        assert_eq!(get(&app, path).await.0, StatusCode::NOT_FOUND, "{path}");
+    }
+    assert_eq!(get(&app, "/app.js.gz").await.0, StatusCode::OK);
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn requests_stay_under_the_root() {
+    let outside = root("escape-outside", &["secret.txt"]);
+    let dir = root("escape", &["index.html", "pub/a.txt"]);
+    let app = site(&dir, "");
+    let escape = format!(
+        "/{}/secret.txt",
+        outside.file_name().unwrap().to_str().unwrap()
+    );
+    for path in [
+        format!("/..{escape}"),
+        format!("/pub/../..{escape}"),
+        format!("/pub/..%2f..{}", escape.replace('/', "%2f")),
+        format!("/%2e%2e{escape}"),
+        format!("/pub/%2e%2e%2f%2e%2e{escape}"),
+        format!("/pub%5c..%5c..{}", escape.replace('/', "%5c")),
+    ] {
+        let (status, body) = get(&app, &path).await;
+        assert_ne!(status, StatusCode::OK, "{path}");
+        assert!(!body.contains("secret"), "{path}: {body}");
+    }
+    assert_eq!(
+        get(&app, "/pub/a.txt").await,
+        (StatusCode::OK, "pub/a.txt".into())
+    );
+    std::fs::remove_dir_all(dir).ok();
+    std::fs::remove_dir_all(outside).ok();
+}
+
+#[tokio::test]
+async fn hidden_paths_are_refused_encoded_too() {
+    let dir = root(
+        "hidden",
+        &[".env", ".git/config", ".well-known/security.txt"],
+    );
+    let app = site(&dir, "");
+    for path in ["/.env", "/%2eenv", "/.git/config", "/.git%2fconfig"] {
+        assert_eq!(get(&app, path).await.0, StatusCode::NOT_FOUND, "{path}");
+    }
+    assert_eq!(
+        get(&app, "/.well-known/security.txt").await.0,
+        StatusCode::OK
+    );
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlinks_follow_the_policy() {
+    use std::os::unix::fs::symlink;
+
+    let outside = root("links-outside", &["secret.txt"]);
+    let dir = root("links", &["a.txt"]);
+    symlink(outside.join("secret.txt"), dir.join("out.txt")).unwrap();
+    symlink(dir.join("a.txt"), dir.join("in.txt")).unwrap();
+    symlink(&outside, dir.join("outdir")).unwrap();
+
+    let within = site(&dir, "symlinks = \"within_root_only\"\n");
+    assert_eq!(get(&within, "/out.txt").await.0, StatusCode::NOT_FOUND);
+    assert_eq!(
+        get(&within, "/outdir/secret.txt").await.0,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        get(&within, "/in.txt").await,
+        (StatusCode::OK, "a.txt".into())
+    );
+
+    let deny = site(&dir, "symlinks = \"deny\"\n");
+    for path in ["/out.txt", "/in.txt", "/outdir/secret.txt"] {
+        assert_eq!(get(&deny, path).await.0, StatusCode::NOT_FOUND, "{path}");
+    }
+    assert_eq!(get(&deny, "/a.txt").await.0, StatusCode::OK);
+
+    // Links and targets here all belong to the test's user.
+    let owner = site(&dir, "symlinks = \"owner_match\"\n");
+    assert_eq!(get(&owner, "/out.txt").await.0, StatusCode::OK);
+
+    let follow = site(&dir, "");
+    assert_eq!(
+        get(&follow, "/out.txt").await,
+        (StatusCode::OK, "secret.txt".into())
+    );
+    std::fs::remove_dir_all(dir).ok();
+    std::fs::remove_dir_all(outside).ok();
+}
@@ -0,0 +1,125 @@
+//! `try_files` candidates for single-page apps, and the `not_found` page.
+
+use std::path::PathBuf;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+/// A site serving `/index.html` as the app shell, except under `/assets/`.
+fn site(name: &str, extra: &str) -> (Router, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("luciuz-try-files-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (file, content) in [
+        ("index.html", "shell"),
+        ("about.html", "about"),
+        ("docs/index.html", "docs"),
+        ("assets/app.js", "js"),
+        ("404.html", "missing"),
+    ] {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+    }
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\n{extra}",
+        dir.to_str().unwrap()
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    (luciuz_proxy::static_router(&cfg).unwrap(), dir)
+}
+
+const SPA: &str = "not_found = \"404.html\"\n\
+                   try_files = [\"$uri\", \"$uri.html\", \"$uri/\", \
+                   { path = \"/index.html\", exclude = [\"/assets/\"] }]\n";
+
+async fn get(app: &Router, method: &str, path: &str) -> (StatusCode, String) {
+    let req = Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Body::empty())
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), 1 << 20).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn candidates_are_tried_in_order() {
+    let (app, dir) = site("order", SPA);
+    for (path, expected) in [
+        ("/assets/app.js", "js"),
+        ("/about", "about"),
+        ("/docs", "docs"),
+        ("/dashboard/settings?tab=1", "shell"),
+    ] {
+        assert_eq!(
+            get(&app, "GET", path).await,
+            (StatusCode::OK, expected.to_string()),
+            "{path}"
+        );
+    }
+    // HEAD gets the same status, without a body.
+    assert_eq!(
+        get(&app, "HEAD", "/dashboard").await,
+        (StatusCode::OK, String::new())
+    );
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn excluded_prefixes_get_the_404_page() {
+    let (app, dir) = site("exclude", SPA);
+    assert_eq!(
+        get(&app, "GET", "/assets/missing.js").await,
+        (StatusCode::NOT_FOUND, "missing".to_string())
+    );
+    // The page keeps the type of its own file.
+    let req = Request::get("/assets/missing.js")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.oneshot(req).await.unwrap();
+    assert!(res.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn without_try_files_missing_files_get_the_404_page() {
+    let (app, dir) = site("plain", "not_found = \"404.html\"\n");
+    assert_eq!(
+        get(&app, "GET", "/about.html").await,
+        (StatusCode::OK, "about".to_string())
+    );
+    let (status, body) = get(&app, "GET", "/about").await;
+    assert_eq!((status, body.as_str()), (StatusCode::NOT_FOUND, "missing"));
+
+    std::fs::remove_dir_all(dir).ok();
+
+    let (app, dir) = site("bare", "");
+    assert_eq!(get(&app, "GET", "/about").await.0, StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn a_404_page_outside_root_is_refused() {
+    let dir = std::env::temp_dir().join(format!("luciuz-try-files-{}-outside", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\nnot_found = \"../404.html\"\n",
+        dir.to_str().unwrap()
+    );
+    let (cfg, _) = luciuz_config::check_str("luciuz.toml", &toml);
+    let err = luciuz_proxy::static_router(&cfg.unwrap()).unwrap_err();
+    assert!(err.to_string().contains("must stay inside root"), "{err}");
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,129 @@
+//! The `[static_site.upload]` write API: who may write, and where.
+
+use std::path::PathBuf;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use base64::Engine;
+use tower::ServiceExt;
+
+/// Hash of "s3cret" (`luciuz hash-password`).
+const HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$GaS97Q/p9IqnuK/uzSF1ew$adtUNnZpe1rJ2yAQ2qttJrRt8AG8d5LeuYj1/HyGfXc";
+
+/// A site with uploads under `/_upload` for "designer", its root, and a
+/// directory next to the root that must stay untouched.
+fn site(name: &str) -> (Router, PathBuf, PathBuf) {
+    let base = std::env::temp_dir().join(format!("luciuz-upload-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    let (root, outside) = (base.join("root"), base.join("outside"));
+    std::fs::create_dir_all(root.join("css")).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    let toml = format!(
+        "[server]\nprofile = \"static_site\"\nhttp_listen = \"127.0.0.1:8080\"\n\n\
+         [static_site]\nroot = {:?}\n\n\
+         [static_site.upload]\nprefix = \"/_upload\"\n\n\
+         [[static_site.upload.users]]\nname = \"designer\"\npassword_hash = \"{HASH}\"\n",
+        root.to_str().unwrap()
+    );
+    let (cfg, report) = luciuz_config::check_str("luciuz.toml", &toml);
+    let cfg = cfg.unwrap_or_else(|| panic!("{}", report.render()));
+    (luciuz_proxy::static_router(&cfg).unwrap(), root, outside)
+}
+
+fn basic(user: &str, password: &str) -> String {
+    let token = base64::engine::general_purpose::STANDARD.encode(format!("{user}:{password}"));
+    format!("Basic {token}")
+}
+
+/// Status of `PUT path` with `body`, as `auth`.
+async fn put(app: &Router, path: &str, auth: Option<&str>, body: &'static str) -> StatusCode {
+    let mut req = Request::put(path);
+    if let Some(auth) = auth {
+        req = req.header(header::AUTHORIZATION, auth);
+    }
+    let res = app
+        .clone()
+        .oneshot(req.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+    let status = res.status();
+    to_bytes(res.into_body(), 1024).await.unwrap();
+    status
+}
+
+#[tokio::test]
+async fn writes_need_a_valid_password() {
+    let (app, root, _) = site("auth");
+    let good = basic("designer", "s3cret");
+    assert_eq!(
+        put(&app, "/_upload/css/site.css", Some(&good), "v1").await,
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        put(&app, "/_upload/css/site.css", Some(&good), "v2").await,
+        StatusCode::NO_CONTENT
+    );
+
+    for auth in [
+        None,
+        Some(basic("designer", "wrong")),
+        Some(basic("designer", "")),
+        Some(basic("nobody", "s3cret")),
+        Some("Basic not-base64!".to_string()),
+        Some("Bearer s3cret".to_string()),
+    ] {
+        let req = Request::put("/_upload/css/site.css");
+        let req = match &auth {
+            Some(auth) => req.header(header::AUTHORIZATION, auth),
+            None => req,
+        };
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::from("evil")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{auth:?}");
+        assert!(res.headers().contains_key(header::WWW_AUTHENTICATE));
+    }
+    assert_eq!(
+        std::fs::read_to_string(root.join("css/site.css")).unwrap(),
+        "v2"
+    );
+    std::fs::remove_dir_all(root.parent().unwrap()).ok();
+}
+
+#[tokio::test]
+async fn writes_stay_under_the_root() {
+    let (app, root, outside) = site("traversal");
+    let good = basic("designer", "s3cret");
+    for path in [
+        "/_upload/../outside/x.txt",
+        "/_upload/css/../../outside/x.txt",
+        "/_upload/..%2foutside%2fx.txt",
+        "/_upload/css%2f..%2f..%2foutside%2fx.txt",
+        "/_upload/%2e%2e/outside/x.txt",
+        "/_upload/..%5coutside%5cx.txt",
+    ] {
+        let status = put(&app, path, Some(&good), "escaped").await;
+        assert!(!status.is_success(), "{path}: {status}");
+    }
+    // Hidden names are refused as for reads.
+    assert_eq!(
+        put(&app, "/_upload/.htaccess", Some(&good), "x").await,
+        StatusCode::FORBIDDEN
+    );
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&outside, root.join("out")).unwrap();
+        assert_eq!(
+            put(&app, "/_upload/out/x.txt", Some(&good), "escaped").await,
+            StatusCode::FORBIDDEN
+        );
+    }
+    assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+    std::fs::remove_dir_all(root.parent().unwrap()).ok();
+}
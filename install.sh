@@ -164,6 +164,8 @@ if [[ "$WANT_WWW" == "yes" ]]; then
 fi
 
 cat > /etc/luciuz/luciuz.toml <<EOF
+version = 2
+
 [server]
 http_listen = "${HTTP_LISTEN}"
 https_listen = "${HTTPS_LISTEN}"
@@ -188,7 +190,7 @@ json_logs = true
 log_level = "info"
 
 [timeouts]
-handler_secs = 30
+handler = "30s"
 
 [static_site]
 root = "${STATIC_ROOT}"
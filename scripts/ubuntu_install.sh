@@ -180,6 +180,8 @@ write_config() {
   say "Writing Luciuz config: $config_path"
   install -m 0755 -d "$(dirname "$config_path")"
   {
+    echo "version = 2"
+    echo ""
     echo "[server]"
     echo "http_listen = \"${http_listen}\""
     echo "https_listen = \"${https_listen}\""
@@ -204,7 +206,7 @@ write_config() {
     echo "log_level = \"info\""
     echo ""
     echo "[timeouts]"
-    echo "handler_secs = 30"
+    echo "handler = \"30s\""
     echo ""
     echo "[static_site]"
     echo "root = \"${static_root}\""